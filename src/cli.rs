@@ -0,0 +1,77 @@
+//! Headless command-line mode: sample expressions and render them straight to a PNG.
+
+use crate::{export, function_color, FunctionPlot, ParseOptions, Sampling, PALETTE};
+use clap::Parser;
+use std::path::PathBuf;
+
+/// Render functions to a PNG without opening the GUI.
+#[derive(Parser)]
+#[command(name = "function_plotter_gui", about)]
+pub struct CliArgs {
+    /// Expression in x to plot; repeat for several functions.
+    #[arg(long = "expr", required = true)]
+    pub exprs: Vec<String>,
+    /// Lower bound of the x-domain.
+    #[arg(long, default_value_t = -10.0, allow_negative_numbers = true)]
+    pub xmin: f64,
+    /// Upper bound of the x-domain.
+    #[arg(long, default_value_t = 10.0, allow_negative_numbers = true)]
+    pub xmax: f64,
+    /// Lower bound of the y-range.
+    #[arg(long, default_value_t = -10.0, allow_negative_numbers = true)]
+    pub ymin: f64,
+    /// Upper bound of the y-range.
+    #[arg(long, default_value_t = 10.0, allow_negative_numbers = true)]
+    pub ymax: f64,
+    /// Number of sample points per function.
+    #[arg(long, default_value_t = 1000)]
+    pub points: usize,
+    /// Image width in pixels.
+    #[arg(long, default_value_t = 1920)]
+    pub width: u32,
+    /// Image height in pixels.
+    #[arg(long, default_value_t = 1080)]
+    pub height: u32,
+    /// Stroke width of the curves.
+    #[arg(long, default_value_t = 2.0)]
+    pub line_width: f32,
+    /// Output PNG path.
+    #[arg(long)]
+    pub out: PathBuf,
+}
+
+/// Samples every expression and writes the figure to `args.out`.
+pub fn run(args: &CliArgs) -> Result<(), String> {
+    if !(args.xmin.is_finite() && args.xmax.is_finite() && args.xmin < args.xmax) {
+        return Err("xmin must be less than xmax".to_owned());
+    }
+    if !(args.ymin.is_finite() && args.ymax.is_finite() && args.ymin < args.ymax) {
+        return Err("ymin must be less than ymax".to_owned());
+    }
+
+    let mut functions = Vec::new();
+    for (i, expression) in args.exprs.iter().enumerate() {
+        let mut function = FunctionPlot::new(expression, function_color(&PALETTE, i));
+        function.update(
+            args.xmin,
+            args.xmax,
+            crate::clamp_num_points(args.points),
+            Sampling::Linear,
+            &ParseOptions::default(),
+            &[],
+        );
+        if let Some(ref err) = function.error_message {
+            return Err(format!("{}: {}", expression, err));
+        }
+        functions.push(function);
+    }
+
+    let mut scene = export::Scene {
+        title: String::new(),
+        x_range: (args.xmin, args.xmax),
+        y_range: (args.ymin, args.ymax),
+        curves: crate::export_curves(&functions),
+    };
+    scene.set_line_width(args.line_width);
+    export::write_png(&args.out, &scene, args.width, args.height)
+}
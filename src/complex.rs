@@ -0,0 +1,337 @@
+//! Complex evaluation of parsed expressions for a real variable, so that
+//! functions undefined over the reals (`sqrt(x)` for x < 0, `ln(x)`, ...) can
+//! still be plotted through a real-valued part such as the modulus.
+
+use meval::tokenizer::{Operation, Token};
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex {
+    pub const I: Complex = Complex { re: 0.0, im: 1.0 };
+
+    pub fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+
+    pub fn real(re: f64) -> Self {
+        Self { re, im: 0.0 }
+    }
+
+    pub fn abs(self) -> f64 {
+        self.re.hypot(self.im)
+    }
+
+    pub fn arg(self) -> f64 {
+        self.im.atan2(self.re)
+    }
+
+    fn from_polar(r: f64, theta: f64) -> Self {
+        Self::new(r * theta.cos(), r * theta.sin())
+    }
+
+    fn exp(self) -> Self {
+        Self::from_polar(self.re.exp(), self.im)
+    }
+
+    /// Principal logarithm, with the branch cut along the negative reals.
+    fn ln(self) -> Self {
+        Self::new(self.abs().ln(), self.arg())
+    }
+
+    /// Principal square root.
+    fn sqrt(self) -> Self {
+        Self::from_polar(self.abs().sqrt(), 0.5 * self.arg())
+    }
+
+    fn sin(self) -> Self {
+        Self::new(
+            self.re.sin() * self.im.cosh(),
+            self.re.cos() * self.im.sinh(),
+        )
+    }
+
+    fn cos(self) -> Self {
+        Self::new(
+            self.re.cos() * self.im.cosh(),
+            -self.re.sin() * self.im.sinh(),
+        )
+    }
+
+    fn sinh(self) -> Self {
+        Self::new(
+            self.re.sinh() * self.im.cos(),
+            self.re.cosh() * self.im.sin(),
+        )
+    }
+
+    fn cosh(self) -> Self {
+        Self::new(
+            self.re.cosh() * self.im.cos(),
+            self.re.sinh() * self.im.sin(),
+        )
+    }
+
+    /// asin z = -i ln(iz + sqrt(1 - z²)).
+    fn asin(self) -> Self {
+        let one = Self::real(1.0);
+        -Self::I * (Self::I * self + (one - self * self).sqrt()).ln()
+    }
+
+    /// acos z = π/2 - asin z.
+    fn acos(self) -> Self {
+        Self::real(std::f64::consts::FRAC_PI_2) - self.asin()
+    }
+
+    /// atan z = (i/2) ln((i + z) / (i - z)).
+    fn atan(self) -> Self {
+        Self::new(0.0, 0.5) * ((Self::I + self) / (Self::I - self)).ln()
+    }
+
+    fn powi(self, n: i32) -> Self {
+        let mut result = Self::real(1.0);
+        let mut base = if n < 0 { Self::real(1.0) / self } else { self };
+        let mut n = n.unsigned_abs();
+        while n > 0 {
+            if n & 1 == 1 {
+                result = result * base;
+            }
+            base = base * base;
+            n >>= 1;
+        }
+        result
+    }
+
+    /// Principal power, exp(w ln z); whole real exponents multiply exactly.
+    fn pow(self, w: Self) -> Self {
+        if w.im == 0.0 && w.re.fract() == 0.0 && w.re.abs() <= 1024.0 {
+            return self.powi(w.re as i32);
+        }
+        if self == Self::real(0.0) {
+            return match w.re > 0.0 {
+                true => self,
+                false => Self::new(f64::NAN, f64::NAN),
+            };
+        }
+        (w * self.ln()).exp()
+    }
+}
+
+impl Neg for Complex {
+    type Output = Complex;
+
+    fn neg(self) -> Complex {
+        Complex::new(-self.re, -self.im)
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+
+    fn add(self, other: Complex) -> Complex {
+        Complex::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+
+    fn sub(self, other: Complex) -> Complex {
+        Complex::new(self.re - other.re, self.im - other.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+
+    fn mul(self, other: Complex) -> Complex {
+        Complex::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        )
+    }
+}
+
+impl Div for Complex {
+    type Output = Complex;
+
+    fn div(self, other: Complex) -> Complex {
+        let scale = other.re * other.re + other.im * other.im;
+        Complex::new(
+            (self.re * other.re + self.im * other.im) / scale,
+            (self.im * other.re - self.re * other.im) / scale,
+        )
+    }
+}
+
+/// The real-valued part of a complex result that gets plotted.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ComplexPart {
+    Modulus,
+    Argument,
+    Real,
+    Imaginary,
+}
+
+impl ComplexPart {
+    pub const ALL: [ComplexPart; 4] = [
+        ComplexPart::Modulus,
+        ComplexPart::Argument,
+        ComplexPart::Real,
+        ComplexPart::Imaginary,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ComplexPart::Modulus => "|f(x)|",
+            ComplexPart::Argument => "arg f(x)",
+            ComplexPart::Real => "Re f(x)",
+            ComplexPart::Imaginary => "Im f(x)",
+        }
+    }
+
+    /// How the plotted part of `formula` is written in legends.
+    pub fn wrap(self, formula: &str) -> String {
+        match self {
+            ComplexPart::Modulus => format!("|{}|", formula),
+            ComplexPart::Argument => format!("arg({})", formula),
+            ComplexPart::Real => format!("Re({})", formula),
+            ComplexPart::Imaginary => format!("Im({})", formula),
+        }
+    }
+
+    pub fn of(self, z: Complex) -> f64 {
+        match self {
+            ComplexPart::Modulus => z.abs(),
+            ComplexPart::Argument => z.arg(),
+            ComplexPart::Real => z.re,
+            ComplexPart::Imaginary => z.im,
+        }
+    }
+}
+
+/// One step of a compiled expression, run on a stack of complex values.
+#[derive(Clone, Copy)]
+enum Step {
+    Constant(f64),
+    Variable,
+    Negate,
+    Binary(Operation),
+    Unary(fn(Complex) -> Complex),
+}
+
+/// The complex version of a one-argument function, if there is one.
+fn unary(name: &str) -> Option<fn(Complex) -> Complex> {
+    Some(match name {
+        "sqrt" => Complex::sqrt,
+        "exp" => Complex::exp,
+        "ln" => Complex::ln,
+        "abs" => |z| Complex::real(z.abs()),
+        "sin" => Complex::sin,
+        "cos" => Complex::cos,
+        "tan" => |z| z.sin() / z.cos(),
+        "asin" => Complex::asin,
+        "acos" => Complex::acos,
+        "atan" => Complex::atan,
+        "sinh" => Complex::sinh,
+        "cosh" => Complex::cosh,
+        "tanh" => |z| z.sinh() / z.cosh(),
+        _ => return None,
+    })
+}
+
+/// A parsed expression compiled for complex evaluation.
+pub struct Program {
+    steps: Vec<Step>,
+}
+
+impl Program {
+    /// Compiles meval's RPN tokens. `constant` gives the value of every name
+    /// except the variable. Fails with the name of the first function or
+    /// operator that has no complex version.
+    pub fn compile(
+        tokens: &[Token],
+        constant: &dyn Fn(&str) -> Option<f64>,
+    ) -> Result<Self, String> {
+        let steps = tokens
+            .iter()
+            .map(|token| match token {
+                Token::Number(n) => Ok(Step::Constant(*n)),
+                Token::Var(name) => Ok(constant(name).map_or(Step::Variable, Step::Constant)),
+                Token::Unary(Operation::Minus) => Ok(Step::Negate),
+                Token::Unary(Operation::Plus) => Ok(Step::Unary(|z| z)),
+                Token::Binary(Operation::Rem) => Err("%".to_owned()),
+                Token::Binary(op) => Ok(Step::Binary(*op)),
+                Token::Func(name, arity) if arity.unwrap_or(1) == 1 => {
+                    unary(name).map(Step::Unary).ok_or_else(|| name.clone())
+                }
+                Token::Func(name, _) => Err(name.clone()),
+                _ => Err(format!("{:?}", token)),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { steps })
+    }
+
+    /// The expression's value with the variable set to the real `x`.
+    pub fn eval(&self, x: f64) -> Complex {
+        let nan = Complex::new(f64::NAN, f64::NAN);
+        let mut stack: Vec<Complex> = Vec::new();
+        for step in &self.steps {
+            let value = match *step {
+                Step::Constant(c) => Complex::real(c),
+                Step::Variable => Complex::real(x),
+                Step::Negate => -stack.pop().unwrap_or(nan),
+                Step::Unary(f) => f(stack.pop().unwrap_or(nan)),
+                Step::Binary(op) => {
+                    let right = stack.pop().unwrap_or(nan);
+                    let left = stack.pop().unwrap_or(nan);
+                    match op {
+                        Operation::Plus => left + right,
+                        Operation::Minus => left - right,
+                        Operation::Times => left * right,
+                        Operation::Div => left / right,
+                        Operation::Pow => left.pow(right),
+                        // Rejected when compiling.
+                        _ => nan,
+                    }
+                }
+            };
+            stack.push(value);
+        }
+        stack.pop().unwrap_or(nan)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(text: &str, x: f64) -> Complex {
+        let expr: meval::Expr = text.parse().unwrap();
+        Program::compile(&expr, &|_| None).unwrap().eval(x)
+    }
+
+    fn close(a: Complex, b: Complex) -> bool {
+        (a - b).abs() < 1e-12
+    }
+
+    #[test]
+    fn functions_extend_past_the_reals() {
+        assert!(close(eval("sqrt(x)", -4.0), Complex::new(0.0, 2.0)));
+        let ln = eval("ln(x)", -1.0);
+        assert!(close(ln, Complex::new(0.0, std::f64::consts::PI)));
+        assert!(close(eval("x^2 - 1", 3.0), Complex::real(8.0)));
+        assert!(close(eval("asin(x)", 0.5), Complex::real(0.5f64.asin())));
+        // |(-8)^(1/3)| is 2 whichever root is principal.
+        assert!((eval("x^(1/3)", -8.0).abs() - 2.0).abs() < 1e-12);
+        let expr: meval::Expr = "floor(x)".parse().unwrap();
+        assert_eq!(
+            Program::compile(&expr, &|_| None).err().as_deref(),
+            Some("floor")
+        );
+    }
+}
@@ -0,0 +1,122 @@
+//! Measured (x, y) data series: reading them from text files and smoothing them.
+
+use std::path::Path;
+
+/// Parses (x, y) pairs, one per line, separated by commas, semicolons, tabs or spaces.
+///
+/// Blank lines and lines starting with `#` are skipped, as is a first line
+/// that isn't numeric (a column header). Columns after the second are ignored.
+pub fn parse_points(text: &str) -> Result<Vec<[f64; 2]>, String> {
+    let mut points = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut fields = line
+            .split(|c: char| c == ',' || c == ';' || c.is_whitespace())
+            .filter(|field| !field.is_empty())
+            .map(|field| field.parse::<f64>());
+        match (fields.next(), fields.next()) {
+            (Some(Ok(x)), Some(Ok(y))) if x.is_finite() && y.is_finite() => points.push([x, y]),
+            _ if points.is_empty() && number == 0 => {}
+            _ => return Err(format!("line {}: expected two numbers", number + 1)),
+        }
+    }
+    if points.is_empty() {
+        return Err("no data points found".to_owned());
+    }
+    Ok(points)
+}
+
+/// Reads a data file, see `parse_points` for the format.
+pub fn load_points(path: &Path) -> Result<Vec<[f64; 2]>, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    parse_points(&text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Centered moving average of the y values over `window` neighbouring points.
+///
+/// An even window reaches one point further ahead than behind. Near the ends
+/// the window shrinks to the points that exist; x values are kept.
+pub fn moving_average(points: &[[f64; 2]], window: usize) -> Vec<[f64; 2]> {
+    let window = window.max(1);
+    let (before, after) = ((window - 1) / 2, window / 2);
+    (0..points.len())
+        .map(|i| {
+            let neighbours = &points[i.saturating_sub(before)..(i + after + 1).min(points.len())];
+            let mean = neighbours.iter().map(|p| p[1]).sum::<f64>() / neighbours.len() as f64;
+            [points[i][0], mean]
+        })
+        .collect()
+}
+
+/// Summary of the residuals between a data series and a function.
+#[derive(Debug, PartialEq)]
+pub struct ResidualStats {
+    pub mean: f64,
+    pub rms: f64,
+    /// Largest absolute residual.
+    pub max: f64,
+}
+
+/// Mean, RMS and largest magnitude of `residuals`, or `None` if there are none.
+pub fn residual_stats(residuals: &[f64]) -> Option<ResidualStats> {
+    if residuals.is_empty() {
+        return None;
+    }
+    let n = residuals.len() as f64;
+    Some(ResidualStats {
+        mean: residuals.iter().sum::<f64>() / n,
+        rms: (residuals.iter().map(|r| r * r).sum::<f64>() / n).sqrt(),
+        max: residuals.iter().fold(0.0, |max, r| f64::max(max, r.abs())),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn points_are_parsed_with_any_separator() {
+        let text = "x,y\n# comment\n0, 1\n1;2\n\n2\t3 extra\n";
+        assert_eq!(
+            parse_points(text).unwrap(),
+            vec![[0.0, 1.0], [1.0, 2.0], [2.0, 3.0]]
+        );
+        assert_eq!(
+            parse_points("0 1\nfoo bar").unwrap_err(),
+            "line 2: expected two numbers"
+        );
+        assert!(parse_points("# nothing\n").is_err());
+    }
+
+    #[test]
+    fn residual_stats_summarize() {
+        assert_eq!(
+            residual_stats(&[1.0, -1.0, 3.0, -3.0]),
+            Some(ResidualStats {
+                mean: 0.0,
+                rms: 5f64.sqrt(),
+                max: 3.0
+            })
+        );
+        assert_eq!(residual_stats(&[]), None);
+    }
+
+    #[test]
+    fn moving_average_shrinks_at_the_ends() {
+        let points = [[0.0, 0.0], [1.0, 3.0], [2.0, 0.0], [3.0, 3.0]];
+        assert_eq!(
+            moving_average(&points, 3),
+            vec![[0.0, 1.5], [1.0, 1.0], [2.0, 2.0], [3.0, 1.5]]
+        );
+        assert_eq!(moving_average(&points, 1), points.to_vec());
+        // An even window averages exactly that many points.
+        assert_eq!(
+            moving_average(&points, 4),
+            vec![[0.0, 1.0], [1.0, 1.5], [2.0, 2.0], [3.0, 1.5]]
+        );
+    }
+}
@@ -0,0 +1,676 @@
+//! Offscreen rendering of the plotted curves to image files and archives.
+
+use eframe::egui;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// A curve captured for export.
+#[derive(Clone)]
+pub struct ExportCurve {
+    pub points: Vec<[f64; 2]>,
+    pub color: egui::Color32,
+    pub width: f32,
+}
+
+/// Everything needed to render a figure.
+///
+/// A scene is captured when an export starts, so the live UI can keep changing
+/// while a background thread writes the file.
+#[derive(Clone)]
+pub struct Scene {
+    pub title: String,
+    pub x_range: (f64, f64),
+    pub y_range: (f64, f64),
+    pub curves: Vec<ExportCurve>,
+}
+
+impl Scene {
+    /// Gives every curve the same stroke width, for figures with thicker lines.
+    pub fn set_line_width(&mut self, width: f32) {
+        for curve in &mut self.curves {
+            curve.width = width;
+        }
+    }
+}
+
+/// Maps plot coordinates onto a pixel canvas whose y-axis points down.
+pub struct Viewport {
+    x_range: (f64, f64),
+    y_range: (f64, f64),
+    width: f64,
+    height: f64,
+}
+
+impl Viewport {
+    pub fn new(scene: &Scene, width: u32, height: u32) -> Self {
+        Self {
+            x_range: scene.x_range,
+            y_range: scene.y_range,
+            width: width as f64,
+            height: height as f64,
+        }
+    }
+
+    /// Converts a plot coordinate into canvas pixels.
+    pub fn to_screen(&self, [x, y]: [f64; 2]) -> [f64; 2] {
+        let (x0, x1) = self.x_range;
+        let (y0, y1) = self.y_range;
+        [
+            (x - x0) / (x1 - x0) * self.width,
+            (y1 - y) / (y1 - y0) * self.height,
+        ]
+    }
+}
+
+/// Spacing between gridlines that splits `span` into roughly `target` parts,
+/// rounded to 1, 2 or 5 times a power of ten.
+pub fn grid_step(span: f64, target: f64) -> f64 {
+    let raw = span / target;
+    let magnitude = 10f64.powf(raw.log10().floor());
+    let step = [1.0, 2.0, 5.0, 10.0]
+        .into_iter()
+        .find(|m| m * magnitude >= raw)
+        .unwrap_or(10.0);
+    step * magnitude
+}
+
+/// Multiples of `step` that lie within `[min, max]`.
+pub fn grid_lines(min: f64, max: f64, step: f64) -> Vec<f64> {
+    let first = (min / step).ceil() as i64;
+    let last = (max / step).floor() as i64;
+    (first..=last).map(|k| k as f64 * step).collect()
+}
+
+/// Formats a tick value with just enough decimals for the grid `step`.
+pub fn format_tick(value: f64, step: f64) -> String {
+    let decimals = (-step.log10().floor()).max(0.0) as usize;
+    // Avoid printing "-0".
+    let value = if value.abs() < step * 1e-6 {
+        0.0
+    } else {
+        value
+    };
+    format!("{:.*}", decimals, value)
+}
+
+/// 5x7 bitmap glyphs for the characters that appear in tick labels.
+fn glyph(c: char) -> Option<[u8; 7]> {
+    Some(match c {
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        '+' => [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        'e' => [0x00, 0x00, 0x0E, 0x11, 0x1F, 0x10, 0x0E],
+        _ => return None,
+    })
+}
+
+/// Strokes wider than this many pixels are tessellated into triangles before
+/// being filled; thinner ones are drawn from their distance to the polyline.
+const TESSELLATE_WIDTH: f32 = 3.0;
+
+/// Subsamples per pixel side when filling triangles; the coverage of all of
+/// them fits in a `u16`.
+const SUBSAMPLES: usize = 4;
+
+/// Tessellates a stroke of half-width `half` along `points` into triangles:
+/// a quad per segment, a fan over the outside of every turn for round joins,
+/// and a half disc at either end for round caps.
+pub fn tessellate_polyline(points: &[[f64; 2]], half: f64) -> Vec<[[f64; 2]; 3]> {
+    use std::f64::consts::{PI, TAU};
+    let mut points = points.to_vec();
+    points.dedup();
+    // Enough fan steps per turn that arcs stay within a quarter pixel of the circle.
+    let step = TAU / (PI / (1.0 - 0.25 / half.max(0.5)).acos()).ceil().max(8.0);
+    let mut triangles = Vec::new();
+    let mut fan = |[cx, cy]: [f64; 2], start: f64, sweep: f64| {
+        let steps = (sweep.abs() / step).ceil().max(1.0) as usize;
+        let rim = |k: usize| {
+            let angle = start + sweep * k as f64 / steps as f64;
+            [cx + half * angle.cos(), cy + half * angle.sin()]
+        };
+        for k in 0..steps {
+            triangles.push([[cx, cy], rim(k), rim(k + 1)]);
+        }
+    };
+    // Direction of each segment, as an angle.
+    let directions: Vec<f64> = points
+        .windows(2)
+        .map(|w| (w[1][1] - w[0][1]).atan2(w[1][0] - w[0][0]))
+        .collect();
+    let (Some(&first), Some(&last)) = (directions.first(), directions.last()) else {
+        if let Some(&point) = points.first() {
+            fan(point, 0.0, TAU);
+        }
+        return triangles;
+    };
+    // Each cap sweeps from one side of the stroke around its end to the other.
+    fan(points[0], first + PI / 2.0, PI);
+    fan(points[points.len() - 1], last - PI / 2.0, PI);
+    for (i, pair) in directions.windows(2).enumerate() {
+        let turn = (pair[1] - pair[0] + PI).rem_euclid(TAU) - PI;
+        if turn != 0.0 {
+            // The gap opens on the outside: the right of a left turn, and vice versa.
+            let outside = if turn > 0.0 { -PI / 2.0 } else { PI / 2.0 };
+            fan(points[i + 1], pair[0] + outside, turn);
+        }
+    }
+    for (segment, &direction) in points.windows(2).zip(&directions) {
+        let (nx, ny) = (-direction.sin() * half, direction.cos() * half);
+        let ([ax, ay], [bx, by]) = (segment[0], segment[1]);
+        let [p, q, r, s] = [
+            [ax + nx, ay + ny],
+            [bx + nx, by + ny],
+            [bx - nx, by - ny],
+            [ax - nx, ay - ny],
+        ];
+        triangles.push([p, q, r]);
+        triangles.push([p, r, s]);
+    }
+    triangles
+}
+
+/// An RGBA8 pixel buffer with simple antialiased drawing primitives.
+pub struct Canvas {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Canvas {
+    pub fn new(width: u32, height: u32, background: egui::Color32) -> Self {
+        let pixels = background
+            .to_srgba_unmultiplied()
+            .repeat((width * height) as usize);
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Alpha-blends `color` into the pixel at (x, y) with the given coverage.
+    fn blend(&mut self, x: i64, y: i64, color: egui::Color32, coverage: f32) {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            return;
+        }
+        let [r, g, b, a] = color.to_srgba_unmultiplied();
+        let alpha = coverage.clamp(0.0, 1.0) * a as f32 / 255.0;
+        let i = ((y * self.width as i64 + x) * 4) as usize;
+        for (channel, value) in [r, g, b].into_iter().enumerate() {
+            let old = self.pixels[i + channel] as f32;
+            self.pixels[i + channel] = (old + (value as f32 - old) * alpha).round() as u8;
+        }
+        self.pixels[i + 3] = 255;
+    }
+
+    /// Draws a horizontal or vertical hairline across the whole canvas.
+    fn axis_line(&mut self, position: f64, vertical: bool, color: egui::Color32) {
+        let p = position.round() as i64;
+        let length = if vertical { self.height } else { self.width } as i64;
+        for t in 0..length {
+            if vertical {
+                self.blend(p, t, color, 1.0);
+            } else {
+                self.blend(t, p, color, 1.0);
+            }
+        }
+    }
+
+    /// Draws a polyline of the given stroke width with round joins.
+    ///
+    /// Strokes wider than `TESSELLATE_WIDTH` are filled as triangles from
+    /// `tessellate_polyline`. Thinner ones accumulate coverage per pixel (max
+    /// over segments) before blending, so overlapping segment ends don't
+    /// darken the joints.
+    pub fn draw_polyline(&mut self, points: &[[f64; 2]], color: egui::Color32, width: f32) {
+        if width > TESSELLATE_WIDTH {
+            self.fill_triangles(&tessellate_polyline(points, width as f64 / 2.0), color);
+            return;
+        }
+        let mut coverage = vec![0u8; (self.width * self.height) as usize];
+        let half = (width as f64 / 2.0).max(0.5);
+        for segment in points.windows(2) {
+            let ([ax, ay], [bx, by]) = (segment[0], segment[1]);
+            let x0 = (ax.min(bx) - half - 1.0).floor().max(0.0) as i64;
+            let x1 = (ax.max(bx) + half + 1.0)
+                .ceil()
+                .min(self.width as f64 - 1.0) as i64;
+            let y0 = (ay.min(by) - half - 1.0).floor().max(0.0) as i64;
+            let y1 = (ay.max(by) + half + 1.0)
+                .ceil()
+                .min(self.height as f64 - 1.0) as i64;
+            let (dx, dy) = (bx - ax, by - ay);
+            let len2 = dx * dx + dy * dy;
+            for py in y0..=y1 {
+                for px in x0..=x1 {
+                    let (cx, cy) = (px as f64 + 0.5, py as f64 + 0.5);
+                    let t = if len2 > 0.0 {
+                        (((cx - ax) * dx + (cy - ay) * dy) / len2).clamp(0.0, 1.0)
+                    } else {
+                        0.0
+                    };
+                    let distance = (cx - (ax + t * dx)).hypot(cy - (ay + t * dy));
+                    let c = ((half + 0.5 - distance).clamp(0.0, 1.0) * 255.0) as u8;
+                    let slot = &mut coverage[(py * self.width as i64 + px) as usize];
+                    *slot = (*slot).max(c);
+                }
+            }
+        }
+        for (i, &c) in coverage.iter().enumerate() {
+            if c > 0 {
+                let x = (i % self.width as usize) as i64;
+                let y = (i / self.width as usize) as i64;
+                self.blend(x, y, color, c as f32 / 255.0);
+            }
+        }
+    }
+
+    /// Fills the union of `triangles`, antialiased by testing `SUBSAMPLES`²
+    /// points per pixel. The points covered by any triangle are merged before
+    /// blending, so overlaps don't darken and shared edges leave no seams.
+    fn fill_triangles(&mut self, triangles: &[[[f64; 2]; 3]], color: egui::Color32) {
+        let mut masks = vec![0u16; (self.width * self.height) as usize];
+        // Twice the signed area of (p, q, s); its sign tells the side of pq that s is on.
+        let edge = |p: [f64; 2], q: [f64; 2], s: [f64; 2]| {
+            (q[0] - p[0]) * (s[1] - p[1]) - (q[1] - p[1]) * (s[0] - p[0])
+        };
+        for &[a, b, c] in triangles {
+            let area = edge(a, b, c);
+            if area == 0.0 || !area.is_finite() {
+                continue;
+            }
+            let [x0, y0] = [0, 1].map(|k| a[k].min(b[k]).min(c[k]).floor().max(0.0));
+            let x1 = a[0].max(b[0]).max(c[0]).ceil().min(self.width as f64 - 1.0);
+            let y1 = a[1]
+                .max(b[1])
+                .max(c[1])
+                .ceil()
+                .min(self.height as f64 - 1.0);
+            for py in y0 as i64..=y1 as i64 {
+                for px in x0 as i64..=x1 as i64 {
+                    let mut mask = 0u16;
+                    for k in 0..SUBSAMPLES * SUBSAMPLES {
+                        let s = [
+                            px as f64 + ((k % SUBSAMPLES) as f64 + 0.5) / SUBSAMPLES as f64,
+                            py as f64 + ((k / SUBSAMPLES) as f64 + 0.5) / SUBSAMPLES as f64,
+                        ];
+                        if [edge(a, b, s), edge(b, c, s), edge(c, a, s)]
+                            .iter()
+                            .all(|&e| e * area >= 0.0)
+                        {
+                            mask |= 1 << k;
+                        }
+                    }
+                    masks[(py * self.width as i64 + px) as usize] |= mask;
+                }
+            }
+        }
+        let samples = (SUBSAMPLES * SUBSAMPLES) as f32;
+        for (i, &mask) in masks.iter().enumerate() {
+            if mask != 0 {
+                let x = (i % self.width as usize) as i64;
+                let y = (i / self.width as usize) as i64;
+                self.blend(x, y, color, mask.count_ones() as f32 / samples);
+            }
+        }
+    }
+
+    /// Draws `text` with its top-left corner at (x, y) using the built-in bitmap font.
+    fn draw_text(&mut self, x: i64, y: i64, text: &str, color: egui::Color32, scale: i64) {
+        for (n, c) in text.chars().enumerate() {
+            let Some(rows) = glyph(c) else { continue };
+            let origin = x + n as i64 * 6 * scale;
+            for (row, bits) in rows.iter().enumerate() {
+                for col in 0..5 {
+                    if bits & (0x10 >> col) == 0 {
+                        continue;
+                    }
+                    for sy in 0..scale {
+                        for sx in 0..scale {
+                            self.blend(
+                                origin + col * scale + sx,
+                                y + row as i64 * scale + sy,
+                                color,
+                                1.0,
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Renders the scene (grid, axes with tick labels, curves) into an RGBA canvas.
+pub fn render(scene: &Scene, width: u32, height: u32) -> Canvas {
+    let mut canvas = Canvas::new(width, height, egui::Color32::WHITE);
+    let view = Viewport::new(scene, width, height);
+    let scale = (height as i64 / 400).max(1);
+    let grid = egui::Color32::from_gray(225);
+    let axis = egui::Color32::from_gray(110);
+    let label = egui::Color32::from_gray(60);
+
+    let (x0, x1) = scene.x_range;
+    let (y0, y1) = scene.y_range;
+    let x_step = grid_step(x1 - x0, 10.0);
+    let y_step = grid_step(y1 - y0, 10.0);
+    let x_lines = grid_lines(x0, x1, x_step);
+    let y_lines = grid_lines(y0, y1, y_step);
+
+    for &x in &x_lines {
+        canvas.axis_line(view.to_screen([x, y0])[0], true, grid);
+    }
+    for &y in &y_lines {
+        canvas.axis_line(view.to_screen([x0, y])[1], false, grid);
+    }
+    if x0 <= 0.0 && 0.0 <= x1 {
+        canvas.axis_line(view.to_screen([0.0, y0])[0], true, axis);
+    }
+    if y0 <= 0.0 && 0.0 <= y1 {
+        canvas.axis_line(view.to_screen([x0, 0.0])[1], false, axis);
+    }
+
+    for curve in &scene.curves {
+        let screen: Vec<[f64; 2]> = curve.points.iter().map(|&p| view.to_screen(p)).collect();
+        canvas.draw_polyline(&screen, curve.color, curve.width * scale as f32);
+    }
+
+    // Tick labels along the bottom and left edges, drawn last so they stay readable.
+    let margin = 4 * scale;
+    let text_height = 7 * scale;
+    for &x in &x_lines {
+        let px = view.to_screen([x, y0])[0].round() as i64;
+        canvas.draw_text(
+            px + margin,
+            height as i64 - text_height - margin,
+            &format_tick(x, x_step),
+            label,
+            scale,
+        );
+    }
+    for &y in &y_lines {
+        let py = view.to_screen([x0, y])[1].round() as i64;
+        canvas.draw_text(
+            margin,
+            py - text_height - margin,
+            &format_tick(y, y_step),
+            label,
+            scale,
+        );
+    }
+    canvas
+}
+
+/// Renders the scene and writes it to `path` as a PNG, storing the title as metadata.
+pub fn write_png(path: &Path, scene: &Scene, width: u32, height: u32) -> Result<(), String> {
+    if width == 0 || height == 0 {
+        return Err("Image size must be non-zero".to_owned());
+    }
+    let file =
+        File::create(path).map_err(|e| format!("Cannot create {}: {}", path.display(), e))?;
+    encode_png(BufWriter::new(file), scene, width, height)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Renders the scene as a PNG into `out`.
+fn encode_png(out: impl Write, scene: &Scene, width: u32, height: u32) -> Result<(), String> {
+    let canvas = render(scene, width, height);
+    let mut encoder = png::Encoder::new(out, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    if !scene.title.is_empty() {
+        encoder
+            .add_text_chunk("Title".to_owned(), scene.title.clone())
+            .map_err(|e| e.to_string())?;
+    }
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer
+        .write_image_data(&canvas.pixels)
+        .map_err(|e| e.to_string())
+}
+
+/// The sampled points of every curve as CSV, one `curve,x,y` row per point
+/// with curves numbered from 1.
+pub fn curves_csv(scene: &Scene) -> String {
+    let mut csv = String::from("curve,x,y\n");
+    for (i, curve) in scene.curves.iter().enumerate() {
+        for [x, y] in &curve.points {
+            let _ = writeln!(csv, "{},{},{}", i + 1, x, y);
+        }
+    }
+    csv
+}
+
+/// Writes a zip archive holding the session (`session.json`), the curve
+/// data (`data.csv`) and the rendered figure (`plot.png`).
+pub fn write_archive(
+    path: &Path,
+    scene: &Scene,
+    session_json: &str,
+    width: u32,
+    height: u32,
+) -> Result<(), String> {
+    if width == 0 || height == 0 {
+        return Err("Image size must be non-zero".to_owned());
+    }
+    let file =
+        File::create(path).map_err(|e| format!("Cannot create {}: {}", path.display(), e))?;
+    let failed = |e: &dyn std::fmt::Display| format!("Failed to write {}: {}", path.display(), e);
+    let mut zip = zip::ZipWriter::new(BufWriter::new(file));
+    let options = zip::write::FileOptions::default();
+    for (name, contents) in [
+        ("session.json", session_json),
+        ("data.csv", &curves_csv(scene)),
+    ] {
+        zip.start_file(name, options).map_err(|e| failed(&e))?;
+        zip.write_all(contents.as_bytes()).map_err(|e| failed(&e))?;
+    }
+    // PNG data is already compressed.
+    let stored = options.compression_method(zip::CompressionMethod::Stored);
+    zip.start_file("plot.png", stored).map_err(|e| failed(&e))?;
+    encode_png(&mut zip, scene, width, height).map_err(|e| failed(&e))?;
+    zip.finish().map_err(|e| failed(&e))?;
+    Ok(())
+}
+
+/// Formats a color as an SVG `#rrggbb` string.
+fn svg_color(color: egui::Color32) -> String {
+    let [r, g, b, _] = color.to_srgba_unmultiplied();
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+/// Escapes text for use inside SVG markup.
+fn svg_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Renders the scene (grid, axes with tick labels, curves) as an SVG document.
+pub fn render_svg(scene: &Scene, width: u32, height: u32) -> String {
+    let view = Viewport::new(scene, width, height);
+    let (x0, x1) = scene.x_range;
+    let (y0, y1) = scene.y_range;
+    let x_step = grid_step(x1 - x0, 10.0);
+    let y_step = grid_step(y1 - y0, 10.0);
+    let x_lines = grid_lines(x0, x1, x_step);
+    let y_lines = grid_lines(y0, y1, y_step);
+    let (w, h) = (width as f64, height as f64);
+
+    // `write!` into a String cannot fail, so the results are ignored below.
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#
+    );
+    if !scene.title.is_empty() {
+        let _ = writeln!(svg, "<title>{}</title>", svg_escape(&scene.title));
+    }
+    let _ = writeln!(
+        svg,
+        r#"<defs><clipPath id="plot-area"><rect width="{w}" height="{h}"/></clipPath></defs>"#
+    );
+    let _ = writeln!(svg, r#"<rect width="{w}" height="{h}" fill="white"/>"#);
+
+    let _ = writeln!(svg, r##"<g stroke="#e1e1e1" stroke-width="1">"##);
+    for &x in &x_lines {
+        let px = view.to_screen([x, y0])[0];
+        let _ = writeln!(svg, r#"<line x1="{px:.2}" y1="0" x2="{px:.2}" y2="{h}"/>"#);
+    }
+    for &y in &y_lines {
+        let py = view.to_screen([x0, y])[1];
+        let _ = writeln!(svg, r#"<line x1="0" y1="{py:.2}" x2="{w}" y2="{py:.2}"/>"#);
+    }
+    let _ = writeln!(svg, "</g>");
+
+    let _ = writeln!(svg, r##"<g stroke="#6e6e6e" stroke-width="1">"##);
+    if x0 <= 0.0 && 0.0 <= x1 {
+        let px = view.to_screen([0.0, y0])[0];
+        let _ = writeln!(svg, r#"<line x1="{px:.2}" y1="0" x2="{px:.2}" y2="{h}"/>"#);
+    }
+    if y0 <= 0.0 && 0.0 <= y1 {
+        let py = view.to_screen([x0, 0.0])[1];
+        let _ = writeln!(svg, r#"<line x1="0" y1="{py:.2}" x2="{w}" y2="{py:.2}"/>"#);
+    }
+    let _ = writeln!(svg, "</g>");
+
+    let _ = writeln!(
+        svg,
+        r#"<g clip-path="url(#plot-area)" fill="none" stroke-linejoin="round" stroke-linecap="round">"#
+    );
+    for curve in &scene.curves {
+        let mut d = String::new();
+        for (i, &point) in curve.points.iter().enumerate() {
+            // Keep far off-screen values bounded so the file stays readable.
+            let [px, py] = view.to_screen(point).map(|v| v.clamp(-1e6, 1e6));
+            let _ = write!(d, "{}{:.2} {:.2} ", if i == 0 { "M" } else { "L" }, px, py);
+        }
+        let _ = writeln!(
+            svg,
+            r#"<path d="{}" stroke="{}" stroke-width="{}"/>"#,
+            d.trim_end(),
+            svg_color(curve.color),
+            curve.width
+        );
+    }
+    let _ = writeln!(svg, "</g>");
+
+    let _ = writeln!(
+        svg,
+        r##"<g font-family="sans-serif" font-size="11" fill="#3c3c3c">"##
+    );
+    for &x in &x_lines {
+        let px = view.to_screen([x, y0])[0] + 4.0;
+        let _ = writeln!(
+            svg,
+            r#"<text x="{px:.2}" y="{:.2}">{}</text>"#,
+            h - 4.0,
+            format_tick(x, x_step)
+        );
+    }
+    for &y in &y_lines {
+        let py = view.to_screen([x0, y])[1] - 4.0;
+        let _ = writeln!(
+            svg,
+            r#"<text x="4" y="{py:.2}">{}</text>"#,
+            format_tick(y, y_step)
+        );
+    }
+    let _ = writeln!(svg, "</g>");
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Renders the scene and writes it to `path` as an SVG document.
+pub fn write_svg(path: &Path, scene: &Scene, width: u32, height: u32) -> Result<(), String> {
+    if width == 0 || height == 0 {
+        return Err("Image size must be non-zero".to_owned());
+    }
+    std::fs::write(path, render_svg(scene, width, height))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn thick_polylines_have_round_joins() {
+        let mut canvas = Canvas::new(100, 100, egui::Color32::WHITE);
+        let corner = [[20.0, 90.0], [50.0, 30.0], [80.0, 90.0]];
+        canvas.draw_polyline(&corner, egui::Color32::BLACK, 20.0);
+        let red = |x: usize, y: usize| canvas.pixels[(y * 100 + x) * 4];
+        // The join covers 8 px above the corner but not 13 px, where a miter
+        // would still be painted (it reaches 22 px for this angle).
+        assert_eq!(red(50, 22), 0);
+        assert_eq!(red(50, 17), 255);
+    }
+
+    #[test]
+    fn tessellated_strokes_leave_no_seams() {
+        let line = [[10.0, 50.0], [50.0, 50.0], [90.0, 50.0]];
+        // Quads for both segments and caps at the ends; a straight joint
+        // needs no fan, and nothing reaches past the stroke.
+        let triangles = tessellate_polyline(&line, 5.0);
+        assert!(triangles
+            .iter()
+            .flatten()
+            .all(|&[x, y]| { (4.99..=95.01).contains(&x) && (44.99..=55.01).contains(&y) }));
+
+        let mut canvas = Canvas::new(100, 100, egui::Color32::WHITE);
+        canvas.draw_polyline(&line, egui::Color32::BLACK, 10.0);
+        let red = |x: usize, y: usize| canvas.pixels[(y * 100 + x) * 4];
+        // Fully covered along the diagonals inside the quads and at the joint.
+        assert!((45..55).all(|y| red(30, y) == 0 && red(50, y) == 0));
+        assert_eq!(red(30, 55), 255);
+        assert_eq!(red(30, 44), 255);
+    }
+
+    #[test]
+    fn archive_holds_session_data_and_image() {
+        let scene = Scene {
+            title: String::new(),
+            x_range: (0.0, 1.0),
+            y_range: (0.0, 1.0),
+            curves: vec![ExportCurve {
+                points: vec![[0.0, 0.0], [1.0, 0.5]],
+                color: egui::Color32::RED,
+                width: 2.0,
+            }],
+        };
+        // Unique per process, so concurrent test runs don't share the file.
+        let path = std::env::temp_dir().join(format!(
+            "function_plotter_archive_test_{}.zip",
+            std::process::id()
+        ));
+        write_archive(&path, &scene, "{}", 16, 16).unwrap();
+
+        let mut archive = zip::ZipArchive::new(File::open(&path).unwrap()).unwrap();
+        let mut csv = String::new();
+        archive
+            .by_name("data.csv")
+            .unwrap()
+            .read_to_string(&mut csv)
+            .unwrap();
+        let has_session = archive.by_name("session.json").is_ok();
+        let png_size = archive.by_name("plot.png").map_or(0, |png| png.size());
+        // Removed before checking, so a failure doesn't leave it behind.
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(csv, "curve,x,y\n1,0,0\n1,1,0.5\n");
+        assert!(has_session);
+        assert!(png_size > 0);
+    }
+}
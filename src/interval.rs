@@ -0,0 +1,463 @@
+//! Interval arithmetic over parsed expressions: bounds on every value a
+//! function takes across a range of inputs, so that thin spikes between two
+//! samples can't go unnoticed.
+//!
+//! Results are widened outwards after every operation, by one ulp for the
+//! arithmetic and by a few for the library functions, which aren't correctly
+//! rounded; the bounds are reliable but not the tightest possible.
+
+use meval::tokenizer::{Operation, Token};
+use std::f64::consts::{FRAC_PI_2, PI, TAU};
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+/// Ulps by which the results of library functions such as `sin` are widened.
+const LIBRARY_ULPS: usize = 4;
+
+/// A closed range of reals, `lo..=hi`; empty when `lo > hi`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Interval {
+    pub lo: f64,
+    pub hi: f64,
+}
+
+impl Interval {
+    pub const EMPTY: Interval = Interval {
+        lo: f64::INFINITY,
+        hi: f64::NEG_INFINITY,
+    };
+    pub const ENTIRE: Interval = Interval {
+        lo: f64::NEG_INFINITY,
+        hi: f64::INFINITY,
+    };
+
+    /// The interval between `a` and `b`, in either order.
+    pub fn new(a: f64, b: f64) -> Self {
+        Self {
+            lo: a.min(b),
+            hi: a.max(b),
+        }
+    }
+
+    pub fn point(x: f64) -> Self {
+        Self { lo: x, hi: x }
+    }
+
+    pub fn is_empty(self) -> bool {
+        // NaN bounds count as empty too.
+        let ordered = self.lo <= self.hi;
+        !ordered
+    }
+
+    fn is_point(self) -> bool {
+        self.lo == self.hi
+    }
+
+    fn contains(self, x: f64) -> bool {
+        self.lo <= x && x <= self.hi
+    }
+
+    /// Bounds computed from the endpoints, pushed out by `ulps` ulps on each
+    /// side. A NaN bound (from ∞ − ∞, say) could be anything.
+    fn rounded(lo: f64, hi: f64, ulps: usize) -> Self {
+        if lo.is_nan() || hi.is_nan() {
+            return Self::ENTIRE;
+        }
+        let (mut lo, mut hi) = (lo, hi);
+        for _ in 0..ulps {
+            lo = lo.next_down();
+            hi = hi.next_up();
+        }
+        Self { lo, hi }
+    }
+
+    /// The part of the interval inside `lo..=hi`.
+    fn clamp(self, lo: f64, hi: f64) -> Self {
+        Self {
+            lo: self.lo.max(lo),
+            hi: self.hi.min(hi),
+        }
+    }
+
+    /// Applies a function that doesn't decrease (or, unless `increasing`,
+    /// doesn't increase) over `domain`, outside of which it is undefined.
+    fn monotone(self, f: fn(f64) -> f64, increasing: bool, domain: (f64, f64)) -> Self {
+        let x = self.clamp(domain.0, domain.1);
+        if x.is_empty() {
+            return Self::EMPTY;
+        }
+        let (a, b) = (f(x.lo), f(x.hi));
+        match increasing {
+            true => Self::rounded(a, b, LIBRARY_ULPS),
+            false => Self::rounded(b, a, LIBRARY_ULPS),
+        }
+    }
+
+    /// Whether `offset + k·period` lies in the interval for some integer k,
+    /// counting near misses so that rounding can't hide an extremum.
+    fn meets_lattice(self, offset: f64, period: f64) -> bool {
+        let first = ((self.lo - offset) / period - 1e-9).ceil();
+        let last = ((self.hi - offset) / period + 1e-9).floor();
+        first <= last
+    }
+
+    fn abs(self) -> Self {
+        if self.is_empty() || self.lo >= 0.0 {
+            self
+        } else if self.hi <= 0.0 {
+            -self
+        } else {
+            Self::new(0.0, self.hi.max(-self.lo))
+        }
+    }
+
+    fn sin(self) -> Self {
+        if self.is_empty() {
+            return self;
+        }
+        if self.hi - self.lo >= TAU {
+            return Self::new(-1.0, 1.0);
+        }
+        let ends = Self::rounded(
+            self.lo.sin().min(self.hi.sin()),
+            self.lo.sin().max(self.hi.sin()),
+            LIBRARY_ULPS,
+        );
+        Self {
+            lo: if self.meets_lattice(-FRAC_PI_2, TAU) {
+                -1.0
+            } else {
+                ends.lo
+            },
+            hi: if self.meets_lattice(FRAC_PI_2, TAU) {
+                1.0
+            } else {
+                ends.hi
+            },
+        }
+        .clamp(-1.0, 1.0)
+    }
+
+    fn cos(self) -> Self {
+        if self.is_empty() {
+            return self;
+        }
+        if self.hi - self.lo >= TAU {
+            return Self::new(-1.0, 1.0);
+        }
+        let ends = Self::rounded(
+            self.lo.cos().min(self.hi.cos()),
+            self.lo.cos().max(self.hi.cos()),
+            LIBRARY_ULPS,
+        );
+        Self {
+            lo: if self.meets_lattice(PI, TAU) {
+                -1.0
+            } else {
+                ends.lo
+            },
+            hi: if self.meets_lattice(0.0, TAU) {
+                1.0
+            } else {
+                ends.hi
+            },
+        }
+        .clamp(-1.0, 1.0)
+    }
+
+    fn tan(self) -> Self {
+        if self.is_empty() {
+            return self;
+        }
+        // Between two poles tan increases; across one it takes every value.
+        if self.hi - self.lo >= PI || self.meets_lattice(FRAC_PI_2, PI) {
+            return Self::ENTIRE;
+        }
+        Self::rounded(self.lo.tan(), self.hi.tan(), LIBRARY_ULPS)
+    }
+
+    fn frac(self) -> Self {
+        if self.is_empty() {
+            return self;
+        }
+        let whole = self.lo.floor();
+        if self.hi.floor() == whole {
+            Self::rounded(self.lo - whole, self.hi - whole, 1).clamp(0.0, 1.0)
+        } else {
+            Self::new(0.0, 1.0)
+        }
+    }
+
+    fn max(self, other: Self) -> Self {
+        if self.is_empty() || other.is_empty() {
+            return Self::EMPTY;
+        }
+        Self::new(self.lo.max(other.lo), self.hi.max(other.hi))
+    }
+
+    fn min(self, other: Self) -> Self {
+        if self.is_empty() || other.is_empty() {
+            return Self::EMPTY;
+        }
+        Self::new(self.lo.min(other.lo), self.hi.min(other.hi))
+    }
+
+    fn powi(self, n: i32) -> Self {
+        if self.is_empty() {
+            return self;
+        }
+        match n {
+            0 => Self::point(1.0),
+            n if n < 0 => Self::point(1.0) / self.powi(-n),
+            // Even powers fold the negative half onto the positive one.
+            n if n % 2 == 0 => {
+                let x = self.abs();
+                Self::rounded(x.lo.powi(n), x.hi.powi(n), LIBRARY_ULPS)
+            }
+            n => Self::rounded(self.lo.powi(n), self.hi.powi(n), LIBRARY_ULPS),
+        }
+    }
+
+    fn pow(self, exponent: Self) -> Self {
+        if self.is_empty() || exponent.is_empty() {
+            return Self::EMPTY;
+        }
+        if exponent.is_point() && exponent.lo.fract() == 0.0 && exponent.lo.abs() <= 1e9 {
+            return self.powi(exponent.lo as i32);
+        }
+        // Other powers are only defined for non-negative bases, where they are
+        // monotone in each argument, so the extremes are at the corners.
+        let base = self.clamp(0.0, f64::INFINITY);
+        if base.is_empty() {
+            return Self::EMPTY;
+        }
+        let corners = [
+            base.lo.powf(exponent.lo),
+            base.lo.powf(exponent.hi),
+            base.hi.powf(exponent.lo),
+            base.hi.powf(exponent.hi),
+        ];
+        Self::rounded(
+            corners.iter().copied().fold(f64::INFINITY, f64::min),
+            corners.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            LIBRARY_ULPS,
+        )
+    }
+}
+
+impl Neg for Interval {
+    type Output = Interval;
+
+    fn neg(self) -> Interval {
+        Interval {
+            lo: -self.hi,
+            hi: -self.lo,
+        }
+    }
+}
+
+impl Add for Interval {
+    type Output = Interval;
+
+    fn add(self, other: Interval) -> Interval {
+        if self.is_empty() || other.is_empty() {
+            return Interval::EMPTY;
+        }
+        Interval::rounded(self.lo + other.lo, self.hi + other.hi, 1)
+    }
+}
+
+impl Sub for Interval {
+    type Output = Interval;
+
+    fn sub(self, other: Interval) -> Interval {
+        self + -other
+    }
+}
+
+impl Mul for Interval {
+    type Output = Interval;
+
+    fn mul(self, other: Interval) -> Interval {
+        if self.is_empty() || other.is_empty() {
+            return Interval::EMPTY;
+        }
+        // 0 · ∞ counts as 0: the infinite bound is never actually reached.
+        let product = |a: f64, b: f64| if a == 0.0 || b == 0.0 { 0.0 } else { a * b };
+        let products = [
+            product(self.lo, other.lo),
+            product(self.lo, other.hi),
+            product(self.hi, other.lo),
+            product(self.hi, other.hi),
+        ];
+        Interval::rounded(
+            products.iter().copied().fold(f64::INFINITY, f64::min),
+            products.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            1,
+        )
+    }
+}
+
+impl Div for Interval {
+    type Output = Interval;
+
+    fn div(self, other: Interval) -> Interval {
+        if self.is_empty() || other.is_empty() || other == Interval::point(0.0) {
+            return Interval::EMPTY;
+        }
+        if other.contains(0.0) {
+            return Interval::ENTIRE;
+        }
+        let reciprocal = Interval::rounded(1.0 / other.hi, 1.0 / other.lo, 1);
+        self * reciprocal
+    }
+}
+
+/// One step of a compiled expression, run on a stack of intervals.
+#[derive(Clone, Copy)]
+enum Step {
+    Constant(f64),
+    Variable,
+    Negate,
+    Binary(Operation),
+    Unary(fn(Interval) -> Interval),
+    Max(usize),
+    Min(usize),
+}
+
+/// The interval version of a one-argument function, if there is one.
+fn unary(name: &str) -> Option<fn(Interval) -> Interval> {
+    const ALL: (f64, f64) = (f64::NEG_INFINITY, f64::INFINITY);
+    const POSITIVE: (f64, f64) = (0.0, f64::INFINITY);
+    const UNIT: (f64, f64) = (-1.0, 1.0);
+    Some(match name {
+        "sqrt" => |x| x.monotone(f64::sqrt, true, POSITIVE),
+        "exp" => |x| x.monotone(f64::exp, true, ALL),
+        "ln" => |x| x.monotone(f64::ln, true, POSITIVE),
+        "abs" => Interval::abs,
+        "sin" => Interval::sin,
+        "cos" => Interval::cos,
+        "tan" => Interval::tan,
+        "asin" => |x| x.monotone(f64::asin, true, UNIT),
+        "acos" => |x| x.monotone(f64::acos, false, UNIT),
+        "atan" => |x| x.monotone(f64::atan, true, ALL),
+        "sinh" => |x| x.monotone(f64::sinh, true, ALL),
+        "cosh" => |x| x.abs().monotone(f64::cosh, true, ALL),
+        "tanh" => |x| x.monotone(f64::tanh, true, ALL),
+        "asinh" => |x| x.monotone(f64::asinh, true, ALL),
+        "acosh" => |x| x.monotone(f64::acosh, true, (1.0, f64::INFINITY)),
+        "atanh" => |x| x.monotone(f64::atanh, true, UNIT),
+        "floor" => |x| x.monotone(f64::floor, true, ALL),
+        "ceil" => |x| x.monotone(f64::ceil, true, ALL),
+        "round" => |x| x.monotone(f64::round, true, ALL),
+        "signum" => |x| x.monotone(f64::signum, true, ALL),
+        "frac" => Interval::frac,
+        _ => return None,
+    })
+}
+
+/// A parsed expression compiled for evaluation over intervals.
+pub struct Program {
+    steps: Vec<Step>,
+}
+
+impl Program {
+    /// Compiles meval's RPN tokens. `constant` gives the value of every name
+    /// except the variable. Fails with the name of the first function or
+    /// operator that has no interval version.
+    pub fn compile(
+        tokens: &[Token],
+        constant: &dyn Fn(&str) -> Option<f64>,
+    ) -> Result<Self, String> {
+        let steps = tokens
+            .iter()
+            .map(|token| match token {
+                Token::Number(n) => Ok(Step::Constant(*n)),
+                Token::Var(name) => Ok(constant(name).map_or(Step::Variable, Step::Constant)),
+                Token::Unary(Operation::Minus) => Ok(Step::Negate),
+                Token::Unary(Operation::Plus) => Ok(Step::Unary(|x| x)),
+                Token::Binary(Operation::Rem) => Err("%".to_owned()),
+                Token::Binary(op) => Ok(Step::Binary(*op)),
+                Token::Func(name, arity) => match (name.as_str(), arity.unwrap_or(1)) {
+                    ("max", n) => Ok(Step::Max(n)),
+                    ("min", n) => Ok(Step::Min(n)),
+                    (name, 1) => unary(name).map(Step::Unary).ok_or_else(|| name.to_owned()),
+                    (name, _) => Err(name.to_owned()),
+                },
+                _ => Err(format!("{:?}", token)),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { steps })
+    }
+
+    /// Bounds on the expression's value for every value of the variable in `x`.
+    pub fn eval(&self, x: Interval) -> Interval {
+        let mut stack: Vec<Interval> = Vec::new();
+        for step in &self.steps {
+            let value = match *step {
+                Step::Constant(c) => Interval::point(c),
+                Step::Variable => x,
+                Step::Negate => -stack.pop().unwrap_or(Interval::EMPTY),
+                Step::Unary(f) => f(stack.pop().unwrap_or(Interval::EMPTY)),
+                Step::Binary(op) => {
+                    let right = stack.pop().unwrap_or(Interval::EMPTY);
+                    let left = stack.pop().unwrap_or(Interval::EMPTY);
+                    match op {
+                        Operation::Plus => left + right,
+                        Operation::Minus => left - right,
+                        Operation::Times => left * right,
+                        Operation::Div => left / right,
+                        Operation::Pow => left.pow(right),
+                        // Rejected when compiling.
+                        _ => Interval::ENTIRE,
+                    }
+                }
+                Step::Max(n) | Step::Min(n) => {
+                    let args = stack.split_off(stack.len().saturating_sub(n));
+                    let combine = match step {
+                        Step::Max(_) => Interval::max,
+                        _ => Interval::min,
+                    };
+                    args.into_iter().reduce(combine).unwrap_or(Interval::EMPTY)
+                }
+            };
+            stack.push(value);
+        }
+        stack.pop().unwrap_or(Interval::EMPTY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn program(text: &str) -> Result<Program, String> {
+        let expr: meval::Expr = text.parse().unwrap();
+        Program::compile(&expr, &|name| (name == "pi").then_some(PI))
+    }
+
+    #[test]
+    fn bounds_enclose_every_value() {
+        let f = program("x^2 - 2*x").unwrap();
+        let y = f.eval(Interval::new(-1.0, 3.0));
+        // The true range is [-1, 3]; evaluating term by term overestimates it.
+        assert!(y.lo <= -1.0 && y.hi >= 3.0);
+        for i in 0..=400 {
+            let x = -1.0 + i as f64 / 100.0;
+            assert!(y.contains(x * x - 2.0 * x));
+        }
+
+        let sin = program("sin(pi*x)").unwrap().eval(Interval::new(0.4, 0.6));
+        assert_eq!(sin.hi, 1.0);
+        assert!(sin.lo <= (0.4 * PI).sin());
+        assert_eq!(
+            program("1/x").unwrap().eval(Interval::new(-1.0, 1.0)),
+            Interval::ENTIRE
+        );
+        assert!(program("ln(x)")
+            .unwrap()
+            .eval(Interval::new(-2.0, -1.0))
+            .is_empty());
+        assert_eq!(program("atan2(x, 1)").err().as_deref(), Some("atan2"));
+        assert_eq!(program("x % 2").err().as_deref(), Some("%"));
+    }
+}
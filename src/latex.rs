@@ -0,0 +1,132 @@
+//! LaTeX rendering of parsed expressions, for pasting into write-ups.
+
+use meval::tokenizer::{Operation, Token};
+
+// Binding strength of each kind of node, used to decide where parentheses go.
+const SUM: u8 = 1;
+const PRODUCT: u8 = 2;
+const NEGATION: u8 = 3;
+const POWER: u8 = 4;
+const ATOM: u8 = 5;
+
+/// Variable names written as Greek letters.
+const GREEK: &[&str] = &[
+    "alpha", "beta", "gamma", "delta", "epsilon", "zeta", "eta", "theta", "lambda", "mu", "nu",
+    "xi", "pi", "rho", "sigma", "tau", "phi", "chi", "psi", "omega",
+];
+
+/// Renders a parsed expression as LaTeX, e.g. `\frac{\sin\left(x\right)}{x}` for `sin(x)/x`.
+///
+/// Returns `None` if the token stream is not a well-formed expression.
+pub fn to_latex(tokens: &[Token]) -> Option<String> {
+    let mut stack: Vec<(String, u8)> = Vec::new();
+    for token in tokens {
+        let node = match token {
+            Token::Number(n) => (n.to_string(), ATOM),
+            Token::Var(name) => (variable(name), ATOM),
+            Token::Unary(Operation::Minus) => {
+                let operand = stack.pop()?;
+                (format!("-{}", wrap(operand, NEGATION)), NEGATION)
+            }
+            Token::Unary(_) => stack.pop()?,
+            Token::Binary(op) => {
+                let right = stack.pop()?;
+                let left = stack.pop()?;
+                binary(*op, left, right)
+            }
+            Token::Func(name, arity) => {
+                let count = arity.unwrap_or(1);
+                let args = stack.split_off(stack.len().checked_sub(count)?);
+                function(name, args.into_iter().map(|(text, _)| text).collect())
+            }
+            Token::LParen | Token::RParen | Token::Comma => return None,
+        };
+        stack.push(node);
+    }
+    match &stack[..] {
+        [(text, _)] => Some(text.clone()),
+        _ => None,
+    }
+}
+
+/// Parenthesizes `node` when it binds more loosely than `min`.
+fn wrap((text, strength): (String, u8), min: u8) -> String {
+    if strength < min {
+        format!("\\left({}\\right)", text)
+    } else {
+        text
+    }
+}
+
+fn variable(name: &str) -> String {
+    if GREEK.contains(&name) {
+        format!("\\{}", name)
+    } else if name.chars().count() > 1 {
+        format!("\\mathrm{{{}}}", name)
+    } else {
+        name.to_owned()
+    }
+}
+
+fn binary(op: Operation, left: (String, u8), right: (String, u8)) -> (String, u8) {
+    match op {
+        Operation::Plus => (format!("{} + {}", left.0, wrap(right, SUM)), SUM),
+        // The right side of a difference needs parentheses around sums: a - (b + c).
+        Operation::Minus => (format!("{} - {}", left.0, wrap(right, PRODUCT)), SUM),
+        Operation::Times => (
+            format!("{} \\cdot {}", wrap(left, PRODUCT), wrap(right, NEGATION)),
+            PRODUCT,
+        ),
+        Operation::Rem => (
+            format!("{} \\bmod {}", wrap(left, PRODUCT), wrap(right, NEGATION)),
+            PRODUCT,
+        ),
+        // Fraction bars and exponents group their contents on their own, but
+        // a fraction still needs parentheses as the base of a power.
+        Operation::Div => (format!("\\frac{{{}}}{{{}}}", left.0, right.0), POWER),
+        Operation::Pow => (format!("{}^{{{}}}", wrap(left, ATOM), right.0), POWER),
+    }
+}
+
+fn function(name: &str, args: Vec<String>) -> (String, u8) {
+    let joined = args.join(", ");
+    let text = match (name, &args[..]) {
+        ("sqrt", [arg]) => format!("\\sqrt{{{}}}", arg),
+        ("abs", [arg]) => format!("\\left|{}\\right|", arg),
+        ("floor", [arg]) => format!("\\left\\lfloor {}\\right\\rfloor", arg),
+        ("ceil", [arg]) => format!("\\left\\lceil {}\\right\\rceil", arg),
+        ("sin" | "cos" | "tan" | "sinh" | "cosh" | "tanh" | "exp" | "ln" | "max" | "min", _) => {
+            format!("\\{}\\left({}\\right)", name, joined)
+        }
+        ("asin" | "acos" | "atan", _) => format!("\\arc{}\\left({}\\right)", &name[1..], joined),
+        // Anything else keeps its name, set upright like the built-in operators.
+        _ => format!("\\operatorname{{{}}}\\left({}\\right)", name, joined),
+    };
+    (text, ATOM)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn latex(text: &str) -> String {
+        to_latex(&meval::Expr::from_str(text).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn common_forms_are_rendered() {
+        assert_eq!(latex("sin(x)/x"), "\\frac{\\sin\\left(x\\right)}{x}");
+        assert_eq!(latex("2*pi*x^2"), "2 \\cdot \\pi \\cdot x^{2}");
+        assert_eq!(latex("(x+1)^(1/2)"), "\\left(x + 1\\right)^{\\frac{1}{2}}");
+        assert_eq!(latex("a - (b - c)"), "a - \\left(b - c\\right)");
+        assert_eq!(
+            latex("-(x+1)*abs(x)"),
+            "-\\left(x + 1\\right) \\cdot \\left|x\\right|"
+        );
+        assert_eq!(
+            latex("frac(atan(x))"),
+            "\\operatorname{frac}\\left(\\arctan\\left(x\\right)\\right)"
+        );
+    }
+}
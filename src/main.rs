@@ -1,306 +1,8357 @@
+use complex::ComplexPart;
 use eframe::egui;
-use egui::plot::{Line, Plot, PlotPoints};
+use egui::plot::{
+    uniform_grid_spacer, GridInput, HLine, Legend, Line, LineStyle, Plot, PlotPoint, PlotPoints,
+    PlotUi, Points, Polygon, Text, VLine,
+};
+use interval::Interval;
+use meval::ContextProvider;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+mod cli;
+mod complex;
+mod data;
+mod export;
+mod interval;
+mod latex;
+mod preprocess;
+mod session;
+mod theme;
+mod watch;
+
+/// Smallest number of sample points per function.
+const MIN_POINTS: usize = 100;
+/// Largest number of sample points per function.
+const MAX_POINTS: usize = 5000;
+
+/// Clamps a sample count coming from outside the slider (config, session
+/// load, etc.) into the supported range.
+fn clamp_num_points(num_points: usize) -> usize {
+    num_points.clamp(MIN_POINTS, MAX_POINTS)
+}
+
+/// Settings the quality slider applies together.
+struct QualityPreset {
+    label: &'static str,
+    num_points: usize,
+    antialiasing: bool,
+    /// Resample over the visible range after panning or zooming.
+    follow_view: bool,
+    /// Drop sample points that don't change the drawn line.
+    decimate: bool,
+    /// Draw curves smoothly instead of with straight segments; step styles
+    /// are left as they are.
+    smooth: bool,
+}
+
+/// Quality slider positions, fastest first; the default is "Normal".
+const QUALITY_PRESETS: [QualityPreset; 5] = [
+    QualityPreset {
+        label: "Draft",
+        num_points: MIN_POINTS,
+        antialiasing: false,
+        follow_view: false,
+        decimate: true,
+        smooth: false,
+    },
+    QualityPreset {
+        label: "Fast",
+        num_points: 400,
+        antialiasing: true,
+        follow_view: false,
+        decimate: true,
+        smooth: false,
+    },
+    QualityPreset {
+        label: "Normal",
+        num_points: 1000,
+        antialiasing: true,
+        follow_view: false,
+        decimate: false,
+        smooth: false,
+    },
+    QualityPreset {
+        label: "Fine",
+        num_points: 2500,
+        antialiasing: true,
+        follow_view: true,
+        decimate: false,
+        smooth: true,
+    },
+    QualityPreset {
+        label: "Best",
+        num_points: MAX_POINTS,
+        antialiasing: true,
+        follow_view: true,
+        decimate: false,
+        smooth: true,
+    },
+];
+const DEFAULT_QUALITY: usize = 2;
+
+/// Scrolling this many points over the plot with Alt held doubles (or halves)
+/// the number of sample points.
+const SCROLL_PER_DOUBLING: f32 = 200.0;
+
+/// The sample count after scrolling by `delta` points with Alt held.
+fn scrolled_num_points(num_points: usize, delta: f32) -> usize {
+    let scaled = num_points as f64 * 2f64.powf((delta / SCROLL_PER_DOUBLING) as f64);
+    clamp_num_points(scaled.round() as usize)
+}
+
+/// How far (as a fraction of the visible width) the view must move away from
+/// the sampled x range before following the view resamples.
+const VIEW_THRESHOLD: f64 = 0.1;
+/// Seconds the view must stay still before following it resamples.
+const VIEW_DEBOUNCE: f64 = 0.25;
+
+/// Storage key for the UI scale factor.
+const UI_SCALE_KEY: &str = "ui_scale";
+/// Storage key for the autosave preference.
+const AUTOSAVE_KEY: &str = "autosave";
+/// Storage key for the focus-on-startup preference.
+const STARTUP_FOCUS_KEY: &str = "startup_focus";
+/// Storage key for the dark mode preference.
+const DARK_MODE_KEY: &str = "dark_mode";
+/// Storage key for the path of the loaded theme file, if any.
+const THEME_KEY: &str = "theme";
+/// Storage key for the high-contrast mode preference.
+const HIGH_CONTRAST_KEY: &str = "high_contrast";
+
+/// Text size and curve width in high-contrast mode, relative to the usual ones.
+const HIGH_CONTRAST_FONT_SCALE: f32 = 1.3;
+const HIGH_CONTRAST_LINE_SCALE: f32 = 1.75;
+
+/// Contrast a curve needs against the plot background (WCAG's minimum for graphics).
+const MIN_CONTRAST: f64 = 3.0;
+
+/// Relative luminance of an sRGB color, as defined by WCAG 2.
+fn relative_luminance(color: egui::Color32) -> f64 {
+    let channel = |c: u8| {
+        let c = c as f64 / 255.0;
+        if c <= 0.03928 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    0.2126 * channel(color.r()) + 0.7152 * channel(color.g()) + 0.0722 * channel(color.b())
+}
+
+/// WCAG contrast ratio between two colors, from 1 (identical) to 21 (black on white).
+fn contrast_ratio(a: egui::Color32, b: egui::Color32) -> f64 {
+    let (la, lb) = (relative_luminance(a), relative_luminance(b));
+    (la.max(lb) + 0.05) / (la.min(lb) + 0.05)
+}
+
+/// Lightens (on dark backgrounds) or darkens (on light ones) `color` in small
+/// steps until it has `MIN_CONTRAST` against `background`.
+fn ensure_contrast(color: egui::Color32, background: egui::Color32) -> egui::Color32 {
+    let target = if contrast_ratio(background, egui::Color32::WHITE)
+        > contrast_ratio(background, egui::Color32::BLACK)
+    {
+        egui::Color32::WHITE
+    } else {
+        egui::Color32::BLACK
+    };
+    let mix =
+        |from: u8, to: u8, t: f64| (from as f64 + (to as f64 - from as f64) * t).round() as u8;
+    let mut adjusted = color;
+    for step in 1..=10 {
+        if contrast_ratio(adjusted, background) >= MIN_CONTRAST {
+            break;
+        }
+        let t = step as f64 / 10.0;
+        adjusted = egui::Color32::from_rgba_unmultiplied(
+            mix(color.r(), target.r(), t),
+            mix(color.g(), target.g(), t),
+            mix(color.b(), target.b(), t),
+            color.a(),
+        );
+    }
+    adjusted
+}
+
+/// The app's light theme.
+fn light_visuals() -> egui::Visuals {
+    let mut visuals = egui::Visuals::light();
+    visuals.window_fill = egui::Color32::from_rgb(250, 250, 250);
+    visuals.widgets.noninteractive.bg_fill = egui::Color32::from_rgb(230, 230, 230);
+    visuals.widgets.hovered.bg_fill = egui::Color32::from_rgb(220, 220, 255);
+    visuals.widgets.active.bg_fill = egui::Color32::from_rgb(200, 200, 250);
+    visuals
+}
+
+/// Base palette used when assigning colors to new functions.
+const PALETTE: [egui::Color32; 12] = [
+    egui::Color32::from_rgb(220, 20, 60),   // Crimson
+    egui::Color32::from_rgb(65, 105, 225),  // RoyalBlue
+    egui::Color32::from_rgb(34, 139, 34),   // ForestGreen
+    egui::Color32::from_rgb(255, 140, 0),   // DarkOrange
+    egui::Color32::from_rgb(138, 43, 226),  // BlueViolet
+    egui::Color32::from_rgb(0, 139, 139),   // DarkCyan
+    egui::Color32::from_rgb(199, 21, 133),  // MediumVioletRed
+    egui::Color32::from_rgb(139, 69, 19),   // SaddleBrown
+    egui::Color32::from_rgb(107, 142, 35),  // OliveDrab
+    egui::Color32::from_rgb(70, 130, 180),  // SteelBlue
+    egui::Color32::from_rgb(218, 165, 32),  // GoldenRod
+    egui::Color32::from_rgb(112, 128, 144), // SlateGray
+];
+
+/// Returns the color for the function at `index`.
+///
+/// The first entries come from `palette`; beyond that, hues are generated
+/// procedurally (golden-angle spacing) so colors keep being distinct.
+fn function_color(palette: &[egui::Color32], index: usize) -> egui::Color32 {
+    if let Some(&color) = palette.get(index) {
+        return color;
+    }
+    let extra = index - palette.len();
+    let hue = (extra as f32 * 0.618_034).fract();
+    // Alternate the brightness so neighbouring hues are easier to tell apart.
+    let value = [0.85, 0.65][extra % 2];
+    egui::ecolor::Hsva::new(hue, 0.75, value, 1.0).into()
+}
+
+/// Options controlling how expression text is interpreted before parsing.
+#[derive(Clone)]
+struct ParseOptions {
+    /// Accept `0x..` and `0b..` literals by rewriting them to decimal.
+    radix_literals: bool,
+    /// Read expressions as postfix (RPN), e.g. `x 2 ^ x sin +`.
+    rpn: bool,
+    /// Name of the independent variable; empty means detect it from the expression.
+    variable: String,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            radix_literals: false,
+            rpn: false,
+            variable: "x".to_owned(),
+        }
+    }
+}
+
+/// A named constant usable in every expression (e.g. `a` in `a*sin(x)`).
+#[derive(Clone)]
+struct Constant {
+    name: String,
+    value: f64,
+    /// Slider range, also used as the sweep range when animating.
+    min: f64,
+    max: f64,
+}
+
+impl Constant {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            value: 1.0,
+            min: -5.0,
+            max: 5.0,
+        }
+    }
+}
+
+/// What the sign strip under the plot shows for a function.
+#[derive(Clone, Copy, PartialEq)]
+enum SignStrip {
+    Off,
+    /// Sign of f(x).
+    Value,
+    /// Sign of the slope f'(x), estimated from neighbouring samples.
+    Derivative,
+}
+
+impl SignStrip {
+    fn label(self) -> &'static str {
+        match self {
+            SignStrip::Off => "No sign strip",
+            SignStrip::Value => "Sign of f",
+            SignStrip::Derivative => "Sign of f'",
+        }
+    }
+}
+
+/// Grid step sizes for one axis: minor, major and a coarser tier, where the
+/// major step is a 1/2/5 value and the minor step divides it evenly.
+fn grid_steps(input: GridInput, subdivisions: usize) -> [f64; 3] {
+    let major = export::grid_step(input.bounds.1 - input.bounds.0, 8.0);
+    [major / subdivisions.max(1) as f64, major, major * 10.0]
+}
+
+/// Tick labels in multiples of a constant, such as π/2, π, 3π/2.
+#[derive(Clone)]
+struct TickUnit {
+    enabled: bool,
+    /// Written after the multiple in the labels.
+    label: String,
+    /// Expression for the constant's value.
+    value: String,
+}
+
+impl TickUnit {
+    fn pi() -> Self {
+        Self {
+            enabled: false,
+            label: "π".to_owned(),
+            value: "pi".to_owned(),
+        }
+    }
+
+    /// The constant's value while enabled, or why it has none.
+    fn unit(&self, options: &ParseOptions, constants: &[Constant]) -> Option<Result<f64, String>> {
+        self.enabled.then(
+            || match evaluate_constant(&self.value, options, constants) {
+                Ok(unit) if unit.is_finite() && unit > 0.0 => Ok(unit),
+                Ok(_) => Err("The tick unit must be positive".to_owned()),
+                Err(e) => Err(e.to_string()),
+            },
+        )
+    }
+}
+
+/// Grid steps for ticks in multiples of `unit`: a major step of a simple
+/// fraction (1/12 up to 1) or a 1/2/5 multiple of `unit`, with a minor step
+/// of half that.
+fn unit_grid_steps(input: GridInput, unit: f64) -> [f64; 3] {
+    let target = (input.bounds.1 - input.bounds.0) / unit / 8.0;
+    let multiple = if target > 1.0 {
+        export::grid_step(target * 8.0, 8.0)
+    } else {
+        [1.0 / 12.0, 1.0 / 6.0, 0.25, 0.5, 1.0]
+            .into_iter()
+            .find(|&m| m >= target)
+            .unwrap_or(1.0)
+    };
+    let major = multiple * unit;
+    [major / 2.0, major, major * 4.0]
+}
+
+/// Largest denominator written as a fraction in unit tick labels.
+const MAX_TICK_DENOMINATOR: i64 = 24;
+
+/// Labels `value` as a multiple of `unit`, e.g. "3π/2" or "-π"; values that
+/// aren't a simple fraction of it are written as decimals.
+fn format_unit_tick(value: f64, unit: f64, label: &str) -> String {
+    let multiple = value / unit;
+    let Some((numerator, denominator)) = (1..=MAX_TICK_DENOMINATOR).find_map(|d| {
+        let n = (multiple * d as f64).round();
+        ((multiple * d as f64 - n).abs() < 1e-6).then_some((n as i64, d))
+    }) else {
+        return format!("{:.3}{}", multiple, label);
+    };
+    let coefficient = match numerator {
+        0 => return "0".to_owned(),
+        1 => String::new(),
+        -1 => "-".to_owned(),
+        n => n.to_string(),
+    };
+    match denominator {
+        1 => format!("{}{}", coefficient, label),
+        d => format!("{}{}/{}", coefficient, label, d),
+    }
+}
+
+/// Maps a value on the secondary y-axis into the primary axis' coordinates.
+///
+/// egui plots have a single y-axis, so secondary-axis functions are drawn
+/// through this affine map: the bottom of the secondary range `(s0, s1)` lands
+/// on the bottom of the primary range `(p0, p1)` and the top on the top. The
+/// right-hand tick labels show the secondary values, so readings against them
+/// stay in the function's own units.
+fn secondary_to_primary(y: f64, (s0, s1): (f64, f64), (p0, p1): (f64, f64)) -> f64 {
+    p0 + (y - s0) * (p1 - p0) / (s1 - s0)
+}
+
+/// Common x ranges offered next to the x inputs, as `(label, min, max)`.
+const X_PRESETS: [(&str, f64, f64); 3] = [
+    ("±π", -std::f64::consts::PI, std::f64::consts::PI),
+    ("±2π", -std::f64::consts::TAU, std::f64::consts::TAU),
+    ("0 to 2π", 0.0, std::f64::consts::TAU),
+];
+/// Common y ranges offered next to the y inputs.
+const Y_PRESETS: [(&str, f64, f64); 3] = [
+    ("±1", -1.0, 1.0),
+    ("±10", -10.0, 10.0),
+    ("0 to 1", 0.0, 1.0),
+];
+
+/// Steps the number in `text` up or down by one unit of its last decimal
+/// place (so "10" moves by 1 and "0.25" by 0.01), or ten units if `large`.
+///
+/// Returns `None` for text that isn't a plain decimal number.
+fn step_number(text: &str, up: bool, large: bool) -> Option<String> {
+    let text = text.trim();
+    if text.contains(['e', 'E']) {
+        return None;
+    }
+    let value = parse_finite(text)?;
+    let decimals = text
+        .split_once('.')
+        .map_or(0, |(_, fraction)| fraction.len());
+    let step = 10f64.powi(-(decimals as i32)) * if large { 10.0 } else { 1.0 };
+    let value = if up { value + step } else { value - step };
+    // Adding 0.0 avoids printing "-0".
+    Some(format!("{:.*}", decimals, value + 0.0))
+}
+
+/// A single-line numeric text field whose value Up/Down step while it has
+/// focus (Shift for larger steps). Returns whether the text changed.
+fn numeric_edit(ui: &mut egui::Ui, text: &mut String) -> bool {
+    let response = ui.text_edit_singleline(text);
+    let mut changed = response.changed();
+    if response.has_focus() {
+        let (up, down, large) = ui.input(|i| {
+            (
+                i.key_pressed(egui::Key::ArrowUp),
+                i.key_pressed(egui::Key::ArrowDown),
+                i.modifiers.shift,
+            )
+        });
+        if let Some(stepped) = (up != down).then(|| step_number(text, up, large)).flatten() {
+            *text = stepped;
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// Describes the offset between two points: Δx, Δy, distance and slope.
+fn describe_measurement([x0, y0]: [f64; 2], [x1, y1]: [f64; 2]) -> String {
+    let (dx, dy) = (x1 - x0, y1 - y0);
+    let slope = if dx == 0.0 {
+        "undefined".to_owned()
+    } else {
+        format!("{:.4}", dy / dx)
+    };
+    format!(
+        "Δx = {:.4}, Δy = {:.4}, distance = {:.4}, slope = {}",
+        dx,
+        dy,
+        dx.hypot(dy),
+        slope
+    )
+}
+
+/// Parses a domain bound, rejecting values such as `inf` and `nan`.
+fn parse_finite(text: &str) -> Option<f64> {
+    text.trim().parse::<f64>().ok().filter(|v| v.is_finite())
+}
+
+/// How the plot's y range is fitted to the sampled data.
+#[derive(Clone, Copy, PartialEq)]
+enum Autoscale {
+    /// Use the y bounds from the domain settings.
+    Off,
+    /// Exactly the range of the data.
+    Tight,
+    /// The data range widened to clean multiples of 1, 2 or 5.
+    Nice,
+}
+
+impl Autoscale {
+    fn label(self) -> &'static str {
+        match self {
+            Autoscale::Off => "Off",
+            Autoscale::Tight => "Tight",
+            Autoscale::Nice => "Nice numbers",
+        }
+    }
+}
+
+/// Widens `[min, max]` outwards to multiples of a 1/2/5 step.
+fn nice_bounds(min: f64, max: f64) -> (f64, f64) {
+    if min == max {
+        // A flat curve has no span to round; give it one unit either side.
+        return ((min - 1.0).floor(), (max + 1.0).ceil());
+    }
+    let step = export::grid_step(max - min, 5.0);
+    ((min / step).floor() * step, (max / step).ceil() * step)
+}
+
+/// Percentiles of the sampled y values kept by the robust y fit.
+const ROBUST_PERCENTILES: (f64, f64) = (0.05, 0.95);
+/// Room added above and below the percentile range, as a fraction of it.
+const ROBUST_MARGIN: f64 = 0.1;
+
+/// A y-window holding the bulk of `values`: the `ROBUST_PERCENTILES` range
+/// plus `ROBUST_MARGIN`, rounded out by `nice_bounds`. A few spikes don't
+/// widen it. `None` if there are no finite values.
+fn robust_y_range(mut values: Vec<f64>) -> Option<(f64, f64)> {
+    values.retain(|y| y.is_finite());
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(f64::total_cmp);
+    let percentile = |p: f64| {
+        let rank = p * (values.len() - 1) as f64;
+        let (below, above) = (values[rank.floor() as usize], values[rank.ceil() as usize]);
+        below + (above - below) * rank.fract()
+    };
+    let (low, high) = (
+        percentile(ROBUST_PERCENTILES.0),
+        percentile(ROBUST_PERCENTILES.1),
+    );
+    let margin = ROBUST_MARGIN * (high - low);
+    Some(nice_bounds(low - margin, high + margin))
+}
+
+/// Splits the sampled curve into x-intervals tagged with the sign (-1, 0 or 1)
+/// of either the value or the slope over that interval.
+fn sign_segments(points: &[[f64; 2]], strip: SignStrip) -> Vec<(f64, f64, f64)> {
+    points
+        .windows(2)
+        .map(|w| {
+            let ([x0, y0], [x1, y1]) = (w[0], w[1]);
+            let value = match strip {
+                SignStrip::Derivative => y1 - y0,
+                _ => 0.5 * (y0 + y1),
+            };
+            let sign = if value == 0.0 { 0.0 } else { value.signum() };
+            (x0, x1, sign)
+        })
+        .collect()
+}
+
+/// Transformation applied to a function when sampling: `b*f(a*(x - h)) + k`.
+#[derive(Clone, Copy, PartialEq)]
+struct CurveTransform {
+    /// Horizontal shift.
+    h: f64,
+    /// Vertical shift.
+    k: f64,
+    /// Horizontal scale applied to the input.
+    a: f64,
+    /// Vertical scale applied to the output.
+    b: f64,
+}
+
+impl Default for CurveTransform {
+    fn default() -> Self {
+        Self {
+            h: 0.0,
+            k: 0.0,
+            a: 1.0,
+            b: 1.0,
+        }
+    }
+}
+
+impl CurveTransform {
+    /// Evaluates the transformed function at `x`.
+    fn apply(&self, f: impl Fn(f64) -> f64, x: f64) -> f64 {
+        self.b * f(self.a * (x - self.h)) + self.k
+    }
+}
+
+/// What a function's inputs describe.
+#[derive(Clone, Copy, PartialEq)]
+enum PlotMode {
+    /// y = f(x).
+    Cartesian,
+    /// (x(t), y(t)) over a range of t.
+    Parametric,
+    /// r(theta) over a range of theta.
+    Polar,
+}
+
+impl PlotMode {
+    const ALL: [PlotMode; 3] = [PlotMode::Cartesian, PlotMode::Parametric, PlotMode::Polar];
+
+    fn label(self) -> &'static str {
+        match self {
+            PlotMode::Cartesian => "y = f(x)",
+            PlotMode::Parametric => "Parametric",
+            PlotMode::Polar => "Polar",
+        }
+    }
+}
+
+/// Inputs of a parametric curve, kept while another mode is shown.
+#[derive(Clone)]
+struct ParametricInputs {
+    x: String,
+    y: String,
+    t_min: String,
+    t_max: String,
+}
+
+impl Default for ParametricInputs {
+    fn default() -> Self {
+        Self {
+            x: "cos(t)".to_owned(),
+            y: "sin(2*t)".to_owned(),
+            t_min: "0".to_owned(),
+            t_max: "2*pi".to_owned(),
+        }
+    }
+}
+
+/// Inputs of a polar curve, kept while another mode is shown.
+#[derive(Clone)]
+struct PolarInputs {
+    r: String,
+    theta_min: String,
+    theta_max: String,
+}
+
+impl Default for PolarInputs {
+    fn default() -> Self {
+        Self {
+            r: "1 + cos(theta)".to_owned(),
+            theta_min: "0".to_owned(),
+            theta_max: "2*pi".to_owned(),
+        }
+    }
+}
+
+/// Evaluates a parameter bound such as "2*pi".
+fn parse_bound(text: &str, name: &str) -> Result<f64, PlotError> {
+    meval::eval_str(text.trim())
+        .ok()
+        .filter(|v| v.is_finite())
+        .ok_or_else(|| PlotError::Domain(format!("Invalid {} value", name)))
+}
+
+/// How a curve is drawn between consecutive samples.
+#[derive(Clone, Copy, PartialEq)]
+enum Interpolation {
+    Straight,
+    /// Monotone cubic (PCHIP) through the samples.
+    Smooth,
+    /// Staircase: horizontal, then vertical.
+    StepHorizontal,
+    /// Staircase: vertical, then horizontal.
+    StepVertical,
+}
+
+impl Interpolation {
+    const ALL: [Interpolation; 4] = [
+        Interpolation::Straight,
+        Interpolation::Smooth,
+        Interpolation::StepHorizontal,
+        Interpolation::StepVertical,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Interpolation::Straight => "Straight lines",
+            Interpolation::Smooth => "Smooth (PCHIP)",
+            Interpolation::StepHorizontal => "Steps (horizontal first)",
+            Interpolation::StepVertical => "Steps (vertical first)",
+        }
+    }
+
+    /// Expands `points` into the polyline that is actually drawn.
+    fn apply(self, points: &[[f64; 2]]) -> Vec<[f64; 2]> {
+        match self {
+            Interpolation::Straight => points.to_vec(),
+            Interpolation::Smooth => pchip_resample(points, 8),
+            Interpolation::StepHorizontal | Interpolation::StepVertical => {
+                let mut out = Vec::with_capacity(points.len() * 2);
+                for w in points.windows(2) {
+                    let ([x0, y0], [x1, y1]) = (w[0], w[1]);
+                    out.push([x0, y0]);
+                    out.push(if self == Interpolation::StepHorizontal {
+                        [x1, y0]
+                    } else {
+                        [x0, y1]
+                    });
+                }
+                out.extend(points.last());
+                out
+            }
+        }
+    }
+}
+
+/// How sample x values are spread across the domain.
+#[derive(Clone, Copy, PartialEq)]
+enum Sampling {
+    /// Evenly spaced.
+    Linear,
+    /// Evenly spaced in log(x), so denser near x_min. Needs 0 < x_min.
+    Log,
+    /// Every `step` from x_min towards x_max, which may lie below x_min (the
+    /// step is then negative). Ignores the sample count.
+    Step(f64),
+}
+
+impl Sampling {
+    /// The `i`-th of `n + 1` sample positions from `x_min` to `x_max`.
+    ///
+    /// Log spacing falls back to linear when the domain isn't positive.
+    fn x(self, x_min: f64, x_max: f64, i: usize, n: usize) -> f64 {
+        match self {
+            Sampling::Log if x_min > 0.0 => x_min * (x_max / x_min).powf(i as f64 / n as f64),
+            Sampling::Step(step) => x_min + step * i as f64,
+            _ => grid_x(x_min, x_max, i, n),
+        }
+    }
+
+    /// Number of intervals between samples: `n`, except for a fixed step.
+    fn intervals(self, x_min: f64, x_max: f64, n: usize) -> usize {
+        match self {
+            // The small slack keeps x_max when it is a multiple of the step up to rounding.
+            Sampling::Step(step) => ((x_max - x_min) / step + 1e-9).floor() as usize,
+            _ => n,
+        }
+    }
+}
+
+/// The `n + 1` evenly spaced positions from `x_min` to `x_max`, both ends
+/// exact. Everything that samples an interval evenly goes through here (or
+/// `grid_x`), so that all features evaluate at identical x values.
+fn x_grid(x_min: f64, x_max: f64, n: usize) -> Vec<f64> {
+    let n = n.max(1);
+    (0..=n).map(|i| grid_x(x_min, x_max, i, n)).collect()
+}
+
+/// The `i`-th position of `x_grid(x_min, x_max, n)`.
+fn grid_x(x_min: f64, x_max: f64, i: usize, n: usize) -> f64 {
+    let t = i as f64 / n as f64;
+    // Weighting both ends lands on x_max exactly at t = 1, which
+    // x_min + (x_max - x_min) * t can miss by rounding.
+    x_min * (1.0 - t) + x_max * t
+}
+
+/// A named set of functions that are listed, shown and tinted together.
+#[derive(Clone)]
+struct FunctionGroup {
+    name: String,
+    visible: bool,
+    /// Whether the members' panels are folded away in the list.
+    collapsed: bool,
+    /// Color blended half-and-half into each member's own color when drawing.
+    tint: Option<egui::Color32>,
+}
+
+impl FunctionGroup {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            visible: true,
+            collapsed: false,
+            tint: None,
+        }
+    }
+}
+
+/// `color` mixed half-and-half with `tint`.
+fn tint_color(color: egui::Color32, tint: egui::Color32) -> egui::Color32 {
+    let mix = |a: u8, b: u8| (a as u16 + b as u16).div_ceil(2) as u8;
+    egui::Color32::from_rgba_unmultiplied(
+        mix(color.r(), tint.r()),
+        mix(color.g(), tint.g()),
+        mix(color.b(), tint.b()),
+        color.a(),
+    )
+}
 
 /// Structure representing a single function definition.
+#[derive(Clone)]
 struct FunctionPlot {
+    /// Which of the inputs below are plotted.
+    mode: PlotMode,
     /// The user-defined function expression (e.g., "sin(x)" or "abs(ln(x-1)/ln(x-2))")
     expression: String,
+    /// Inputs of the other modes; each keeps its text while the others are in use.
+    parametric: ParametricInputs,
+    polar: PolarInputs,
     /// Any error message produced while parsing or evaluating this function.
     error_message: Option<String>,
     /// The computed (x, y) plot points.
     plot_points: Vec<[f64; 2]>,
     /// The color used when plotting this function.
     color: egui::Color32,
+    /// Keep `color` as chosen instead of adjusting it to the theme.
+    color_locked: bool,
+    /// Which palette entry `color` comes from. Fixed when the function is
+    /// created, so removing other functions or switching palettes never hands
+    /// it another function's color.
+    palette_slot: usize,
+    /// Optional uncertainty expression δ(x), drawn as a band between f - δ and f + δ.
+    uncertainty_expr: Option<String>,
+    /// Lower and upper edges of the uncertainty band.
+    band_lower: Vec<[f64; 2]>,
+    band_upper: Vec<[f64; 2]>,
+    /// Draw the inverse relation by plotting (y, x) instead of (x, y).
+    plot_inverse: bool,
+    /// Whether to detect poles and mark them with dashed vertical lines.
+    show_asymptotes: bool,
+    /// Approximate x locations of detected poles.
+    asymptotes: Vec<f64>,
+    /// Whether this function's panel is expanded in the list.
+    expanded: bool,
+    /// Sign chart drawn in a strip below the plot.
+    sign_strip: SignStrip,
+    /// Shift/scale applied on top of the expression.
+    transform: CurveTransform,
+    /// Whether to draw and export a simplified copy of the curve.
+    decimate: bool,
+    /// Simplified points, kept alongside the full `plot_points`.
+    simplified: Option<Vec<[f64; 2]>>,
+    /// How consecutive samples are joined when drawing.
+    interpolation: Interpolation,
+    /// Draw against the secondary y-axis on the right (see `secondary_to_primary`).
+    secondary_axis: bool,
+    /// Join the last point of a parametric or polar curve back to the first.
+    close_curve: bool,
+    /// Slice mode: one curve per value of `slice_variable` listed in
+    /// `slice_values`, instead of a single curve.
+    slice_enabled: bool,
+    slice_variable: String,
+    slice_values: String,
+    /// The sampled curve of each slice value.
+    slices: Vec<Slice>,
+    /// Smallest and largest drawn y value, shown in the legend on request.
+    y_range: Option<(f64, f64)>,
+    /// x positions of samples dropped for exceeding the |y| limit; the curve
+    /// is not joined across them.
+    breaks: Vec<f64>,
+    /// The expression with references to other functions (`f2(x)`) expanded,
+    /// when it has any; set by the tab before sampling.
+    resolved: Option<Result<String, PlotError>>,
+    /// The last source text parsed and its parse, reused while the text is
+    /// unchanged so that resampling doesn't reparse.
+    parsed: Option<(String, Result<meval::Expr, PlotError>)>,
+    /// Point at which to estimate the limit, and the resulting readout.
+    limit_at: String,
+    limit_result: Option<Result<String, String>>,
+    /// Whether to draw the Taylor polynomial of order `taylor_order` about
+    /// `taylor_center`, and its samples.
+    show_taylor: bool,
+    taylor_center: String,
+    taylor_order: usize,
+    taylor: Option<Result<Vec<[f64; 2]>, String>>,
+    /// Whether to classify the function as even or odd, the result (`None`
+    /// when f(-x) is undefined at every sample), and whether to draw the
+    /// mirror image of the curve, the graph of f(-x).
+    show_symmetry: bool,
+    symmetry: Option<Symmetry>,
+    show_reflection: bool,
+    /// Name of the group this function is listed under; empty for none.
+    group: String,
+    /// Rigorous mode: bound the function between each pair of samples with
+    /// interval arithmetic, and draw the extents that reach past the samples.
+    rigorous: bool,
+    /// `[x, low, high]` of each such extent, drawn as a vertical line.
+    extents: Vec<[f64; 3]>,
+    /// Why rigorous mode fell back to plain point sampling.
+    rigorous_note: Option<String>,
+    /// Complex mode: evaluate over the complex numbers (x stays real) and
+    /// plot only `complex_part` of the result.
+    complex_mode: bool,
+    complex_part: ComplexPart,
 }
 
 impl FunctionPlot {
     fn new(expression: &str, color: egui::Color32) -> Self {
         Self {
+            mode: PlotMode::Cartesian,
             expression: expression.to_owned(),
+            parametric: ParametricInputs::default(),
+            polar: PolarInputs::default(),
             error_message: None,
             plot_points: Vec::new(),
             color,
+            color_locked: false,
+            palette_slot: 0,
+            uncertainty_expr: None,
+            band_lower: Vec::new(),
+            band_upper: Vec::new(),
+            plot_inverse: false,
+            show_asymptotes: false,
+            asymptotes: Vec::new(),
+            expanded: false,
+            sign_strip: SignStrip::Off,
+            transform: CurveTransform::default(),
+            decimate: false,
+            simplified: None,
+            interpolation: Interpolation::Straight,
+            secondary_axis: false,
+            close_curve: false,
+            slice_enabled: false,
+            slice_variable: "a".to_owned(),
+            slice_values: "0.5, 1, 2".to_owned(),
+            slices: Vec::new(),
+            y_range: None,
+            breaks: Vec::new(),
+            resolved: None,
+            parsed: None,
+            limit_at: String::new(),
+            limit_result: None,
+            show_taylor: false,
+            taylor_center: "0".to_owned(),
+            taylor_order: 3,
+            taylor: None,
+            show_symmetry: false,
+            symmetry: None,
+            show_reflection: false,
+            group: String::new(),
+            rigorous: false,
+            extents: Vec::new(),
+            rigorous_note: None,
+            complex_mode: false,
+            complex_part: ComplexPart::Modulus,
         }
     }
 
-    /// Updates the plot points for this function given the x-range and sample count.
-    fn update(&mut self, x_min: f64, x_max: f64, num_points: usize) {
-        // Try to parse the expression.
-        let expr = match meval::Expr::from_str(&self.expression) {
-            Ok(e) => e,
-            Err(e) => {
-                self.error_message = Some(format!("Parse error: {}", e));
-                self.plot_points.clear();
-                return;
-            }
-        };
-
-        // Bind the variable "x" so we get a function f(x).
-        let func = match expr.bind("x") {
-            Ok(f) => f,
-            Err(e) => {
-                self.error_message = Some(format!("Binding error: {}", e));
-                self.plot_points.clear();
-                return;
-            }
-        };
+    /// Points used for drawing and export: the simplified set when there is one.
+    fn drawn_points(&self) -> &[[f64; 2]] {
+        self.simplified.as_deref().unwrap_or(&self.plot_points)
+    }
 
-        // Clear any previous error and compute the new points.
-        self.error_message = None;
-        self.plot_points.clear();
+    /// The first point again, appended when drawing a closed curve so that
+    /// sampling gaps at the ends don't leave the loop open.
+    fn closing_point(&self) -> Option<[f64; 2]> {
+        let closes = self.close_curve && self.mode != PlotMode::Cartesian && self.breaks.is_empty();
+        closes
+            .then(|| self.drawn_points().first().copied())
+            .flatten()
+    }
 
-        for i in 0..=num_points {
-            let x = x_min + (x_max - x_min) * (i as f64) / (num_points as f64);
-            let y = func(x);
-            if y.is_finite() {
-                self.plot_points.push([x, y]);
-            }
+    /// Maps a sampled point to where it is drawn (swapping axes for the inverse).
+    fn display_point(&self, [x, y]: [f64; 2]) -> [f64; 2] {
+        if self.plot_inverse {
+            [y, x]
+        } else {
+            [x, y]
         }
     }
-}
-
-/// Main application structure.
-struct App {
-    // Domain inputs for the x-axis.
-    x_min_input: String,
-    x_max_input: String,
-    // Domain inputs for the y-axis.
-    y_min_input: String,
-    y_max_input: String,
-    // A list of functions to plot.
-    functions: Vec<FunctionPlot>,
-    // Number of sample points per function.
-    num_points: usize,
-    // Whether the plot updates automatically as you type.
-    auto_update: bool,
-    // Error messages for the domain settings.
-    domain_error: Option<String>,
-}
 
-impl Default for App {
-    fn default() -> Self {
-        // Prepopulate with two sample functions.
-        let default_colors = vec![
-            egui::Color32::from_rgb(220, 20, 60),   // Crimson
-            egui::Color32::from_rgb(65, 105, 225),   // RoyalBlue
-        ];
-        let functions = vec![
-            FunctionPlot::new("abs(ln(x-1)/ln(x-2))", default_colors[0]),
-            FunctionPlot::new("sin(x)", default_colors[1]),
-        ];
+    /// Recomputes `y_range` from the current samples.
+    fn update_y_range(&mut self) {
+        self.y_range = self
+            .plot_points
+            .iter()
+            .chain(self.slices.iter().flat_map(|(_, points)| points))
+            .map(|&p| self.display_point(p)[1])
+            .fold(None, |range, y| match range {
+                None => Some((y, y)),
+                Some((lo, hi)) => Some((f64::min(lo, y), f64::max(hi, y))),
+            });
+    }
 
-        Self {
-            x_min_input: "-10.0".to_owned(),
-            x_max_input: "10.0".to_owned(),
-            y_min_input: "-10.0".to_owned(),
-            y_max_input: "10.0".to_owned(),
-            functions,
-            num_points: 1000,
-            auto_update: true,
-            domain_error: None,
+    /// The plotted formula as typed, for labels.
+    fn formula(&self) -> String {
+        match self.mode {
+            PlotMode::Cartesian if self.complex_mode => {
+                self.complex_part.wrap(self.expression.trim())
+            }
+            PlotMode::Cartesian => self.expression.trim().to_owned(),
+            PlotMode::Parametric => format!(
+                "({}, {})",
+                self.parametric.x.trim(),
+                self.parametric.y.trim()
+            ),
+            PlotMode::Polar => format!("r = {}", self.polar.r.trim()),
         }
     }
-}
 
-impl App {
-    /// Update all functions (and validate the domain settings).
-    fn update_functions(&mut self) {
-        // Parse x-domain.
-        let x_min: f64 = match self.x_min_input.trim().parse() {
-            Ok(val) => val,
-            Err(_) => {
-                self.domain_error = Some("Invalid x_min value".to_owned());
-                return;
-            }
+    /// Samples a parametric or polar curve at `num_points + 1` parameter values,
+    /// keeping the finite points.
+    fn sample_curve(
+        &self,
+        num_points: usize,
+        options: &ParseOptions,
+        constants: &[Constant],
+    ) -> Result<Vec<[f64; 2]>, PlotError> {
+        let with_variable = |variable: &str| ParseOptions {
+            variable: variable.to_owned(),
+            ..options.clone()
         };
-        let x_max: f64 = match self.x_max_input.trim().parse() {
-            Ok(val) => val,
-            Err(_) => {
-                self.domain_error = Some("Invalid x_max value".to_owned());
-                return;
+        // Maps the parameter to a point of the curve.
+        type Curve = Box<dyn Fn(f64) -> [f64; 2]>;
+        let (start, end, point): (f64, f64, Curve) = match self.mode {
+            PlotMode::Cartesian => return Ok(Vec::new()),
+            PlotMode::Parametric => {
+                let inputs = &self.parametric;
+                let options = with_variable("t");
+                let x = compile_expression(&inputs.x, &options, constants)?;
+                let y = compile_expression(&inputs.y, &options, constants)?;
+                (
+                    parse_bound(&inputs.t_min, "t min")?,
+                    parse_bound(&inputs.t_max, "t max")?,
+                    Box::new(move |t| [x(t), y(t)]),
+                )
+            }
+            PlotMode::Polar => {
+                let inputs = &self.polar;
+                let r = compile_expression(&inputs.r, &with_variable("theta"), constants)?;
+                (
+                    parse_bound(&inputs.theta_min, "theta min")?,
+                    parse_bound(&inputs.theta_max, "theta max")?,
+                    Box::new(move |theta| {
+                        let r = r(theta);
+                        [r * theta.cos(), r * theta.sin()]
+                    }),
+                )
             }
         };
-        if x_min >= x_max {
-            self.domain_error = Some("x_min must be less than x_max".to_owned());
-            return;
-        }
+        Ok(x_grid(start, end, num_points)
+            .into_iter()
+            .map(point)
+            .filter(|p| p[0].is_finite() && p[1].is_finite())
+            .collect())
+    }
 
-        // Parse y-domain.
-        let y_min: f64 = match self.y_min_input.trim().parse() {
-            Ok(val) => val,
-            Err(_) => {
-                self.domain_error = Some("Invalid y_min value".to_owned());
-                return;
+    /// Legend entry for the function, e.g. "f2: sin(x) [-1, 1]".
+    fn legend_label(&self, index: usize, show_range: bool) -> String {
+        let label = format!("f{}: {}", index + 1, self.formula());
+        match self.y_range {
+            Some((lo, hi)) if show_range => {
+                // Four decimals at most; adding 0.0 turns -0 into 0.
+                let round = |v: f64| (v * 1e4).round() / 1e4 + 0.0;
+                format!("{} [{}, {}]", label, round(lo), round(hi))
             }
+            _ => label,
+        }
+    }
+
+    /// The expression as LaTeX, or as plain text when it doesn't parse.
+    fn latex(&self, options: &ParseOptions) -> String {
+        let mut source = match &self.resolved {
+            Some(Ok(text)) => text.clone(),
+            _ => self.expression.trim().to_owned(),
         };
-        let y_max: f64 = match self.y_max_input.trim().parse() {
-            Ok(val) => val,
-            Err(_) => {
-                self.domain_error = Some("Invalid y_max value".to_owned());
-                return;
+        if options.rpn {
+            source = preprocess::rpn_to_infix(&source, &rpn_arity).unwrap_or(source);
+        }
+        let mut source = preprocess::expand_let_bindings(&source).unwrap_or(source);
+        if options.radix_literals {
+            if let Ok(expanded) = preprocess::expand_radix_literals(&source) {
+                source = expanded;
             }
-        };
-        if y_min >= y_max {
-            self.domain_error = Some("y_min must be less than y_max".to_owned());
-            return;
         }
-        self.domain_error = None;
+        meval::Expr::from_str(&source)
+            .ok()
+            .and_then(|expr| latex::to_latex(&expr))
+            .unwrap_or(source)
+    }
 
-        // Update each function's plot points.
-        for f in &mut self.functions {
-            f.update(x_min, x_max, self.num_points);
-        }
+    /// Wraps the current expression as `prefix(expression)suffix`.
+    ///
+    /// The expression is always parenthesized so the result stays valid
+    /// regardless of operator precedence.
+    fn wrap_expression(&mut self, prefix: &str, suffix: &str) {
+        self.expression = format!("{}({}){}", prefix, self.expression.trim(), suffix);
     }
-}
 
-impl eframe::App for App {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        // When real-time update is enabled, update on every frame.
-        if self.auto_update {
-            self.update_functions();
+    /// Drops samples with |y| above `limit`, remembering where the curve breaks.
+    fn limit_y(&mut self, limit: f64) {
+        let breaks = &mut self.breaks;
+        self.plot_points.retain(|&[x, y]| {
+            let keep = y.abs() <= limit;
+            if !keep {
+                breaks.push(x);
+            }
+            keep
+        });
+        self.update_y_range();
+    }
+
+    /// The text compiled for this function: its expression with references
+    /// to other functions expanded.
+    fn source(&self) -> Result<&str, PlotError> {
+        match &self.resolved {
+            Some(Ok(text)) => Ok(text),
+            Some(Err(e)) => Err(e.clone()),
+            None => Ok(&self.expression),
         }
+    }
 
+    /// Parses the source again if it changed since the last parse.
+    fn reparse(&mut self, options: &ParseOptions) {
+        let Ok(source) = self.source() else {
+            return;
+        };
+        if !matches!(&self.parsed, Some((text, _)) if text == source) {
+            self.parsed = Some((source.to_owned(), parse_expression(source, options)));
+        }
+    }
+
+    /// Compiles the expression with this function's transform applied.
+    fn evaluator(
+        &self,
+        options: &ParseOptions,
+        constants: &[Constant],
+    ) -> Result<impl Fn(f64) -> f64, PlotError> {
+        if self.mode != PlotMode::Cartesian {
+            return Err(PlotError::Bind(
+                "only y = f(x) functions can be evaluated at x".to_owned(),
+            ));
+        }
+        let expr = self.parsed_expression(options)?;
+        // Binding checks the names in the expression in either mode.
+        let real = bind_expression(expr.clone(), options, constants)?;
+        let raw: Box<dyn Fn(f64) -> f64> = if self.complex_mode {
+            let program =
+                complex::Program::compile(&expr, &|name| constant_value(name, options, constants))
+                    .map_err(|name| {
+                        PlotError::Bind(format!("'{}' has no complex version", name))
+                    })?;
+            let part = self.complex_part;
+            Box::new(move |x| part.of(program.eval(x)))
+        } else {
+            Box::new(real)
+        };
+        let transform = self.transform;
+        Ok(move |x: f64| transform.apply(&raw, x))
+    }
+
+    /// The parsed source, reusing the last parse while the source is unchanged.
+    fn parsed_expression(&self, options: &ParseOptions) -> Result<meval::Expr, PlotError> {
+        let source = self.source()?;
+        match &self.parsed {
+            Some((text, parsed)) if text == source => parsed.clone(),
+            _ => parse_expression(source, options),
+        }
+    }
+
+    /// The parsed expression compiled for interval arithmetic, or the name of
+    /// the first function in it that interval arithmetic doesn't cover.
+    fn interval_program(
+        &self,
+        options: &ParseOptions,
+        constants: &[Constant],
+    ) -> Result<interval::Program, String> {
+        let expr = self.parsed_expression(options).map_err(|e| e.to_string())?;
+        interval::Program::compile(&expr, &|name| constant_value(name, options, constants))
+    }
+
+    /// Samples the expression once per listed value of the slice variable,
+    /// which is bound like a named constant.
+    fn sample_slices(
+        &self,
+        x_min: f64,
+        x_max: f64,
+        num_points: usize,
+        sampling: Sampling,
+        options: &ParseOptions,
+        constants: &[Constant],
+    ) -> Result<Vec<Slice>, PlotError> {
+        let name = self.slice_variable.trim();
+        if name.is_empty() {
+            return Err(PlotError::Bind("name the variable to slice".to_owned()));
+        }
+        let values = self
+            .slice_values
+            .split(',')
+            .filter(|v| !v.trim().is_empty())
+            .map(|v| parse_bound(v, "slice"))
+            .collect::<Result<Vec<f64>, PlotError>>()?;
+        if values.is_empty() {
+            return Err(PlotError::Domain(format!(
+                "List the values of {} to slice at",
+                name
+            )));
+        }
+        self.sample_sweep(
+            name,
+            &values,
+            (x_min, x_max, num_points, sampling),
+            options,
+            constants,
+        )
+    }
+
+    /// Samples the expression once per value of the parameter `name`, which
+    /// is bound like a named constant. `grid` is how `sample_function` samples.
+    fn sample_sweep(
+        &self,
+        name: &str,
+        values: &[f64],
+        (x_min, x_max, num_points, sampling): (f64, f64, usize, Sampling),
+        options: &ParseOptions,
+        constants: &[Constant],
+    ) -> Result<Vec<Slice>, PlotError> {
+        let mut bound: Vec<Constant> = constants
+            .iter()
+            .filter(|c| c.name.trim() != name)
+            .cloned()
+            .collect();
+        bound.push(Constant::new(name));
+        values
+            .iter()
+            .map(|&value| {
+                bound.last_mut().unwrap().value = value;
+                let f = self.evaluator(options, &bound)?;
+                Ok((
+                    value,
+                    sample_function(&f, x_min, x_max, num_points, sampling),
+                ))
+            })
+            .collect()
+    }
+
+    /// Updates the plot points for this function given the x-range and sample count.
+    fn update(
+        &mut self,
+        x_min: f64,
+        x_max: f64,
+        num_points: usize,
+        sampling: Sampling,
+        options: &ParseOptions,
+        constants: &[Constant],
+    ) {
+        self.band_lower.clear();
+        self.band_upper.clear();
+        self.asymptotes.clear();
+        self.extents.clear();
+        self.rigorous_note = None;
+        self.simplified = None;
+        self.breaks.clear();
+
+        // Curves in the other modes only have their points; the analysis below is for y = f(x).
+        if self.mode != PlotMode::Cartesian {
+            self.limit_result = None;
+            self.taylor = None;
+            self.symmetry = None;
+            match self.sample_curve(num_points, options, constants) {
+                Ok(points) => {
+                    self.error_message = points.is_empty().then(|| NO_FINITE_VALUES.to_owned());
+                    self.plot_points = points;
+                }
+                Err(e) => {
+                    self.error_message = Some(e.to_string());
+                    self.plot_points.clear();
+                }
+            }
+            self.update_y_range();
+            return;
+        }
+
+        self.reparse(options);
+
+        // Slices replace the single curve; like the other modes they skip the analysis.
+        self.slices.clear();
+        if self.slice_enabled {
+            self.limit_result = None;
+            self.taylor = None;
+            self.symmetry = None;
+            self.plot_points.clear();
+            self.error_message = self
+                .sample_slices(x_min, x_max, num_points, sampling, options, constants)
+                .map(|slices| self.slices = slices)
+                .err()
+                .map(|e| e.to_string());
+            self.update_y_range();
+            if self.error_message.is_none() && self.y_range.is_none() {
+                self.error_message = Some(NO_FINITE_VALUES.to_owned());
+            }
+            return;
+        }
+
+        let func = match self.evaluator(options, constants) {
+            Ok(f) => f,
+            Err(e) => {
+                self.error_message = Some(e.to_string());
+                self.plot_points.clear();
+                self.y_range = None;
+                return;
+            }
+        };
+
+        // Clear any previous error and compute the new points.
+        self.error_message = None;
+        let sampled =
+            sample_function_within(&func, x_min, x_max, num_points, sampling, SAMPLE_TIME_LIMIT);
+        self.plot_points = match sampled {
+            Ok(points) => points,
+            Err(e) => {
+                self.error_message = Some(e.to_string());
+                self.plot_points.clear();
+                self.y_range = None;
+                return;
+            }
+        };
+        self.update_y_range();
+        // The expression is fine but undefined everywhere sampled, e.g. ln(x) for x < 0.
+        if self.plot_points.is_empty() {
+            self.error_message = Some(NO_FINITE_VALUES.to_owned());
+        }
+
+        if self.show_asymptotes {
+            self.asymptotes = find_poles(&self.plot_points, &func);
+        }
+
+        if self.rigorous && self.complex_mode {
+            self.rigorous_note = Some("Point sampling only in complex mode".to_owned());
+        } else if self.rigorous {
+            match self.interval_program(options, constants) {
+                Ok(program) => {
+                    let t = self.transform;
+                    let point = Interval::point;
+                    let bound = |x: Interval| {
+                        point(t.b) * program.eval(point(t.a) * (x - point(t.h))) + point(t.k)
+                    };
+                    self.extents = interval_extents(&bound, &self.plot_points);
+                }
+                Err(name) => {
+                    self.rigorous_note = Some(format!(
+                        "Point sampling only: '{}' has no interval version",
+                        name
+                    ));
+                }
+            }
+        }
+
+        self.limit_result = match self.limit_at.trim() {
+            "" => None,
+            text => Some(
+                parse_finite(text)
+                    .map(|x| describe_limit(estimate_limit(&func, x)))
+                    .ok_or_else(|| "Invalid point".to_owned()),
+            ),
+        };
+
+        self.symmetry = if self.show_symmetry {
+            classify_symmetry(&func, &self.plot_points)
+        } else {
+            None
+        };
+
+        let range = self.y_range;
+        self.taylor = self.show_taylor.then(|| {
+            let center = parse_finite(&self.taylor_center).ok_or("Invalid center")?;
+            let coefficients = taylor_coefficients(&func, center, self.taylor_order);
+            if coefficients.iter().any(|c| !c.is_finite()) {
+                return Err(format!("Not differentiable at {}", center));
+            }
+            // Keep to within one curve height of the curve; the polynomial grows fast away from c.
+            let Some((low, high)) = range else {
+                return Ok(Vec::new());
+            };
+            let margin = (high - low).max(1.0);
+            Ok(self
+                .plot_points
+                .iter()
+                .map(|&[x, _]| [x, evaluate_taylor(&coefficients, center, x)])
+                .filter(|&[_, y]| y >= low - margin && y <= high + margin)
+                .collect())
+        });
+
+        // Evaluate the uncertainty band f(x) ± δ(x) on the same samples.
+        if let Some(ref uncertainty) = self.uncertainty_expr {
+            let delta = match compile_expression(uncertainty, options, constants) {
+                Ok(f) => f,
+                Err(e) => {
+                    self.error_message = Some(format!("Uncertainty δ(x): {}", e));
+                    return;
+                }
+            };
+            for &[x, y] in &self.plot_points {
+                let d = delta(x).abs();
+                if d.is_finite() {
+                    self.band_lower.push([x, y - d]);
+                    self.band_upper.push([x, y + d]);
+                }
+            }
+        }
+    }
+}
+
+/// Symmetry of a function about the y-axis or the origin.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Symmetry {
+    Even,
+    Odd,
+    /// Only the zero function is both.
+    Both,
+    Neither,
+}
+
+impl Symmetry {
+    fn label(self) -> &'static str {
+        match self {
+            Symmetry::Even => "Even: f(-x) = f(x)",
+            Symmetry::Odd => "Odd: f(-x) = -f(x)",
+            Symmetry::Both => "Even and odd (zero)",
+            Symmetry::Neither => "Neither even nor odd",
+        }
+    }
+}
+
+/// Relative tolerance when comparing f(-x) against ±f(x).
+const SYMMETRY_TOLERANCE: f64 = 1e-6;
+
+/// Compares f(-x) with f(x) and -f(x) at each sample. Samples where f(-x)
+/// is undefined are skipped; `None` if that leaves none.
+fn classify_symmetry(f: &dyn Fn(f64) -> f64, points: &[[f64; 2]]) -> Option<Symmetry> {
+    let scale = points.iter().fold(1.0, |m: f64, p| m.max(p[1].abs()));
+    let tolerance = SYMMETRY_TOLERANCE * scale;
+    let (mut even, mut odd, mut compared) = (true, true, false);
+    for &[x, y] in points {
+        let mirrored = f(-x);
+        if !mirrored.is_finite() {
+            continue;
+        }
+        compared = true;
+        even &= (mirrored - y).abs() <= tolerance;
+        odd &= (mirrored + y).abs() <= tolerance;
+    }
+    compared.then_some(match (even, odd) {
+        (true, true) => Symmetry::Both,
+        (true, false) => Symmetry::Even,
+        (false, true) => Symmetry::Odd,
+        (false, false) => Symmetry::Neither,
+    })
+}
+
+/// Highest Taylor polynomial order offered; finite differences of higher
+/// orders are dominated by rounding errors.
+const MAX_TAYLOR_ORDER: usize = 10;
+
+/// Taylor coefficients `f^(k)(c) / k!` for `k = 0..=order`.
+///
+/// Each derivative is a central finite difference of order `k`, with a step
+/// balancing truncation against rounding error. The error still grows quickly
+/// with `k`, so high orders are only rough.
+fn taylor_coefficients(f: &dyn Fn(f64) -> f64, c: f64, order: usize) -> Vec<f64> {
+    let mut factorial = 1.0;
+    let mut binomials = vec![1.0];
+    (0..=order)
+        .map(|k| {
+            if k > 0 {
+                factorial *= k as f64;
+                // Row k of Pascal's triangle.
+                binomials = std::iter::once(1.0)
+                    .chain(binomials.windows(2).map(|w| w[0] + w[1]))
+                    .chain(std::iter::once(1.0))
+                    .collect();
+            }
+            let h = f64::EPSILON.powf(1.0 / (k as f64 + 2.0)) * c.abs().max(1.0);
+            let difference: f64 = binomials
+                .iter()
+                .enumerate()
+                .map(|(j, binomial)| {
+                    let sign = if j % 2 == 0 { 1.0 } else { -1.0 };
+                    sign * binomial * f(c + (k as f64 / 2.0 - j as f64) * h)
+                })
+                .sum();
+            difference / h.powi(k as i32) / factorial
+        })
+        .collect()
+}
+
+/// Value at `x` of the Taylor polynomial about `c` with the given coefficients.
+fn evaluate_taylor(coefficients: &[f64], c: f64, x: f64) -> f64 {
+    coefficients
+        .iter()
+        .rev()
+        .fold(0.0, |sum, &a| sum * (x - c) + a)
+}
+
+/// A lighter shade of dark colors and a darker shade of light ones, to tell
+/// a curve's approximations apart from the curve.
+fn contrasting_shade(color: egui::Color32) -> egui::Color32 {
+    let target = if relative_luminance(color) < 0.3 {
+        255.0
+    } else {
+        0.0
+    };
+    let mix = |c: u8| ((c as f64 + target) / 2.0).round() as u8;
+    egui::Color32::from_rgb(mix(color.r()), mix(color.g()), mix(color.b()))
+}
+
+/// Evaluates an expression without the plot variable, such as `sin(pi/4) * sqrt(2)`,
+/// with the same rewrites and constants as the plotted functions.
+///
+/// Every name must be a named or built-in constant, whether or not the
+/// variable is set: otherwise an unknown name would bind as the variable.
+fn evaluate_constant(
+    text: &str,
+    options: &ParseOptions,
+    constants: &[Constant],
+) -> Result<f64, PlotError> {
+    let expr = parse_expression(text, options)?;
+    let known = |name: &str| {
+        constants.iter().any(|c| c.name.trim() == name)
+            || BUILTIN_CONSTANTS
+                .iter()
+                .any(|&(builtin, _)| builtin == name)
+    };
+    for token in expr.iter() {
+        if let meval::tokenizer::Token::Var(name) = token {
+            if !known(name) {
+                return Err(PlotError::Bind(match name == options.variable.trim() {
+                    true => format!("'{}' has no value in the calculator", name),
+                    false => format!("unknown variable '{}'", name),
+                }));
+            }
+        }
+    }
+    bind_expression(expr, options, constants).map(|f| f(0.0))
+}
+
+/// Lowest autocorrelation at which a shifted copy of the samples counts as a repeat.
+const PERIOD_CORRELATION: f64 = 0.8;
+
+/// Fundamental period of evenly spaced samples: the first peak of their
+/// autocorrelation after it has dropped below zero, refined between samples
+/// with a parabola. The samples must cover at least two periods.
+///
+/// Returns `None` for unevenly spaced samples (log sampling, gaps) or when
+/// no shift repeats the samples closely enough.
+fn detect_period(points: &[[f64; 2]]) -> Option<f64> {
+    let n = points.len();
+    if n < 8 {
+        return None;
+    }
+    let dx = (points[n - 1][0] - points[0][0]) / (n - 1) as f64;
+    let uneven = points
+        .windows(2)
+        .any(|w| (w[1][0] - w[0][0] - dx).abs() > 1e-6 * dx);
+    if dx <= 0.0 || uneven {
+        return None;
+    }
+    let mean = points.iter().map(|p| p[1]).sum::<f64>() / n as f64;
+    let ys: Vec<f64> = points.iter().map(|p| p[1] - mean).collect();
+    // Normalized over the overlap only, so long lags aren't penalized.
+    let correlation: Vec<f64> = (0..=n / 2)
+        .map(|lag| {
+            let (a, b) = (&ys[..n - lag], &ys[lag..]);
+            dot(a, b) / (dot(a, a) * dot(b, b)).sqrt()
+        })
+        .collect();
+    let first_negative = correlation.iter().position(|&r| r < 0.0)?;
+    let lag = (first_negative.max(1)..correlation.len() - 1).find(|&k| {
+        let r = correlation[k];
+        r >= PERIOD_CORRELATION && r >= correlation[k - 1] && r >= correlation[k + 1]
+    })?;
+    let (before, peak, after) = (correlation[lag - 1], correlation[lag], correlation[lag + 1]);
+    let curvature = before - 2.0 * peak + after;
+    let shift = if curvature < 0.0 {
+        0.5 * (before - after) / curvature
+    } else {
+        0.0
+    };
+    Some((lag as f64 + shift) * dx)
+}
+
+/// Shown when an expression binds but has no finite value anywhere in the domain.
+const NO_FINITE_VALUES: &str = "No finite values in the current domain";
+
+/// A slice value with the curve sampled at it.
+type Slice = (f64, Vec<[f64; 2]>);
+
+/// Evaluates `f` at `num_points + 1` positions across the domain, keeping
+/// the finite results. A zero-width domain is a single evaluation.
+fn sample_function(
+    f: &dyn Fn(f64) -> f64,
+    x_min: f64,
+    x_max: f64,
+    num_points: usize,
+    sampling: Sampling,
+) -> Vec<[f64; 2]> {
+    sample_function_within(f, x_min, x_max, num_points, sampling, Duration::MAX).unwrap_or_default()
+}
+
+/// How long sampling one function may take before it is given up.
+const SAMPLE_TIME_LIMIT: Duration = Duration::from_secs(2);
+
+/// How many samples are taken between checks of the time limit.
+const SAMPLES_PER_CHECK: usize = 64;
+
+/// Like `sample_function`, but fails with `PlotError::Timeout` once sampling
+/// has taken `limit`.
+fn sample_function_within(
+    f: &dyn Fn(f64) -> f64,
+    x_min: f64,
+    x_max: f64,
+    num_points: usize,
+    sampling: Sampling,
+    limit: Duration,
+) -> Result<Vec<[f64; 2]>, PlotError> {
+    // Guard against a zero sample count, which would divide by zero below.
+    let num_points = num_points.max(1);
+    let intervals = if x_min == x_max {
+        0
+    } else {
+        sampling.intervals(x_min, x_max, num_points)
+    };
+    let started = Instant::now();
+    let mut points = Vec::new();
+    for i in 0..=intervals {
+        if i % SAMPLES_PER_CHECK == 0 && started.elapsed() >= limit {
+            return Err(PlotError::Timeout);
+        }
+        let x = sampling.x(x_min, x_max, i, num_points);
+        let y = f(x);
+        if y.is_finite() {
+            points.push([x, y]);
+        }
+    }
+    // A negative step samples downwards; curves are kept in ascending x.
+    if matches!(sampling, Sampling::Step(step) if step < 0.0) {
+        points.reverse();
+    }
+    Ok(points)
+}
+
+/// How far, as a fraction of the curve's height, the bounds between two
+/// samples must reach past both of them to be drawn in rigorous mode.
+const EXTENT_TOLERANCE: f64 = 0.01;
+
+/// Bounds `f` over the interval between each pair of neighbouring `points`
+/// and returns `[x, low, high]` wherever they reach past the pair: where the
+/// function may spike between the samples. Unbounded ends are cut off one
+/// curve height beyond the curve.
+fn interval_extents(f: &dyn Fn(Interval) -> Interval, points: &[[f64; 2]]) -> Vec<[f64; 3]> {
+    let Some((low, high)) = points.iter().fold(None, |range, &[_, y]| match range {
+        None => Some((y, y)),
+        Some((lo, hi)) => Some((y.min(lo), y.max(hi))),
+    }) else {
+        return Vec::new();
+    };
+    let height = (high - low).max(1e-12);
+    let tolerance = EXTENT_TOLERANCE * height;
+    points
+        .windows(2)
+        .filter_map(|pair| {
+            let [[x0, y0], [x1, y1]] = [pair[0], pair[1]];
+            let bounds = f(Interval::new(x0, x1));
+            let reaches_past =
+                bounds.lo < y0.min(y1) - tolerance || bounds.hi > y0.max(y1) + tolerance;
+            (!bounds.is_empty() && reaches_past).then(|| {
+                let cut = |bound: f64, end: f64| if bound.is_finite() { bound } else { end };
+                [
+                    0.5 * (x0 + x1),
+                    cut(bounds.lo, low - height),
+                    cut(bounds.hi, high + height),
+                ]
+            })
+        })
+        .collect()
+}
+
+/// Index of the function named by a reference such as `f2` (1-based names).
+fn function_reference(name: &str) -> Option<usize> {
+    let digits = name.strip_prefix('f')?;
+    if digits.starts_with('0') || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse::<usize>().ok().map(|n| n - 1)
+}
+
+/// Expands calls such as `f2(t^2)` in the expression of function `index` by
+/// substituting the referenced expression (with its transform) for the call.
+///
+/// `stack` holds the functions being expanded, to report circular references.
+fn resolve_references(
+    functions: &[FunctionPlot],
+    index: usize,
+    variable: &str,
+    stack: &mut Vec<usize>,
+) -> Result<String, PlotError> {
+    if let Some(start) = stack.iter().position(|&i| i == index) {
+        let cycle: Vec<String> = stack[start..]
+            .iter()
+            .chain([&index])
+            .map(|i| format!("f{}", i + 1))
+            .collect();
+        return Err(PlotError::Reference(format!(
+            "circular reference {}",
+            cycle.join(" → ")
+        )));
+    }
+    let mut text =
+        preprocess::expand_let_bindings(&functions[index].expression).map_err(PlotError::Parse)?;
+    // `prev` is shorthand for the function defined just above, at the same input.
+    if preprocess::identifiers(&text)
+        .iter()
+        .any(|name| name == "prev")
+    {
+        if index == 0 {
+            return Err(PlotError::Reference(
+                "'prev' used in f1, which has no previous function".to_owned(),
+            ));
+        }
+        let call = format!("f{}({})", index, variable);
+        text = preprocess::replace_identifier(&text, "prev", &call);
+    }
+    stack.push(index);
+    // expand_calls reports errors as strings; keep our own to pass them through intact.
+    let mut failure = None;
+    let result = preprocess::expand_calls(
+        &text,
+        &|name| function_reference(name).is_some(),
+        &mut |name, argument| {
+            let expanded = function_reference(name)
+                .filter(|&j| j < functions.len())
+                .ok_or_else(|| PlotError::Reference(format!("no function named '{}'", name)))
+                .and_then(|target| match functions[target].mode {
+                    PlotMode::Cartesian => Ok(target),
+                    _ => Err(PlotError::Reference(format!(
+                        "{} is not a function of {}",
+                        name, variable
+                    ))),
+                })
+                .and_then(|target| {
+                    let body = resolve_references(functions, target, variable, stack)?;
+                    let t = functions[target].transform;
+                    if t == CurveTransform::default() {
+                        let input = format!("({})", argument);
+                        let call = preprocess::replace_identifier(&body, variable, &input);
+                        return Ok(format!("({})", call));
+                    }
+                    let input = format!("(({})*(({})-({})))", t.a, argument, t.h);
+                    let call = preprocess::replace_identifier(&body, variable, &input);
+                    Ok(format!("(({})*({})+({}))", t.b, call, t.k))
+                });
+            expanded.map_err(|e| {
+                let message = e.to_string();
+                failure = Some(e);
+                message
+            })
+        },
+    )
+    .map_err(|e| failure.take().unwrap_or(PlotError::Parse(e)));
+    stack.pop();
+    result
+}
+
+/// Numerical estimate of a one-sided limit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum OneSidedLimit {
+    Finite(f64),
+    /// Grows without bound; the value is the sign (±1).
+    Infinite(f64),
+    /// Neither settles nor blows up (e.g. oscillates).
+    Diverges,
+}
+
+impl std::fmt::Display for OneSidedLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            OneSidedLimit::Finite(v) => write!(f, "{:.6}", v),
+            OneSidedLimit::Infinite(sign) if sign > 0.0 => write!(f, "∞"),
+            OneSidedLimit::Infinite(_) => write!(f, "-∞"),
+            OneSidedLimit::Diverges => write!(f, "diverges"),
+        }
+    }
+}
+
+/// Estimates the left and right limits of `func` at `x` from evaluations at
+/// x ∓ ε for ε shrinking by factors of ten.
+fn estimate_limit(func: &dyn Fn(f64) -> f64, x: f64) -> (OneSidedLimit, OneSidedLimit) {
+    // Relative steps so that large x don't drown ε in rounding error.
+    let scale = x.abs().max(1.0);
+    let one_sided = |direction: f64| {
+        let values: Vec<f64> = (1..=8)
+            .map(|k| func(x + direction * scale * 10f64.powi(-k)))
+            .collect();
+        let last = values[values.len() - 1];
+        let previous = values[values.len() - 2];
+        if last.is_infinite() {
+            return OneSidedLimit::Infinite(last.signum());
+        }
+        if !last.is_finite() || !previous.is_finite() {
+            return OneSidedLimit::Diverges;
+        }
+        let growing = values[4..]
+            .windows(2)
+            .all(|w| w[1].abs() > 2.0 * w[0].abs() && w[1].signum() == w[0].signum());
+        if growing && last.abs() > 1e6 {
+            OneSidedLimit::Infinite(last.signum())
+        } else if (last - previous).abs() <= 1e-5 * (1.0 + last.abs()) {
+            OneSidedLimit::Finite(last)
+        } else {
+            OneSidedLimit::Diverges
+        }
+    };
+    (one_sided(-1.0), one_sided(1.0))
+}
+
+/// Formats both one-sided limits and whether they agree.
+fn describe_limit((left, right): (OneSidedLimit, OneSidedLimit)) -> String {
+    let agree = match (left, right) {
+        (OneSidedLimit::Finite(a), OneSidedLimit::Finite(b)) => {
+            (a - b).abs() <= 1e-4 * (1.0 + a.abs().max(b.abs()))
+        }
+        (OneSidedLimit::Infinite(a), OneSidedLimit::Infinite(b)) => a == b,
+        _ => false,
+    };
+    let limit = if agree {
+        right.to_string()
+    } else {
+        "does not exist".to_owned()
+    };
+    format!("left = {}, right = {}, limit = {}", left, right, limit)
+}
+
+/// Finds approximate pole locations between consecutive samples.
+///
+/// A pole is assumed where the sign flips and the function is larger in
+/// magnitude at the midpoint than at either sample (or not finite there);
+/// an ordinary root crossing shrinks towards the midpoint instead.
+fn find_poles(points: &[[f64; 2]], func: &dyn Fn(f64) -> f64) -> Vec<f64> {
+    points
+        .windows(2)
+        .filter_map(|w| {
+            let ([x0, y0], [x1, y1]) = (w[0], w[1]);
+            if y0.signum() == y1.signum() {
+                return None;
+            }
+            let mid = 0.5 * (x0 + x1);
+            let y_mid = func(mid).abs();
+            (!y_mid.is_finite() || y_mid > y0.abs().max(y1.abs())).then_some(mid)
+        })
+        .collect()
+}
+
+/// The root of `func` nearest to `x`: the sign change among the sampled
+/// `points` closest to `x`, refined by bisection. Sign changes at poles (see
+/// `find_poles`) don't count.
+fn nearest_root(func: &dyn Fn(f64) -> f64, points: &[[f64; 2]], x: f64) -> Option<f64> {
+    let poles = find_poles(points, func);
+    let (a, b) = points
+        .windows(2)
+        .filter_map(|w| {
+            let ([x0, y0], [x1, y1]) = (w[0], w[1]);
+            match (y0 == 0.0, y1 == 0.0) {
+                (true, _) => Some((x0, x0)),
+                (_, true) => Some((x1, x1)),
+                _ if y0.signum() != y1.signum() && !poles.contains(&(0.5 * (x0 + x1))) => {
+                    Some((x0, x1))
+                }
+                _ => None,
+            }
+        })
+        .min_by(|p, q| {
+            let distance = |(a, b): (f64, f64)| (a - x).max(x - b).max(0.0);
+            distance(*p).total_cmp(&distance(*q))
+        })?;
+    let (mut a, mut b) = (a, b);
+    let mut fa = func(a);
+    while fa != 0.0 {
+        let middle = 0.5 * (a + b);
+        // Stop once the bracket can't be split any further.
+        if middle <= a || middle >= b {
+            break;
+        }
+        let fm = func(middle);
+        if fm.signum() == fa.signum() {
+            (a, fa) = (middle, fm);
+        } else {
+            b = middle;
+        }
+    }
+    Some(if fa == 0.0 { a } else { 0.5 * (a + b) })
+}
+
+/// Why an expression or the domain could not be turned into a plot.
+///
+/// `Display` gives the message shown to the user.
+#[derive(Clone, Debug, PartialEq)]
+enum PlotError {
+    /// The expression text is malformed.
+    Parse(String),
+    /// The expression parsed but uses names that can't be bound.
+    Bind(String),
+    /// A call to another function (`f2(x)`) can't be expanded.
+    Reference(String),
+    /// The domain settings are invalid.
+    Domain(String),
+    /// Sampling took longer than `SAMPLE_TIME_LIMIT`.
+    Timeout,
+}
+
+impl std::fmt::Display for PlotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PlotError::Parse(message) => write!(f, "Parse error: {}", message),
+            PlotError::Bind(message) => write!(f, "Binding error: {}", message),
+            PlotError::Reference(message) => write!(f, "Reference error: {}", message),
+            PlotError::Domain(message) => f.write_str(message),
+            PlotError::Timeout => write!(
+                f,
+                "Evaluation took longer than {} s; try fewer points",
+                SAMPLE_TIME_LIMIT.as_secs()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PlotError {}
+
+/// A function available in expressions, as listed in the function reference.
+struct FunctionInfo {
+    name: &'static str,
+    /// Number of arguments; `None` for any number (two in RPN input).
+    arity: Option<usize>,
+    description: &'static str,
+    example: &'static str,
+    /// Our own functions, registered on top of meval's built-ins.
+    custom: Option<fn(f64) -> f64>,
+}
+
+impl FunctionInfo {
+    const fn builtin(name: &'static str, description: &'static str, example: &'static str) -> Self {
+        Self {
+            name,
+            arity: Some(1),
+            description,
+            example,
+            custom: None,
+        }
+    }
+}
+
+/// The functions available in expressions: meval's built-ins plus our own.
+const FUNCTIONS: &[FunctionInfo] = &[
+    FunctionInfo::builtin("sqrt", "Square root", "sqrt(x^2 + 1)"),
+    FunctionInfo::builtin("exp", "e raised to the argument", "exp(-x^2)"),
+    FunctionInfo::builtin("ln", "Natural logarithm", "ln(x)"),
+    FunctionInfo::builtin("abs", "Absolute value", "abs(x - 1)"),
+    FunctionInfo::builtin("sin", "Sine, in radians", "sin(2*x)"),
+    FunctionInfo::builtin("cos", "Cosine, in radians", "cos(x)"),
+    FunctionInfo::builtin("tan", "Tangent, in radians", "tan(x)"),
+    FunctionInfo::builtin("asin", "Inverse sine", "asin(x)"),
+    FunctionInfo::builtin("acos", "Inverse cosine", "acos(x)"),
+    FunctionInfo::builtin("atan", "Inverse tangent", "atan(x)"),
+    FunctionInfo::builtin("sinh", "Hyperbolic sine", "sinh(x)"),
+    FunctionInfo::builtin("cosh", "Hyperbolic cosine", "cosh(x)"),
+    FunctionInfo::builtin("tanh", "Hyperbolic tangent", "tanh(x)"),
+    FunctionInfo::builtin("asinh", "Inverse hyperbolic sine", "asinh(x)"),
+    FunctionInfo::builtin("acosh", "Inverse hyperbolic cosine", "acosh(x)"),
+    FunctionInfo::builtin("atanh", "Inverse hyperbolic tangent", "atanh(x)"),
+    FunctionInfo::builtin("floor", "Largest integer not above", "floor(x)"),
+    FunctionInfo::builtin("ceil", "Smallest integer not below", "ceil(x)"),
+    FunctionInfo::builtin(
+        "round",
+        "Nearest integer, halves away from zero",
+        "round(x)",
+    ),
+    FunctionInfo::builtin("signum", "Sign: -1 or 1", "signum(x)"),
+    FunctionInfo {
+        arity: Some(2),
+        ..FunctionInfo::builtin("atan2", "Angle of the point (x, y), y first", "atan2(x, 1)")
+    },
+    FunctionInfo {
+        arity: None,
+        ..FunctionInfo::builtin("max", "Largest of the arguments", "max(x, 0)")
+    },
+    FunctionInfo {
+        arity: None,
+        ..FunctionInfo::builtin("min", "Smallest of the arguments", "min(x, 1, x^2)")
+    },
+    FunctionInfo {
+        custom: Some(|x| x - x.floor()),
+        ..FunctionInfo::builtin("frac", "Fractional part, x - floor(x)", "frac(x)")
+    },
+];
+
+/// Constants available in every expression, besides the named constants of a tab.
+const BUILTIN_CONSTANTS: &[(&str, &str)] =
+    &[("pi", "π ≈ 3.14159"), ("e", "Euler's number ≈ 2.71828")];
+
+/// Number of arguments a function takes in RPN input; `None` for operands.
+fn rpn_arity(name: &str) -> Option<usize> {
+    let function = FUNCTIONS.iter().find(|f| f.name == name)?;
+    Some(function.arity.unwrap_or(2))
+}
+
+/// Levenshtein distance between two strings, counted in characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitute = diagonal + usize::from(ca != cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitute.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// The known function name closest to `name`, if one is close enough to be a typo.
+fn closest_function(name: &str) -> Option<&'static str> {
+    // Short names only tolerate a single edit, otherwise `sq` would suggest `ln`.
+    let limit = if name.chars().count() <= 3 { 1 } else { 2 };
+    FUNCTIONS
+        .iter()
+        .map(|f| (edit_distance(name, f.name), f.name))
+        .filter(|&(distance, _)| distance <= limit)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, known)| known)
+}
+
+/// The value of a name in an expression other than the variable: a named
+/// constant or one of meval's built-in ones. Like meval, the variable shadows
+/// constants of the same name.
+fn constant_value(name: &str, options: &ParseOptions, constants: &[Constant]) -> Option<f64> {
+    if name == options.variable.trim() {
+        return None;
+    }
+    constants
+        .iter()
+        .find(|c| c.name.trim() == name)
+        .map(|c| c.value)
+        .or_else(|| meval::Context::new().get_var(name))
+}
+
+/// Parses `text` (after applying the enabled rewrites) and binds it as a function of x.
+fn compile_expression(
+    text: &str,
+    options: &ParseOptions,
+    constants: &[Constant],
+) -> Result<impl Fn(f64) -> f64, PlotError> {
+    bind_expression(parse_expression(text, options)?, options, constants)
+}
+
+/// Applies the enabled rewrites to `text` and parses it.
+fn parse_expression(text: &str, options: &ParseOptions) -> Result<meval::Expr, PlotError> {
+    let infix;
+    let text = if options.rpn {
+        infix = preprocess::rpn_to_infix(text, &rpn_arity).map_err(PlotError::Parse)?;
+        &infix
+    } else {
+        text
+    };
+    // Rewrite syntax meval doesn't understand.
+    let mut source = preprocess::expand_let_bindings(text).map_err(PlotError::Parse)?;
+    if options.radix_literals {
+        source = preprocess::expand_radix_literals(&source).map_err(PlotError::Parse)?;
+    }
+
+    // Try to parse the expression. meval stays the authority on validity, but an
+    // unbalanced parenthesis gets a friendlier description than its own message.
+    meval::Expr::from_str(&source).map_err(|e| match preprocess::paren_problem(&source) {
+        Some(problem) => PlotError::Parse(problem),
+        None => PlotError::Parse(e.to_string()),
+    })
+}
+
+/// Binds a parsed expression as a function of the plot variable, with the
+/// named constants available.
+fn bind_expression(
+    expr: meval::Expr,
+    options: &ParseOptions,
+    constants: &[Constant],
+) -> Result<impl Fn(f64) -> f64, PlotError> {
+    // Make the named constants available alongside the built-ins (which
+    // already include floor, ceil and round).
+    let mut context = meval::Context::new();
+    for function in FUNCTIONS {
+        if let Some(f) = function.custom {
+            context.func(function.name, f);
+        }
+    }
+    for constant in constants {
+        context.var(constant.name.trim(), constant.value);
+    }
+
+    // meval only reports "unknown function"; point at the likely typo instead.
+    for token in expr.iter() {
+        if let meval::tokenizer::Token::Func(name, _) = token {
+            let unknown = matches!(
+                context.eval_func(name, &[]),
+                Err(meval::FuncEvalError::UnknownFunction)
+            );
+            if let Some(suggestion) = unknown.then(|| closest_function(name)).flatten() {
+                return Err(PlotError::Bind(format!(
+                    "unknown function '{}'; did you mean '{}'?",
+                    name, suggestion
+                )));
+            }
+        }
+    }
+
+    // Variables that neither the constants nor the built-ins provide.
+    let mut free: Vec<&str> = Vec::new();
+    for token in expr.iter() {
+        if let meval::tokenizer::Token::Var(name) = token {
+            if context.get_var(name).is_none() && !free.contains(&name.as_str()) {
+                free.push(name);
+            }
+        }
+    }
+    let variable = match options.variable.trim() {
+        "" => match free[..] {
+            // Without a free variable the expression is constant; any name binds.
+            [] => "x".to_owned(),
+            [name] => name.to_owned(),
+            _ => {
+                return Err(PlotError::Bind(format!(
+                    "cannot tell the variable apart from {}",
+                    free.join(", ")
+                )))
+            }
+        },
+        name => name.to_owned(),
+    };
+    if let Some(other) = free.iter().find(|&&name| name != variable) {
+        return Err(PlotError::Bind(format!(
+            "unknown variable '{}' (the variable is '{}')",
+            other, variable
+        )));
+    }
+
+    // Bind the independent variable so we get a function of one argument.
+    expr.bind_with_context(context, &variable)
+        .map_err(|e| PlotError::Bind(e.to_string()))
+}
+
+/// Returns true if the y-values of `points` never change direction.
+fn is_monotonic(points: &[[f64; 2]]) -> bool {
+    let steps = || points.windows(2).map(|w| w[1][1] - w[0][1]);
+    steps().all(|d| d >= 0.0) || steps().all(|d| d <= 0.0)
+}
+
+/// Returns true when two sampled curves are numerically the same within `tolerance`.
+///
+/// Both curves must have been sampled on the same x-grid and have the same
+/// finite points for the comparison to succeed.
+fn curves_coincide(a: &[[f64; 2]], b: &[[f64; 2]], tolerance: f64) -> bool {
+    !a.is_empty()
+        && a.len() == b.len()
+        && a.iter()
+            .zip(b)
+            .all(|(p, q)| p[0] == q[0] && (p[1] - q[1]).abs() <= tolerance)
+}
+
+/// Ramer–Douglas–Peucker simplification: keeps the fewest points such that
+/// every dropped point lies within `tolerance` of the simplified polyline.
+fn simplify_polyline(points: &[[f64; 2]], tolerance: f64) -> Vec<[f64; 2]> {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+    let mut keep = vec![false; points.len()];
+    keep[0] = true;
+    keep[points.len() - 1] = true;
+    // Ranges still to examine, as (first, last) index pairs.
+    let mut stack = vec![(0, points.len() - 1)];
+    while let Some((first, last)) = stack.pop() {
+        let ([x0, y0], [x1, y1]) = (points[first], points[last]);
+        let (dx, dy) = (x1 - x0, y1 - y0);
+        let length_sq = dx * dx + dy * dy;
+        let distance = |[x, y]: [f64; 2]| {
+            // Distance to the segment, clamping the projection to its ends.
+            let t = if length_sq > 0.0 {
+                (((x - x0) * dx + (y - y0) * dy) / length_sq).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            (x - (x0 + t * dx)).hypot(y - (y0 + t * dy))
+        };
+        let farthest = (first + 1..last)
+            .map(|i| (i, distance(points[i])))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((i, d)) = farthest {
+            if d > tolerance {
+                keep[i] = true;
+                stack.push((first, i));
+                stack.push((i, last));
+            }
+        }
+    }
+    points
+        .iter()
+        .zip(keep)
+        .filter_map(|(&p, k)| k.then_some(p))
+        .collect()
+}
+
+/// Evaluates the monotone cubic (PCHIP) interpolant through `points` at
+/// `steps` evenly spaced positions per interval.
+///
+/// Slopes follow Fritsch–Carlson, so the curve never overshoots the samples:
+/// it is flat at local extrema and monotone wherever the data is.
+fn pchip_resample(points: &[[f64; 2]], steps: usize) -> Vec<[f64; 2]> {
+    let n = points.len();
+    if n < 3 || points.windows(2).any(|w| w[1][0] <= w[0][0]) {
+        return points.to_vec();
+    }
+    let h: Vec<f64> = points.windows(2).map(|w| w[1][0] - w[0][0]).collect();
+    let delta: Vec<f64> = points
+        .windows(2)
+        .zip(&h)
+        .map(|(w, h)| (w[1][1] - w[0][1]) / h)
+        .collect();
+
+    let mut slopes = vec![0.0; n];
+    for k in 1..n - 1 {
+        if delta[k - 1] * delta[k] > 0.0 {
+            let w1 = 2.0 * h[k] + h[k - 1];
+            let w2 = h[k] + 2.0 * h[k - 1];
+            slopes[k] = (w1 + w2) / (w1 / delta[k - 1] + w2 / delta[k]);
+        }
+    }
+    // One-sided three-point estimates at the ends, limited to keep monotonicity.
+    let end_slope = |h0: f64, h1: f64, d0: f64, d1: f64| {
+        let d = ((2.0 * h0 + h1) * d0 - h0 * d1) / (h0 + h1);
+        if d.signum() != d0.signum() {
+            0.0
+        } else if d0.signum() != d1.signum() && d.abs() > 3.0 * d0.abs() {
+            3.0 * d0
+        } else {
+            d
+        }
+    };
+    slopes[0] = end_slope(h[0], h[1], delta[0], delta[1]);
+    slopes[n - 1] = end_slope(h[n - 2], h[n - 3], delta[n - 2], delta[n - 3]);
+
+    let steps = steps.max(1);
+    let mut out = Vec::with_capacity((n - 1) * steps + 1);
+    for k in 0..n - 1 {
+        let ([x0, y0], [_, y1]) = (points[k], points[k + 1]);
+        for i in 0..steps {
+            let t = i as f64 / steps as f64;
+            let (t2, t3) = (t * t, t * t * t);
+            let y = (2.0 * t3 - 3.0 * t2 + 1.0) * y0
+                + (t3 - 2.0 * t2 + t) * h[k] * slopes[k]
+                + (-2.0 * t3 + 3.0 * t2) * y1
+                + (t3 - t2) * h[k] * slopes[k + 1];
+            out.push([x0 + t * h[k], y]);
+        }
+    }
+    out.push(points[n - 1]);
+    out
+}
+
+/// Rule used to approximate the integral over each slice.
+#[derive(Clone, Copy, PartialEq)]
+enum RiemannRule {
+    Left,
+    Right,
+    Midpoint,
+    Trapezoid,
+}
+
+impl RiemannRule {
+    const ALL: [RiemannRule; 4] = [
+        RiemannRule::Left,
+        RiemannRule::Right,
+        RiemannRule::Midpoint,
+        RiemannRule::Trapezoid,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            RiemannRule::Left => "Left",
+            RiemannRule::Right => "Right",
+            RiemannRule::Midpoint => "Midpoint",
+            RiemannRule::Trapezoid => "Trapezoid",
+        }
+    }
+}
+
+/// Settings for the Riemann sum overlay.
+#[derive(Clone)]
+struct RiemannSettings {
+    enabled: bool,
+    /// Index of the function being integrated.
+    function: usize,
+    a_input: String,
+    b_input: String,
+    count: usize,
+    rule: RiemannRule,
+}
+
+impl Default for RiemannSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            function: 0,
+            a_input: "0".to_owned(),
+            b_input: "3".to_owned(),
+            count: 10,
+            rule: RiemannRule::Left,
+        }
+    }
+}
+
+/// Which mouse interactions the plot accepts; all on by default.
+struct PlotInteraction {
+    drag: bool,
+    zoom: bool,
+    scroll: bool,
+    boxed_zoom: bool,
+}
+
+impl Default for PlotInteraction {
+    fn default() -> Self {
+        Self {
+            drag: true,
+            zoom: true,
+            scroll: true,
+            boxed_zoom: true,
+        }
+    }
+}
+
+/// Most cells the small multiples grid shows.
+const MAX_SMALL_MULTIPLES: usize = 36;
+/// Size of each small multiple's plot, in points.
+const SMALL_MULTIPLE_SIZE: egui::Vec2 = egui::vec2(220.0, 150.0);
+
+/// Settings for drawing one function as a grid of small plots, one per
+/// value of a parameter.
+#[derive(Clone)]
+struct SmallMultiples {
+    enabled: bool,
+    /// Index of the function drawn.
+    function: usize,
+    /// The parameter, bound like a named constant, and its values: numbers
+    /// and `start:step:stop` ranges, separated by commas.
+    variable: String,
+    values: String,
+    columns: usize,
+}
+
+impl Default for SmallMultiples {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            function: 0,
+            variable: "a".to_owned(),
+            values: "0.5:0.5:3".to_owned(),
+            columns: 3,
+        }
+    }
+}
+
+/// Parses parameter values: numbers and `start:step:stop` ranges (including
+/// `stop` when the steps land on it), separated by commas.
+fn parse_sweep_values(text: &str) -> Result<Vec<f64>, String> {
+    let number =
+        |text: &str| parse_finite(text).ok_or_else(|| format!("'{}' is not a number", text.trim()));
+    let mut values = Vec::new();
+    for entry in text.split(',').filter(|entry| !entry.trim().is_empty()) {
+        match entry.split(':').collect::<Vec<_>>()[..] {
+            [value] => values.push(number(value)?),
+            [start, step, stop] => {
+                let (start, step, stop) = (number(start)?, number(step)?, number(stop)?);
+                if step <= 0.0 || stop < start {
+                    return Err(format!("'{}' has no values", entry.trim()));
+                }
+                // A little slack so that rounding doesn't drop `stop`. The count
+                // is checked before it becomes an integer: a tiny step makes it huge.
+                let count = ((stop - start) / step + 1e-9).floor() + 1.0;
+                if values.len() as f64 + count > MAX_SMALL_MULTIPLES as f64 {
+                    return Err(format!("At most {} values", MAX_SMALL_MULTIPLES));
+                }
+                values.extend((0..count as usize).map(|k| start + k as f64 * step));
+            }
+            _ => {
+                return Err(format!(
+                    "'{}' is neither a number nor start:step:stop",
+                    entry.trim()
+                ))
+            }
+        }
+    }
+    if values.len() > MAX_SMALL_MULTIPLES {
+        return Err(format!("At most {} values", MAX_SMALL_MULTIPLES));
+    }
+    if values.is_empty() {
+        return Err("List the parameter values".to_owned());
+    }
+    Ok(values)
+}
+
+/// Settings for shading where one function lies above or below another.
+#[derive(Clone)]
+struct InequalitySettings {
+    enabled: bool,
+    /// Indices of the functions on the left and right of the comparison.
+    left: usize,
+    right: usize,
+    /// Compare with `>` rather than `<`.
+    greater: bool,
+}
+
+impl Default for InequalitySettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            left: 0,
+            right: 1,
+            greater: true,
+        }
+    }
+}
+
+/// The maximal intervals of the grid `xs` on which `d` is positive.
+///
+/// An interval that ends between two samples ends where `d` crosses zero,
+/// interpolated linearly; next to an undefined sample it ends at the last
+/// defined one.
+fn positive_intervals(xs: &[f64], d: &dyn Fn(f64) -> f64) -> Vec<(f64, f64)> {
+    let crossing = |x0: f64, v0: f64, x1: f64, v1: f64| x0 + (x1 - x0) * v0 / (v0 - v1);
+    let mut intervals = Vec::new();
+    let mut start = None;
+    let mut previous: Option<(f64, f64)> = None;
+    for &x in xs {
+        let v = d(x);
+        let inside = v > 0.0;
+        match (start, previous) {
+            (None, _) if inside => {
+                start = Some(match previous {
+                    Some((px, pv)) if pv.is_finite() => crossing(px, pv, x, v),
+                    _ => x,
+                });
+            }
+            (Some(s), Some((px, pv))) if !inside => {
+                let end = if v.is_finite() {
+                    crossing(px, pv, x, v)
+                } else {
+                    px
+                };
+                intervals.push((s, end));
+                start = None;
+            }
+            _ => {}
+        }
+        previous = Some((x, v));
+    }
+    if let (Some(s), Some((last, _))) = (start, previous) {
+        intervals.push((s, last));
+    }
+    intervals
+}
+
+/// Splits `[a, b]` into `count` slices and returns the shape of each slice
+/// (as convex polygons) together with the approximated integral.
+///
+/// A trapezoid whose top crosses the axis is drawn as two triangles, since
+/// the crossed shape is not convex.
+fn riemann_sum(
+    f: impl Fn(f64) -> f64,
+    a: f64,
+    b: f64,
+    count: usize,
+    rule: RiemannRule,
+) -> (Vec<Vec<[f64; 2]>>, f64) {
+    let count = count.max(1);
+    let width = (b - a) / count as f64;
+    let mut shapes = Vec::with_capacity(count);
+    let mut sum = 0.0;
+    for i in 0..count {
+        let (x0, x1) = (grid_x(a, b, i, count), grid_x(a, b, i + 1, count));
+        let (y0, y1) = match rule {
+            RiemannRule::Left => (f(x0), f(x0)),
+            RiemannRule::Right => (f(x1), f(x1)),
+            RiemannRule::Midpoint => {
+                let y = f(0.5 * (x0 + x1));
+                (y, y)
+            }
+            RiemannRule::Trapezoid => (f(x0), f(x1)),
+        };
+        if !y0.is_finite() || !y1.is_finite() {
+            continue;
+        }
+        sum += 0.5 * (y0 + y1) * width;
+        if y0 * y1 < 0.0 {
+            let crossing = x0 + width * y0 / (y0 - y1);
+            shapes.push(vec![[x0, 0.0], [crossing, 0.0], [x0, y0]]);
+            shapes.push(vec![[crossing, 0.0], [x1, 0.0], [x1, y1]]);
+        } else {
+            shapes.push(vec![[x0, 0.0], [x1, 0.0], [x1, y1], [x0, y0]]);
+        }
+    }
+    (shapes, sum)
+}
+
+/// A measured (x, y) series loaded from a file, drawn as points.
+struct DataSeries {
+    name: String,
+    points: Vec<[f64; 2]>,
+    color: egui::Color32,
+    /// Whether to overlay a moving average, and over how many points.
+    smooth: bool,
+    window: usize,
+}
+
+/// Solves `a · x = b` by Gaussian elimination with partial pivoting.
+///
+/// Returns `None` when the matrix is singular.
+fn solve_linear(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    let n = b.len();
+    for col in 0..n {
+        let pivot = (col..n).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col].abs() < 1e-300 {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        let (upper, lower) = a.split_at_mut(col + 1);
+        let pivot_row = &upper[col];
+        for (offset, row) in lower.iter_mut().enumerate() {
+            let factor = row[col] / pivot_row[col];
+            for (value, pivot) in row[col..].iter_mut().zip(&pivot_row[col..]) {
+                *value -= factor * pivot;
+            }
+            b[col + 1 + offset] -= factor * b[col];
+        }
+    }
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let tail: f64 = (row + 1..n).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - tail) / a[row][row];
+    }
+    Some(x)
+}
+
+/// Fits the constants at indices `free` so that `model` passes as close as
+/// possible to `data`, in the least-squares sense.
+///
+/// Uses Gauss–Newton steps with Levenberg–Marquardt damping and a
+/// finite-difference Jacobian. Returns the fitted constants and the RMS of
+/// the residuals `y - f(x)`.
+fn fit_constants(
+    model: &FunctionPlot,
+    options: &ParseOptions,
+    constants: &[Constant],
+    free: &[usize],
+    data: &[[f64; 2]],
+) -> Result<(Vec<Constant>, f64), PlotError> {
+    let residuals = |constants: &[Constant]| -> Result<Vec<f64>, PlotError> {
+        let f = model.evaluator(options, constants)?;
+        Ok(data.iter().map(|&[x, y]| y - f(x)).collect())
+    };
+    let cost = |r: &[f64]| {
+        let sum: f64 = r.iter().map(|v| v * v).sum();
+        if sum.is_finite() {
+            sum
+        } else {
+            f64::INFINITY
+        }
+    };
+
+    let mut current = constants.to_vec();
+    let mut r = residuals(&current)?;
+    let mut current_cost = cost(&r);
+    if current_cost.is_infinite() {
+        return Err(PlotError::Domain(
+            "The model is not finite at every data point".to_owned(),
+        ));
+    }
+    let mut damping = 1e-3;
+    for _ in 0..200 {
+        // Jacobian of the residuals, one column per free constant.
+        let mut columns = Vec::with_capacity(free.len());
+        for &k in free {
+            let h = 1e-7 * current[k].value.abs().max(1.0);
+            let mut shifted = current.clone();
+            shifted[k].value += h;
+            let r_shifted = residuals(&shifted)?;
+            columns.push(
+                r_shifted
+                    .iter()
+                    .zip(&r)
+                    .map(|(a, b)| (a - b) / h)
+                    .collect::<Vec<f64>>(),
+            );
+        }
+        let jtj: Vec<Vec<f64>> = columns
+            .iter()
+            .map(|a| columns.iter().map(|b| dot(a, b)).collect())
+            .collect();
+        let gradient: Vec<f64> = columns.iter().map(|c| -dot(c, &r)).collect();
+
+        // Raise the damping until a step lowers the cost.
+        let mut improved = false;
+        while damping < 1e10 {
+            let mut damped = jtj.clone();
+            for (i, row) in damped.iter_mut().enumerate() {
+                row[i] += damping * jtj[i][i].max(1e-12);
+            }
+            if let Some(step) = solve_linear(damped, gradient.clone()) {
+                let mut trial = current.clone();
+                for (&k, delta) in free.iter().zip(&step) {
+                    trial[k].value += delta;
+                }
+                let r_trial = residuals(&trial)?;
+                let trial_cost = cost(&r_trial);
+                if trial_cost < current_cost {
+                    let converged = current_cost - trial_cost <= 1e-12 * current_cost;
+                    (current, r, current_cost) = (trial, r_trial, trial_cost);
+                    damping = (damping / 10.0).max(1e-12);
+                    improved = !converged;
+                    break;
+                }
+            }
+            damping *= 10.0;
+        }
+        if !improved {
+            break;
+        }
+    }
+    let rms = (current_cost / data.len() as f64).sqrt();
+    Ok((current, rms))
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// Settings for the Fourier series overlay.
+#[derive(Clone)]
+struct FourierSettings {
+    enabled: bool,
+    /// Index of the function being approximated.
+    function: usize,
+    /// Highest harmonic in the partial sum.
+    terms: usize,
+}
+
+impl Default for FourierSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            function: 0,
+            terms: 5,
+        }
+    }
+}
+
+/// Samples per period used to integrate the Fourier coefficients.
+const FOURIER_SAMPLES: usize = 4096;
+
+/// Coefficients `(a_n, b_n)` for `n = 0..=terms` of the Fourier series of `f`,
+/// treating `[x_min, x_max]` as one period.
+///
+/// The integrals use the midpoint rule, which is very accurate for periodic
+/// integrands. Non-finite values of `f` count as zero.
+fn fourier_coefficients(
+    f: impl Fn(f64) -> f64,
+    x_min: f64,
+    x_max: f64,
+    terms: usize,
+) -> Vec<(f64, f64)> {
+    let period = x_max - x_min;
+    let omega = 2.0 * std::f64::consts::PI / period;
+    let dx = period / FOURIER_SAMPLES as f64;
+    let samples: Vec<(f64, f64)> = (0..FOURIER_SAMPLES)
+        .map(|i| {
+            let x = x_min + (i as f64 + 0.5) * dx;
+            let y = f(x);
+            (x - x_min, if y.is_finite() { y } else { 0.0 })
+        })
+        .collect();
+    (0..=terms)
+        .map(|n| {
+            let k = omega * n as f64;
+            let (a, b) = samples.iter().fold((0.0, 0.0), |(a, b), &(t, y)| {
+                (a + y * (k * t).cos(), b + y * (k * t).sin())
+            });
+            (2.0 * a * dx / period, 2.0 * b * dx / period)
+        })
+        .collect()
+}
+
+/// Value at `x` of the partial sum with the given coefficients.
+fn fourier_partial_sum(coefficients: &[(f64, f64)], x_min: f64, x_max: f64, x: f64) -> f64 {
+    let omega = 2.0 * std::f64::consts::PI / (x_max - x_min);
+    let t = x - x_min;
+    coefficients
+        .iter()
+        .enumerate()
+        .map(|(n, &(a, b))| match n {
+            0 => a / 2.0,
+            _ => a * (omega * n as f64 * t).cos() + b * (omega * n as f64 * t).sin(),
+        })
+        .sum()
+}
+
+/// Splits `points` into runs that don't straddle any of the (ascending) `breaks`.
+fn split_at_breaks<'a>(points: &'a [[f64; 2]], breaks: &[f64]) -> Vec<&'a [[f64; 2]]> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+    let mut next_break = breaks.iter().peekable();
+    for i in 1..points.len() {
+        // Skip breaks that lie before this interval.
+        while next_break.next_if(|&&b| b <= points[i - 1][0]).is_some() {}
+        if next_break.peek().is_some_and(|&&b| b < points[i][0]) {
+            segments.push(&points[start..i]);
+            start = i;
+        }
+    }
+    if start < points.len() {
+        segments.push(&points[start..]);
+    }
+    segments
+}
+
+/// Samples `a - b` on the shared x-grid, skipping points where either curve is
+/// non-finite. Points are matched by x value, since either curve may have
+/// dropped samples the other kept.
+fn difference_curve(a: &[[f64; 2]], b: &[[f64; 2]]) -> Vec<[f64; 2]> {
+    let b_at: std::collections::HashMap<u64, f64> =
+        b.iter().map(|q| (q[0].to_bits(), q[1])).collect();
+    a.iter()
+        .filter_map(|p| b_at.get(&p[0].to_bits()).map(|y| [p[0], p[1] - y]))
+        .filter(|p| p[1].is_finite())
+        .collect()
+}
+
+/// Messages sent from a background export to the UI thread.
+enum ExportEvent {
+    /// Frames written so far, out of the total.
+    Progress(usize, usize),
+    Done(Result<String, String>),
+}
+
+/// File formats for single-image export.
+#[derive(Clone, Copy)]
+enum ImageFormat {
+    Png,
+    Svg,
+}
+
+/// Converts the drawable functions into curves for the exporter.
+fn export_curves(functions: &[FunctionPlot]) -> Vec<export::ExportCurve> {
+    functions
+        .iter()
+        .flat_map(|f| {
+            let mut segments: Vec<Vec<[f64; 2]>> = match f.plot_points.is_empty() {
+                true => Vec::new(),
+                false => split_at_breaks(f.drawn_points(), &f.breaks)
+                    .into_iter()
+                    .map(<[_]>::to_vec)
+                    .collect(),
+            };
+            // A closed curve has no breaks, so it is a single segment.
+            if let (Some(first), Some(segment)) = (f.closing_point(), segments.first_mut()) {
+                segment.push(first);
+            }
+            segments
+                .into_iter()
+                .chain(f.slices.iter().map(|(_, points)| points.clone()))
+                .map(|points| export::ExportCurve {
+                    points,
+                    color: f.color,
+                    width: 2.0,
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Settings for rendering a sweep of one constant as numbered PNG frames.
+struct AnimationExport {
+    scene: export::Scene,
+    functions: Vec<FunctionPlot>,
+    constants: Vec<Constant>,
+    options: ParseOptions,
+    num_points: usize,
+    sampling: Sampling,
+    /// Index of the constant swept from its `min` to its `max`.
+    constant: usize,
+    frames: usize,
+    directory: PathBuf,
+    size: (u32, u32),
+    line_width: f32,
+}
+
+impl AnimationExport {
+    /// Writes `frame_0001.png`, `frame_0002.png`, ... reporting progress through `sender`.
+    ///
+    /// Works on its own copies of the functions and constants, so the live UI is untouched.
+    fn run(mut self, sender: &mpsc::Sender<ExportEvent>) -> Result<String, String> {
+        std::fs::create_dir_all(&self.directory)
+            .map_err(|e| format!("Cannot create {}: {}", self.directory.display(), e))?;
+        let (x_min, x_max) = self.scene.x_range;
+        let (start, end) = (
+            self.constants[self.constant].min,
+            self.constants[self.constant].max,
+        );
+        let frames = self.frames.max(2);
+        for frame in 0..frames {
+            let t = frame as f64 / (frames - 1) as f64;
+            self.constants[self.constant].value = start + (end - start) * t;
+            for f in &mut self.functions {
+                f.update(
+                    x_min,
+                    x_max,
+                    self.num_points,
+                    self.sampling,
+                    &self.options,
+                    &self.constants,
+                );
+            }
+            self.scene.curves = export_curves(&self.functions);
+            self.scene.set_line_width(self.line_width);
+            let path = self.directory.join(format!("frame_{:04}.png", frame + 1));
+            export::write_png(&path, &self.scene, self.size.0, self.size.1)?;
+            // A closed receiver only means nobody is watching the progress.
+            let _ = sender.send(ExportEvent::Progress(frame + 1, frames));
+        }
+        Ok(format!(
+            "Saved {} frames to {}",
+            frames,
+            self.directory.display()
+        ))
+    }
+}
+
+/// Actions that can be invoked from the command palette.
+#[derive(Clone, Copy, PartialEq)]
+enum Command {
+    TogglePresentation,
+    AddFunction,
+    PasteExpressions,
+    Plot,
+    ToggleAutoUpdate,
+    Snapshot,
+    ClearSnapshot,
+    ExportPng,
+    ExportSvg,
+    ExportAnimation,
+    ExportArchive,
+    LoadTheme,
+    ClearTheme,
+    ToggleHighContrast,
+    TogglePerformance,
+    FunctionHelp,
+    WatchFile,
+    Unwatch,
+}
+
+impl Command {
+    /// Every command, in the order shown in the palette.
+    const ALL: [Command; 18] = [
+        Command::TogglePresentation,
+        Command::AddFunction,
+        Command::PasteExpressions,
+        Command::Plot,
+        Command::ToggleAutoUpdate,
+        Command::Snapshot,
+        Command::ClearSnapshot,
+        Command::ExportPng,
+        Command::ExportSvg,
+        Command::ExportAnimation,
+        Command::ExportArchive,
+        Command::LoadTheme,
+        Command::ClearTheme,
+        Command::ToggleHighContrast,
+        Command::TogglePerformance,
+        Command::FunctionHelp,
+        Command::WatchFile,
+        Command::Unwatch,
+    ];
+
+    /// Label shown (and searched) in the palette.
+    fn label(self) -> &'static str {
+        match self {
+            Command::AddFunction => "Add function",
+            Command::Plot => "Plot now",
+            Command::ToggleAutoUpdate => "Toggle real-time update",
+            Command::Snapshot => "Take snapshot",
+            Command::ClearSnapshot => "Clear snapshot",
+            Command::TogglePresentation => "Toggle presentation mode",
+            Command::PasteExpressions => "Paste expressions",
+            Command::ExportPng => "Export PNG",
+            Command::ExportSvg => "Export SVG",
+            Command::ExportAnimation => "Export animation",
+            Command::ExportArchive => "Export archive (session, data and PNG)",
+            Command::LoadTheme => "Load theme file",
+            Command::ClearTheme => "Use the built-in theme",
+            Command::ToggleHighContrast => "Toggle high-contrast mode",
+            Command::TogglePerformance => "Toggle performance overlay",
+            Command::FunctionHelp => "Show the function reference",
+            Command::WatchFile => "Watch expressions file",
+            Command::Unwatch => "Stop watching expressions file",
+        }
+    }
+}
+
+/// How many label heights a label may be nudged up or down to get clear of
+/// the labels placed before it.
+const LABEL_NUDGES: usize = 3;
+
+/// The first of `rect`, or `rect` moved up or down by whole label heights,
+/// that overlaps none of `placed`; `None` if there is no room nearby.
+fn free_label_spot(placed: &[egui::Rect], rect: egui::Rect) -> Option<egui::Rect> {
+    let step = rect.height();
+    (0..=LABEL_NUDGES)
+        .flat_map(|k| [-(k as f32), k as f32])
+        .map(|k| rect.translate(egui::vec2(0.0, k * step)))
+        .find(|candidate| {
+            // Labels that only touch don't overlap.
+            let overlaps = |r: &egui::Rect| {
+                let shared = r.intersect(*candidate);
+                shared.width() > 0.0 && shared.height() > 0.0
+            };
+            !placed.iter().any(overlaps)
+        })
+}
+
+/// Draws a label at `point`, nudged out of the way of the labels already in
+/// `placed` (screen rectangles), or leaves it out when it would only overlap.
+fn place_label(
+    plot_ui: &mut PlotUi,
+    placed: &mut Vec<egui::Rect>,
+    point: PlotPoint,
+    text: String,
+    color: egui::Color32,
+    anchor: egui::Align2,
+) {
+    // Plot labels use the small text style.
+    let font = egui::TextStyle::Small.resolve(&plot_ui.ctx().style());
+    let size = plot_ui
+        .ctx()
+        .fonts(|fonts| fonts.layout_no_wrap(text.clone(), font, color).size());
+    let at = plot_ui.screen_from_plot(point);
+    let wanted = anchor.anchor_rect(egui::Rect::from_min_size(at, size));
+    let Some(rect) = free_label_spot(placed, wanted) else {
+        return;
+    };
+    placed.push(rect);
+    let moved = plot_ui.plot_from_screen(at + (rect.min - wanted.min));
+    plot_ui.text(Text::new(moved, text).color(color).anchor(anchor));
+}
+
+/// Swaps the coordinates of `p` while the view is mirrored about y = x; the
+/// swap is its own inverse, so it also maps view positions back to data.
+fn mirror_point(p: [f64; 2], mirror: bool) -> [f64; 2] {
+    if mirror {
+        [p[1], p[0]]
+    } else {
+        p
+    }
+}
+
+/// Draws the data's line x = `value` (or y = `value` unless `vertical`),
+/// turned a quarter while the view is mirrored about y = x.
+fn data_axis_line(
+    plot_ui: &mut PlotUi,
+    value: f64,
+    vertical: bool,
+    mirror: bool,
+    color: egui::Color32,
+    style: LineStyle,
+) {
+    if vertical != mirror {
+        plot_ui.vline(VLine::new(value).color(color).style(style));
+    } else {
+        plot_ui.hline(HLine::new(value).color(color).style(style));
+    }
+}
+
+/// Points per circle of the polar grid.
+const POLAR_CIRCLE_POINTS: usize = 180;
+/// Spokes of the polar grid, evenly spaced around the origin (every 30°).
+const POLAR_SPOKES: usize = 12;
+
+/// Radii of the polar grid circles that cross the view from `min` to `max`,
+/// at a 1/2/5 step chosen like the Cartesian gridlines.
+fn polar_grid_radii(min: [f64; 2], max: [f64; 2]) -> Vec<f64> {
+    // Nearest and farthest distance from the origin to a point of the view.
+    let closest = [0, 1].map(|axis| 0.0_f64.clamp(min[axis], max[axis]));
+    let farthest = [0, 1].map(|axis| min[axis].abs().max(max[axis].abs()));
+    let (near, far) = (closest[0].hypot(closest[1]), farthest[0].hypot(farthest[1]));
+    if !(far.is_finite() && far > near) {
+        return Vec::new();
+    }
+    let step = export::grid_step(far - near, 5.0);
+    export::grid_lines(near, far, step)
+        .into_iter()
+        .filter(|&r| r > 0.0)
+        .collect()
+}
+
+/// Number of decimals worth showing for values in a visible range of width `span`.
+fn decimals_for_span(span: f64) -> usize {
+    if !(span.is_finite() && span > 0.0) {
+        return 2;
+    }
+    (2.0 - span.log10().floor()).clamp(0.0, 12.0) as usize
+}
+
+/// Formats a plot position as "x, y" with the precision of the cursor tick
+/// labels for a view of the given width and height.
+fn format_coordinates([x, y]: [f64; 2], width: f64, height: f64) -> String {
+    format!(
+        "{:.*}, {:.*}",
+        decimals_for_span(width),
+        x,
+        decimals_for_span(height),
+        y
+    )
+}
+
+/// The plotting state of one tab: its functions, domain and per-plot settings.
+struct PlotTab {
+    // Stable identifier, used to keep widget and plot state separate per tab.
+    id: u64,
+    // How many times the curves were recomputed, for the performance overlay.
+    recomputes: u64,
+    // Domain inputs for the x-axis.
+    x_min_input: String,
+    x_max_input: String,
+    // Domain inputs for the y-axis.
+    y_min_input: String,
+    y_max_input: String,
+    // A list of functions to plot.
+    functions: Vec<FunctionPlot>,
+    // Maximum number of functions that can be added.
+    max_functions: usize,
+    // Colors handed to new functions, from the loaded theme or `PALETTE`.
+    palette: Vec<egui::Color32>,
+    // Number of sample points per function.
+    num_points: usize,
+    // "Go to" coordinates; y is optional.
+    goto_x: String,
+    goto_y: String,
+    // Set to make the plot drop its zoom/pan state and refit to the domain.
+    reset_view: bool,
+    // Spacing of the sample x values.
+    sampling: Sampling,
+    // Whether to resample over the visible x range after panning or zooming,
+    // the range currently sampled that way, and a view change waiting out
+    // the debounce (visible range, time first seen).
+    follow_view: bool,
+    view_x: Option<(f64, f64)>,
+    view_pending: Option<((f64, f64), f64)>,
+    // Whether to sample an explicit start:step:stop grid instead of the domain.
+    explicit_grid: bool,
+    grid_start_input: String,
+    grid_step_input: String,
+    grid_stop_input: String,
+    // Extra room around the domain in the plot view, as a percentage of its size.
+    plot_padding: f64,
+    // Tick labels of each axis in multiples of a constant instead of decimals.
+    x_ticks: TickUnit,
+    y_ticks: TickUnit,
+    // Whether to draw minor gridlines, and how many parts each major interval is split into.
+    minor_grid: bool,
+    grid_subdivisions: usize,
+    // Whether to draw circles and spokes around the origin; always on while a function is polar.
+    polar_grid: bool,
+    // Whether legend entries include each function's y range.
+    legend_range: bool,
+    // Whether the y range follows the data instead of the y bounds.
+    autoscale: Autoscale,
+    // Title displayed above the plot (independent of the plot's internal id).
+    plot_title: String,
+    // How expression text is interpreted before parsing.
+    parse_options: ParseOptions,
+    // Named constants shared by all expressions.
+    constants: Vec<Constant>,
+    // Error messages for the domain settings.
+    domain_error: Option<String>,
+    // Swap reversed bounds instead of reporting an error.
+    auto_swap_bounds: bool,
+    // Informational message about the domain (e.g. bounds were swapped).
+    domain_notice: Option<String>,
+    // Frozen copies of earlier curves, drawn faded beneath the live ones.
+    snapshots: Vec<(Vec<[f64; 2]>, egui::Color32)>,
+    // Index of the function panel that keyboard navigation acts on.
+    focused_function: usize,
+    // Maximum |Δy| for two curves to be considered drawn on top of each other.
+    overlap_tolerance: f64,
+    // Pairs of function indices whose curves coincide.
+    coincident_pairs: Vec<(usize, usize)>,
+    // Riemann sum overlay settings, and its computed slices and value (or error).
+    riemann: RiemannSettings,
+    riemann_shapes: Vec<Vec<[f64; 2]>>,
+    riemann_result: Option<Result<f64, String>>,
+    // Inequality shading settings, the x-intervals where the inequality holds
+    // (or why it couldn't be evaluated), and the quads filling the region
+    // between the two curves there.
+    inequality: InequalitySettings,
+    inequality_intervals: Option<Result<Vec<(f64, f64)>, String>>,
+    inequality_shapes: Vec<Vec<[f64; 2]>>,
+    // Small multiples settings, and each cell's parameter value and curve
+    // (or why they couldn't be sampled).
+    small_multiples: SmallMultiples,
+    small_multiple_cells: Option<Result<Vec<Slice>, String>>,
+    // Fourier series overlay settings, and the sampled partial sum (or error).
+    fourier: FourierSettings,
+    fourier_points: Result<Vec<[f64; 2]>, String>,
+    // Whether samples with |y| above `y_limit` are dropped (and the curve broken there).
+    limit_y: bool,
+    y_limit: f64,
+    // Maximum deviation allowed when reducing points.
+    decimate_tolerance: f64,
+    // Derived curves f_i - f_j, as (i, j, color).
+    differences: Vec<(usize, usize, egui::Color32)>,
+    // Sampled points of each difference, parallel to `differences`.
+    difference_points: Vec<Vec<[f64; 2]>>,
+    // Function indices picked for the next difference.
+    difference_selection: (usize, usize),
+    // Groups the functions are listed under, in list order (see `sync_groups`).
+    groups: Vec<FunctionGroup>,
+    // The group being renamed and the name typed so far, applied once the
+    // name field loses focus.
+    group_rename: Option<(String, String)>,
+    // Vertical guide lines added from the plot's context menu, and the plot
+    // position the menu was last opened at.
+    guides: Vec<f64>,
+    context_point: Option<[f64; 2]>,
+    // Loaded data series, the path typed for the next one, and the last load error.
+    data_series: Vec<DataSeries>,
+    data_path: String,
+    data_error: Option<String>,
+    // Least-squares fit: the model function, the data series, and the outcome.
+    fit_function: usize,
+    fit_series: usize,
+    fit_result: Option<Result<String, String>>,
+    // Residuals y - f(x) of the same function and series, drawn as stems from the x-axis.
+    show_residuals: bool,
+    residuals: Result<Vec<[f64; 2]>, String>,
+    // Constant swept by the animation export.
+    animation_constant: Option<usize>,
+    // Root found with R over the plot, as (function index, x), or why none was.
+    root: Option<Result<(usize, f64), String>>,
+    // Scratch calculator input, evaluated as it's typed.
+    calculator_input: String,
+    // Set when the parse options change, so the next update reparses every
+    // expression; otherwise only expressions whose text changed are reparsed.
+    needs_reparse: bool,
+    // Set when the domain, sampling, constants, transforms or expressions
+    // change; real-time update skips the recompute while neither flag is set.
+    needs_resample: bool,
+}
+
+impl PlotTab {
+    fn new(id: u64) -> Self {
+        // Prepopulate with two sample functions.
+        let mut functions = vec![
+            FunctionPlot::new("abs(ln(x-1)/ln(x-2))", function_color(&PALETTE, 0)),
+            FunctionPlot::new("sin(x)", function_color(&PALETTE, 1)),
+        ];
+        functions[1].palette_slot = 1;
+
+        Self {
+            id,
+            recomputes: 0,
+            x_min_input: "-10.0".to_owned(),
+            x_max_input: "10.0".to_owned(),
+            y_min_input: "-10.0".to_owned(),
+            y_max_input: "10.0".to_owned(),
+            functions,
+            max_functions: 20,
+            palette: PALETTE.to_vec(),
+            num_points: 1000,
+            goto_x: String::new(),
+            goto_y: String::new(),
+            reset_view: false,
+            sampling: Sampling::Linear,
+            follow_view: false,
+            view_x: None,
+            view_pending: None,
+            explicit_grid: false,
+            grid_start_input: "-10".to_owned(),
+            grid_step_input: "0.1".to_owned(),
+            grid_stop_input: "10".to_owned(),
+            plot_padding: 5.0,
+            x_ticks: TickUnit::pi(),
+            y_ticks: TickUnit::pi(),
+            minor_grid: false,
+            grid_subdivisions: 5,
+            polar_grid: false,
+            legend_range: false,
+            autoscale: Autoscale::Off,
+            plot_title: String::new(),
+            parse_options: ParseOptions::default(),
+            constants: Vec::new(),
+            domain_error: None,
+            auto_swap_bounds: true,
+            domain_notice: None,
+            snapshots: Vec::new(),
+            focused_function: 0,
+            overlap_tolerance: 1e-9,
+            coincident_pairs: Vec::new(),
+            riemann: RiemannSettings::default(),
+            riemann_shapes: Vec::new(),
+            riemann_result: None,
+            inequality: InequalitySettings::default(),
+            inequality_intervals: None,
+            inequality_shapes: Vec::new(),
+            small_multiples: SmallMultiples::default(),
+            small_multiple_cells: None,
+            fourier: FourierSettings::default(),
+            fourier_points: Ok(Vec::new()),
+            limit_y: false,
+            y_limit: 1e9,
+            decimate_tolerance: 1e-3,
+            differences: Vec::new(),
+            difference_points: Vec::new(),
+            difference_selection: (0, 1),
+            groups: Vec::new(),
+            group_rename: None,
+            guides: Vec::new(),
+            context_point: None,
+            data_series: Vec::new(),
+            data_path: String::new(),
+            data_error: None,
+            fit_function: 0,
+            fit_series: 0,
+            fit_result: None,
+            show_residuals: false,
+            residuals: Ok(Vec::new()),
+            animation_constant: None,
+            root: None,
+            calculator_input: String::new(),
+            needs_reparse: false,
+            needs_resample: true,
+        }
+    }
+
+    /// Name shown in the tab bar.
+    fn display_name(&self, index: usize) -> String {
+        match self.plot_title.trim() {
+            "" => format!("Plot {}", index + 1),
+            title => title.to_owned(),
+        }
+    }
+
+    /// Smallest and largest finite y values over the shown functions on the
+    /// primary axis.
+    fn data_y_range(&self) -> Option<(f64, f64)> {
+        self.functions
+            .iter()
+            .filter(|f| !f.secondary_axis && self.is_shown(f))
+            .flat_map(|f| f.plot_points.iter().map(|&p| f.display_point(p)[1]))
+            .filter(|y| y.is_finite())
+            .fold(None, |range, y| match range {
+                None => Some((y, y)),
+                Some((lo, hi)) => Some((f64::min(lo, y), f64::max(hi, y))),
+            })
+    }
+
+    /// Why the plot is empty, when no function has a curve and no data series
+    /// is loaded; shown over the plot instead of a blank grid.
+    fn empty_plot_message(&self) -> Option<String> {
+        let has_curve = |f: &FunctionPlot| {
+            !f.plot_points.is_empty() || f.slices.iter().any(|(_, p)| !p.is_empty())
+        };
+        let drawn = self
+            .functions
+            .iter()
+            .any(|f| has_curve(f) && self.is_shown(f))
+            || !self.data_series.is_empty();
+        if drawn {
+            return None;
+        }
+        Some(match &self.domain_error {
+            Some(e) => format!("Nothing to plot: {}", e),
+            None if self.functions.iter().any(has_curve) => {
+                "Every plotted function is in a hidden group".to_owned()
+            }
+            None if self.functions.is_empty() => "No functions to plot — add one above".to_owned(),
+            None => "No valid functions to plot — check the expressions above".to_owned(),
+        })
+    }
+
+    /// Range of the secondary y-axis: the data of the shown functions on it,
+    /// rounded out to nice numbers.
+    fn secondary_range(&self) -> Option<(f64, f64)> {
+        self.functions
+            .iter()
+            .filter(|f| f.secondary_axis && self.is_shown(f))
+            .flat_map(|f| f.plot_points.iter().map(|&p| f.display_point(p)[1]))
+            .filter(|y| y.is_finite())
+            .fold(None, |range, y| match range {
+                None => Some((y, y)),
+                Some((lo, hi)) => Some((f64::min(lo, y), f64::max(hi, y))),
+            })
+            .map(|(lo, hi)| nice_bounds(lo, hi))
+    }
+
+    /// Parses and checks the domain inputs, returning `(x_min, x_max, y_min, y_max)`.
+    ///
+    /// Reversed bounds are swapped when `auto_swap_bounds` is set.
+    fn validate_domain(&self) -> Result<(f64, f64, f64, f64), PlotError> {
+        let parse = |input: &str, name: &str| match input.trim().parse::<f64>() {
+            Ok(val) if val.is_finite() => Ok(val),
+            Ok(_) => Err(PlotError::Domain(format!(
+                "{} must be a finite number",
+                name
+            ))),
+            Err(_) => Err(PlotError::Domain(format!("Invalid {} value", name))),
+        };
+
+        let mut x_min = parse(&self.x_min_input, "x_min")?;
+        let mut x_max = parse(&self.x_max_input, "x_max")?;
+        if x_min > x_max && self.auto_swap_bounds {
+            std::mem::swap(&mut x_min, &mut x_max);
+        }
+        // Equal bounds are allowed and evaluate each function at that single point.
+        if x_min > x_max {
+            return Err(PlotError::Domain(
+                "x_min must not be greater than x_max".to_owned(),
+            ));
+        }
+        if self.sampling == Sampling::Log && x_min <= 0.0 {
+            return Err(PlotError::Domain(
+                "x_min must be positive for log sampling".to_owned(),
+            ));
+        }
+
+        let mut y_min = parse(&self.y_min_input, "y_min")?;
+        let mut y_max = parse(&self.y_max_input, "y_max")?;
+        if y_min > y_max && self.auto_swap_bounds {
+            std::mem::swap(&mut y_min, &mut y_max);
+        }
+        if y_min >= y_max {
+            return Err(PlotError::Domain(
+                "y_min must be less than y_max".to_owned(),
+            ));
+        }
+        Ok((x_min, x_max, y_min, y_max))
+    }
+
+    /// Parses and checks the explicit grid inputs, returning `(start, step, stop)`.
+    fn parse_explicit_grid(&self) -> Result<(f64, f64, f64), PlotError> {
+        let parse = |input: &str, name: &str| {
+            parse_finite(input)
+                .ok_or_else(|| PlotError::Domain(format!("Invalid grid {} value", name)))
+        };
+        let start = parse(&self.grid_start_input, "start")?;
+        let step = parse(&self.grid_step_input, "step")?;
+        let stop = parse(&self.grid_stop_input, "stop")?;
+        if step == 0.0 {
+            return Err(PlotError::Domain("Grid step must not be zero".to_owned()));
+        }
+        if start != stop && (stop - start).signum() != step.signum() {
+            let direction = if stop > start { "positive" } else { "negative" };
+            return Err(PlotError::Domain(format!(
+                "Grid step must be {} to go from {} to {}",
+                direction, start, stop
+            )));
+        }
+        if Sampling::Step(step).intervals(start, stop, 0) >= MAX_POINTS {
+            return Err(PlotError::Domain(format!(
+                "Grid has more than {} samples",
+                MAX_POINTS
+            )));
+        }
+        Ok((start, step, stop))
+    }
+
+    /// Mark the curves stale and recompute them straight away.
+    fn resample(&mut self) {
+        self.needs_resample = true;
+        self.update_functions();
+    }
+
+    /// Update all functions (and validate the domain settings), unless nothing
+    /// has changed since the last update.
+    fn update_functions(&mut self) {
+        if !std::mem::take(&mut self.needs_resample) && !self.needs_reparse {
+            return;
+        }
+        self.recomputes += 1;
+        let (x_min, x_max, _, _) = match self.validate_domain() {
+            Ok(bounds) => bounds,
+            Err(e) => {
+                self.domain_notice = None;
+                self.domain_error = Some(e.to_string());
+                return;
+            }
+        };
+        // The bounds differ from what was typed only when they were swapped.
+        let swapped_x = parse_finite(&self.x_min_input) != Some(x_min);
+        let swapped_y = parse_finite(&self.y_max_input) < parse_finite(&self.y_min_input);
+        self.domain_notice = match (swapped_x, swapped_y) {
+            (true, true) => Some("Swapped x and y bounds".to_owned()),
+            (true, false) => Some("Swapped x bounds".to_owned()),
+            (false, true) => Some("Swapped y bounds".to_owned()),
+            (false, false) => None,
+        };
+        self.domain_error = None;
+        self.num_points = clamp_num_points(self.num_points);
+        // When following the view, sample what is visible instead of the domain
+        // (log sampling can't reach past zero, so it keeps the domain there).
+        let (x_min, x_max) = match self.view_x {
+            Some(view)
+                if self.follow_view && (self.sampling == Sampling::Linear || view.0 > 0.0) =>
+            {
+                view
+            }
+            _ => (x_min, x_max),
+        };
+        let (x_min, x_max, sampling) = if self.explicit_grid {
+            match self.parse_explicit_grid() {
+                Ok((start, step, stop)) => (start, stop, Sampling::Step(step)),
+                Err(e) => {
+                    self.domain_error = Some(e.to_string());
+                    return;
+                }
+            }
+        } else {
+            (x_min, x_max, self.sampling)
+        };
+
+        // Expand references between functions up front; each expression then
+        // stands on its own, so the evaluation order doesn't matter.
+        let variable = match self.parse_options.variable.trim() {
+            // With an auto-detected variable, referenced expressions are assumed to use x.
+            "" => "x",
+            name => name,
+        };
+        let resolved: Vec<_> = (0..self.functions.len())
+            .map(|i| {
+                let references = preprocess::identifiers(&self.functions[i].expression)
+                    .iter()
+                    .any(|name| name == "prev" || function_reference(name).is_some());
+                if references && self.parse_options.rpn {
+                    // Substitution splices infix text, which the RPN reader can't take.
+                    return Some(Err(PlotError::Reference(
+                        "references aren't supported in RPN input".to_owned(),
+                    )));
+                }
+                references
+                    .then(|| resolve_references(&self.functions, i, variable, &mut Vec::new()))
+            })
+            .collect();
+
+        if std::mem::take(&mut self.needs_reparse) {
+            for f in &mut self.functions {
+                f.parsed = None;
+            }
+        }
+
+        // Update each function's plot points.
+        for (f, resolved) in self.functions.iter_mut().zip(resolved) {
+            f.resolved = resolved;
+            f.update(
+                x_min,
+                x_max,
+                self.num_points,
+                sampling,
+                &self.parse_options,
+                &self.constants,
+            );
+            if self.limit_y {
+                f.limit_y(self.y_limit);
+            }
+            if f.decimate {
+                f.simplified = Some(simplify_polyline(&f.plot_points, self.decimate_tolerance));
+            }
+        }
+
+        // Detect curves that are drawn exactly on top of each other.
+        self.coincident_pairs.clear();
+        for i in 0..self.functions.len() {
+            for j in (i + 1)..self.functions.len() {
+                if curves_coincide(
+                    &self.functions[i].plot_points,
+                    &self.functions[j].plot_points,
+                    self.overlap_tolerance,
+                ) {
+                    self.coincident_pairs.push((i, j));
+                }
+            }
+        }
+
+        self.update_riemann();
+        self.update_inequality();
+        self.update_small_multiples();
+        self.update_fourier();
+        self.update_residuals();
+
+        // Differences are sampled from the base functions just computed.
+        self.difference_points = self
+            .differences
+            .iter()
+            .map(
+                |&(i, j, _)| match (self.functions.get(i), self.functions.get(j)) {
+                    (Some(a), Some(b)) => difference_curve(&a.plot_points, &b.plot_points),
+                    _ => Vec::new(),
+                },
+            )
+            .collect();
+    }
+
+    /// Switches to `palette`, recoloring the functions whose color isn't locked.
+    fn set_palette(&mut self, palette: Vec<egui::Color32>) {
+        for func in &mut self.functions {
+            if !func.color_locked {
+                func.color = function_color(&palette, func.palette_slot);
+            }
+        }
+        self.palette = palette;
+    }
+
+    /// The lowest palette slot no function uses.
+    fn free_palette_slot(&self) -> usize {
+        (0..)
+            .find(|slot| !self.functions.iter().any(|f| f.palette_slot == *slot))
+            .unwrap_or(0)
+    }
+
+    /// Append a new function with the first unused palette color, if below the limit.
+    fn add_function(&mut self) {
+        if self.functions.len() >= self.max_functions {
+            return;
+        }
+        let slot = self.free_palette_slot();
+        let mut func = FunctionPlot::new("x", function_color(&self.palette, slot));
+        func.palette_slot = slot;
+        self.functions.push(func);
+    }
+
+    /// Adds a group for every group name in use and drops the groups that
+    /// have no members left.
+    fn sync_groups(&mut self) {
+        for func in &self.functions {
+            let name = func.group.trim();
+            if !name.is_empty() && !self.groups.iter().any(|g| g.name == name) {
+                self.groups.push(FunctionGroup::new(name));
+            }
+        }
+        let functions = &self.functions;
+        self.groups
+            .retain(|g| functions.iter().any(|f| f.group.trim() == g.name));
+    }
+
+    /// Renames group `old` to `new`, merging its members into `new` if that
+    /// group already exists. A blank name leaves the group as it was.
+    fn rename_group(&mut self, old: &str, new: &str) {
+        let new = new.trim();
+        if new.is_empty() || new == old {
+            return;
+        }
+        for f in &mut self.functions {
+            if f.group.trim() == old {
+                f.group = new.to_owned();
+            }
+        }
+        if self.groups.iter().any(|g| g.name == new) {
+            self.groups.retain(|g| g.name != old);
+        } else if let Some(g) = self.groups.iter_mut().find(|g| g.name == old) {
+            g.name = new.to_owned();
+        }
+    }
+
+    fn group_of(&self, func: &FunctionPlot) -> Option<&FunctionGroup> {
+        let name = func.group.trim();
+        self.groups
+            .iter()
+            .find(|g| !name.is_empty() && g.name == name)
+    }
+
+    /// Whether `func` is drawn: it is in no group or in a visible one.
+    fn is_shown(&self, func: &FunctionPlot) -> bool {
+        self.group_of(func).is_none_or(|g| g.visible)
+    }
+
+    /// The color `func` is drawn in, with its group's tint applied.
+    fn display_color(&self, func: &FunctionPlot) -> egui::Color32 {
+        match self.group_of(func).and_then(|g| g.tint) {
+            Some(tint) => tint_color(func.color, tint),
+            None => func.color,
+        }
+    }
+
+    /// Function indices in list order: functions in no group first, then the
+    /// members of each group in turn.
+    fn grouped_order(&self) -> Vec<usize> {
+        let position = |func: &FunctionPlot| {
+            self.group_of(func).map_or(0, |g| {
+                1 + self
+                    .groups
+                    .iter()
+                    .position(|h| h.name == g.name)
+                    .unwrap_or(0)
+            })
+        };
+        let mut order: Vec<usize> = (0..self.functions.len()).collect();
+        order.sort_by_key(|&i| position(&self.functions[i]));
+        order
+    }
+
+    /// Moves the domain so it is centered on `x` (and `y`, if given) while
+    /// keeping its current width and height, and refits the view to it.
+    fn center_on(&mut self, x: f64, y: Option<f64>) {
+        let span = |min: &str, max: &str| match (parse_finite(min), parse_finite(max)) {
+            (Some(lo), Some(hi)) => (hi - lo).abs(),
+            _ => 20.0,
+        };
+        let width = span(&self.x_min_input, &self.x_max_input);
+        self.x_min_input = (x - width / 2.0).to_string();
+        self.x_max_input = (x + width / 2.0).to_string();
+        if let Some(y) = y {
+            let height = span(&self.y_min_input, &self.y_max_input);
+            self.y_min_input = (y - height / 2.0).to_string();
+            self.y_max_input = (y + height / 2.0).to_string();
+        }
+        self.reset_view = true;
+    }
+
+    /// Finds the root nearest `x` on the curve passing closest to `(x, y)`.
+    ///
+    /// Inverse plots and curves on the secondary axis are not considered.
+    fn root_near(&self, [x, y]: [f64; 2]) -> Result<(usize, f64), String> {
+        let (index, func, _) = self
+            .functions
+            .iter()
+            .enumerate()
+            .filter(|(_, f)| !f.plot_inverse && !f.secondary_axis)
+            .filter_map(|(i, f)| {
+                let func = f.evaluator(&self.parse_options, &self.constants).ok()?;
+                let distance = (func(x) - y).abs();
+                distance.is_finite().then_some((i, func, distance))
+            })
+            .min_by(|a, b| a.2.total_cmp(&b.2))
+            .ok_or("No curve under the cursor")?;
+        nearest_root(&func, &self.functions[index].plot_points, x)
+            .map(|root| (index, root))
+            .ok_or_else(|| format!("f{} has no root in the domain", index + 1))
+    }
+
+    /// Sets x max so the domain spans one period of the selected function,
+    /// starting at x min. Leaves a notice instead when no period is found.
+    fn fit_one_period(&mut self) -> bool {
+        let index = self.focused_function;
+        let (Some(func), Some(x_min)) =
+            (self.functions.get(index), parse_finite(&self.x_min_input))
+        else {
+            return false;
+        };
+        match detect_period(&func.plot_points) {
+            Some(period) => {
+                self.x_max_input = (x_min + period).to_string();
+                self.reset_view = true;
+                true
+            }
+            None => {
+                self.domain_notice = Some(format!("No clear period found in f{}", index + 1));
+                false
+            }
+        }
+    }
+
+    /// Sets the y bounds to `robust_y_range` of every shown curve on the
+    /// primary axis, and turns autoscaling off so they apply.
+    fn robust_fit_y(&mut self) -> bool {
+        let values = self
+            .functions
+            .iter()
+            .filter(|f| !f.secondary_axis && self.is_shown(f))
+            .flat_map(|f| {
+                let slices = f.slices.iter().flat_map(|(_, points)| points);
+                f.drawn_points()
+                    .iter()
+                    .chain(slices)
+                    .map(|&p| f.display_point(p)[1])
+            })
+            .collect();
+        let Some((y_min, y_max)) = robust_y_range(values) else {
+            self.domain_notice = Some("Nothing plotted to fit".to_owned());
+            return false;
+        };
+        self.y_min_input = y_min.to_string();
+        self.y_max_input = y_max.to_string();
+        self.autoscale = Autoscale::Off;
+        self.reset_view = true;
+        true
+    }
+
+    /// Applies the sampling part of a quality preset (antialiasing is app-wide)
+    /// and resamples.
+    fn apply_quality(&mut self, preset: &QualityPreset) {
+        self.num_points = preset.num_points;
+        if self.follow_view != preset.follow_view {
+            self.follow_view = preset.follow_view;
+            self.view_x = None;
+            self.view_pending = None;
+        }
+        for func in &mut self.functions {
+            func.decimate = preset.decimate;
+            if matches!(
+                func.interpolation,
+                Interpolation::Straight | Interpolation::Smooth
+            ) {
+                func.interpolation = match preset.smooth {
+                    true => Interpolation::Smooth,
+                    false => Interpolation::Straight,
+                };
+            }
+        }
+        self.resample();
+    }
+
+    /// Level of detail: resamples over the `visible` x range once it has moved
+    /// away from the sampled range and then stayed put for `VIEW_DEBOUNCE`
+    /// seconds. Returns true while a resample is still waiting.
+    fn follow_view(&mut self, visible: (f64, f64), now: f64) -> bool {
+        let sampled = match self.view_x {
+            Some(view) => view,
+            None => match self.validate_domain() {
+                Ok((x_min, x_max, _, _)) => (x_min, x_max),
+                Err(_) => return false,
+            },
+        };
+        let offset = f64::max((visible.0 - sampled.0).abs(), (visible.1 - sampled.1).abs());
+        if offset <= VIEW_THRESHOLD * (visible.1 - visible.0) {
+            self.view_pending = None;
+            return false;
+        }
+        match self.view_pending {
+            Some((pending, since)) if pending == visible => {
+                if now - since < VIEW_DEBOUNCE {
+                    return true;
+                }
+                self.view_pending = None;
+                self.view_x = Some(visible);
+                self.resample();
+                false
+            }
+            _ => {
+                self.view_pending = Some((visible, now));
+                true
+            }
+        }
+    }
+
+    /// Checks each non-empty line of `text` as an expression, returning the
+    /// 1-based line number, the expression and its error, if any.
+    fn check_pasted(&self, text: &str) -> Vec<(usize, String, Option<String>)> {
+        text.lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty())
+            .map(|(number, line)| {
+                let error = compile_expression(line, &self.parse_options, &self.constants)
+                    .err()
+                    .map(|e| e.to_string());
+                (number, line.to_owned(), error)
+            })
+            .collect()
+    }
+
+    /// Replaces the functions with the expressions in `text`, one per
+    /// non-empty line and up to `max_functions`. Existing functions keep their
+    /// settings and only take the new expression. Returns the lines that
+    /// don't parse, which still become (failing) functions.
+    fn load_expressions(&mut self, text: &str) -> Vec<(usize, String)> {
+        let mut lines = self.check_pasted(text);
+        lines.truncate(self.max_functions);
+        while self.functions.len() > lines.len().max(1) {
+            self.remove_function(self.functions.len() - 1);
+        }
+        let mut errors = Vec::new();
+        for (i, (number, line, error)) in lines.into_iter().enumerate() {
+            if i == self.functions.len() {
+                self.add_function();
+            }
+            let func = &mut self.functions[i];
+            func.mode = PlotMode::Cartesian;
+            func.expression = line;
+            errors.extend(error.map(|e| (number, e)));
+        }
+        errors
+    }
+
+    /// Capture the current domain and curves for export.
+    fn export_scene(&self) -> Result<export::Scene, String> {
+        let (x_min, x_max, y_min, y_max) = match self.validate_domain() {
+            Ok((x0, x1, y0, y1)) if x0 < x1 => (x0, x1, y0, y1),
+            _ => return Err("Fix the domain settings before exporting".to_owned()),
+        };
+        // Hidden groups are left out and tints applied, as on screen.
+        let shown: Vec<FunctionPlot> = self
+            .functions
+            .iter()
+            .filter(|f| self.is_shown(f))
+            .map(|f| FunctionPlot {
+                color: self.display_color(f),
+                ..f.clone()
+            })
+            .collect();
+        Ok(export::Scene {
+            title: self.plot_title.trim().to_owned(),
+            x_range: (x_min, x_max),
+            y_range: (y_min, y_max),
+            curves: export_curves(&shown),
+        })
+    }
+
+    /// Fits the constants used by the selected function to the selected data
+    /// series and stores the fitted values (widening their slider ranges as needed).
+    fn fit_to_data(&mut self) {
+        let result = (|| {
+            let model = self
+                .functions
+                .get(self.fit_function)
+                .ok_or("No such function")?;
+            let series = self
+                .data_series
+                .get(self.fit_series)
+                .ok_or("No such data series")?;
+            let text = match &model.resolved {
+                Some(Ok(text)) => text.clone(),
+                _ => model.expression.clone(),
+            };
+            let used = preprocess::identifiers(&text);
+            let free: Vec<usize> = (0..self.constants.len())
+                .filter(|&k| used.contains(&self.constants[k].name.trim().to_owned()))
+                .collect();
+            if free.is_empty() {
+                return Err(format!(
+                    "f{} uses no constants to fit",
+                    self.fit_function + 1
+                ));
+            }
+            let (fitted, rms) = fit_constants(
+                model,
+                &self.parse_options,
+                &self.constants,
+                &free,
+                &series.points,
+            )
+            .map_err(|e| e.to_string())?;
+            let mut summary = Vec::new();
+            for &k in &free {
+                let constant = &mut self.constants[k];
+                constant.value = fitted[k].value;
+                constant.min = constant.min.min(constant.value);
+                constant.max = constant.max.max(constant.value);
+                summary.push(format!("{} = {:.6}", constant.name.trim(), constant.value));
+            }
+            Ok(format!("{}, RMS {:.6}", summary.join(", "), rms))
+        })();
+        self.fit_result = Some(result);
+        self.resample();
+    }
+
+    /// Evaluates the fit function at the fit series' x values, giving the
+    /// residuals `y - f(x)` at each data point.
+    fn update_residuals(&mut self) {
+        if !self.show_residuals {
+            self.residuals = Ok(Vec::new());
+            return;
+        }
+        self.residuals = (|| {
+            let func = self
+                .functions
+                .get(self.fit_function)
+                .ok_or("No such function")?;
+            let series = self
+                .data_series
+                .get(self.fit_series)
+                .ok_or("No such data series")?;
+            let f = func
+                .evaluator(&self.parse_options, &self.constants)
+                .map_err(|e| e.to_string())?;
+            Ok(series
+                .points
+                .iter()
+                .map(|&[x, y]| [x, y - f(x)])
+                .filter(|p| p[1].is_finite())
+                .collect())
+        })();
+    }
+
+    /// Samples the Fourier partial sum of the selected function over the
+    /// domain, which is taken to be one period.
+    fn update_fourier(&mut self) {
+        let settings = &self.fourier;
+        if !settings.enabled {
+            self.fourier_points = Ok(Vec::new());
+            return;
+        }
+        self.fourier_points = (|| {
+            let func = self
+                .functions
+                .get(settings.function)
+                .ok_or("No such function")?;
+            let (x_min, x_max, _, _) = self.validate_domain().map_err(|e| e.to_string())?;
+            if x_min == x_max {
+                return Err("The domain must have a nonzero width".to_owned());
+            }
+            let f = func
+                .evaluator(&self.parse_options, &self.constants)
+                .map_err(|e| e.to_string())?;
+            let coefficients = fourier_coefficients(f, x_min, x_max, settings.terms);
+            Ok(x_grid(x_min, x_max, self.num_points)
+                .into_iter()
+                .map(|x| [x, fourier_partial_sum(&coefficients, x_min, x_max, x)])
+                .collect())
+        })();
+    }
+
+    /// Recompute the Riemann sum overlay from the current settings.
+    fn update_riemann(&mut self) {
+        self.riemann_shapes.clear();
+        self.riemann_result = None;
+        let settings = &self.riemann;
+        if !settings.enabled {
+            return;
+        }
+        let result = (|| {
+            let func = self
+                .functions
+                .get(settings.function)
+                .ok_or("No such function")?;
+            let a = parse_finite(&settings.a_input).ok_or("Invalid lower bound a")?;
+            let b = parse_finite(&settings.b_input).ok_or("Invalid upper bound b")?;
+            let f = func
+                .evaluator(&self.parse_options, &self.constants)
+                .map_err(|e| e.to_string())?;
+            let (shapes, sum) = riemann_sum(f, a, b, settings.count, settings.rule);
+            self.riemann_shapes = shapes;
+            Ok(sum)
+        })();
+        self.riemann_result = Some(result);
+    }
+
+    /// Resample the small multiples, one curve per parameter value.
+    fn update_small_multiples(&mut self) {
+        let settings = &self.small_multiples;
+        if !settings.enabled {
+            self.small_multiple_cells = None;
+            return;
+        }
+        let result = (|| {
+            let values = parse_sweep_values(&settings.values)?;
+            let func = self
+                .functions
+                .get(settings.function)
+                .ok_or_else(|| "No such function".to_owned())?;
+            let name = settings.variable.trim();
+            if name.is_empty() {
+                return Err("Name the parameter".to_owned());
+            }
+            let (x_min, x_max, _, _) = self.validate_domain().map_err(|e| e.to_string())?;
+            func.sample_sweep(
+                name,
+                &values,
+                (x_min, x_max, self.num_points, self.sampling),
+                &self.parse_options,
+                &self.constants,
+            )
+            .map_err(|e| format!("f{}: {}", settings.function + 1, e))
+        })();
+        self.small_multiple_cells = Some(result);
+    }
+
+    /// Recompute where the selected inequality holds, on the same grid as the curves.
+    fn update_inequality(&mut self) {
+        self.inequality_shapes.clear();
+        self.inequality_intervals = None;
+        let settings = &self.inequality;
+        if !settings.enabled {
+            return;
+        }
+        let mut shapes = Vec::new();
+        let result = (|| {
+            let evaluator = |index: usize| {
+                self.functions
+                    .get(index)
+                    .ok_or_else(|| "No such function".to_owned())?
+                    .evaluator(&self.parse_options, &self.constants)
+                    .map_err(|e| format!("f{}: {}", index + 1, e))
+            };
+            let (f, g) = (evaluator(settings.left)?, evaluator(settings.right)?);
+            let (x_min, x_max, _, _) = self.validate_domain().map_err(|e| e.to_string())?;
+            let sign = if settings.greater { 1.0 } else { -1.0 };
+            let xs = x_grid(x_min, x_max, self.num_points);
+            let intervals = positive_intervals(&xs, &|x| sign * (f(x) - g(x)));
+            for &(a, b) in &intervals {
+                let inner = xs.iter().copied().filter(|&x| x > a && x < b);
+                let edges: Vec<f64> = std::iter::once(a).chain(inner).chain([b]).collect();
+                for w in edges.windows(2) {
+                    let quad = [
+                        [w[0], f(w[0])],
+                        [w[1], f(w[1])],
+                        [w[1], g(w[1])],
+                        [w[0], g(w[0])],
+                    ];
+                    if quad.iter().all(|p| p[1].is_finite()) {
+                        shapes.push(quad.to_vec());
+                    }
+                }
+            }
+            Ok(intervals)
+        })();
+        self.inequality_shapes = shapes;
+        self.inequality_intervals = Some(result);
+    }
+
+    /// Removes a function, dropping differences that use it and renumbering the rest.
+    fn remove_function(&mut self, index: usize) {
+        self.functions.remove(index);
+        self.differences
+            .retain(|&(i, j, _)| i != index && j != index);
+        for (i, j, _) in &mut self.differences {
+            if *i > index {
+                *i -= 1;
+            }
+            if *j > index {
+                *j -= 1;
+            }
+        }
+        // An inequality against the removed function has nothing left to shade.
+        let inequality = &mut self.inequality;
+        if inequality.left == index || inequality.right == index {
+            inequality.enabled = false;
+        }
+        for side in [&mut inequality.left, &mut inequality.right] {
+            if *side > index {
+                *side -= 1;
+            }
+        }
+        let multiples = &mut self.small_multiples;
+        if multiples.function == index {
+            multiples.enabled = false;
+        } else if multiples.function > index {
+            multiples.function -= 1;
+        }
+    }
+
+    /// Copy the current curves into the faded background layer.
+    fn take_snapshot(&mut self) {
+        self.snapshots = self
+            .functions
+            .iter()
+            .filter(|f| !f.plot_points.is_empty())
+            .map(|f| (f.plot_points.clone(), f.color))
+            .collect();
+    }
+}
+
+/// Seconds of recent frames the performance overlay averages over.
+const FPS_WINDOW: f64 = 1.0;
+
+/// Frame timing shown by the performance overlay.
+#[derive(Default)]
+struct FrameStats {
+    /// Start time of each frame in the last `FPS_WINDOW` seconds, oldest
+    /// first, and whether it recomputed any curves.
+    frames: std::collections::VecDeque<(f64, bool)>,
+    /// CPU time spent on the previous frame, in seconds.
+    frame_time: Option<f32>,
+    /// Sum of `PlotTab::recomputes` over all tabs at the end of the last frame.
+    recomputes: u64,
+}
+
+impl FrameStats {
+    /// Records a frame started at `now` that left the tabs' recompute
+    /// counters summing to `recomputes`.
+    fn record(&mut self, now: f64, frame_time: Option<f32>, recomputes: u64) {
+        let recomputed = recomputes != self.recomputes;
+        self.recomputes = recomputes;
+        self.frame_time = frame_time;
+        self.frames.push_back((now, recomputed));
+        while self
+            .frames
+            .front()
+            .is_some_and(|&(start, _)| now - start > FPS_WINDOW)
+        {
+            self.frames.pop_front();
+        }
+    }
+
+    /// Frames per second over the recorded window; `None` until there are
+    /// two frames to measure between.
+    fn fps(&self) -> Option<f64> {
+        let (first, last) = (self.frames.front()?.0, self.frames.back()?.0);
+        (last > first).then(|| (self.frames.len() - 1) as f64 / (last - first))
+    }
+
+    /// Whether the latest frame recomputed any curves.
+    fn recomputed(&self) -> bool {
+        self.frames
+            .back()
+            .is_some_and(|&(_, recomputed)| recomputed)
+    }
+
+    /// How many of the recorded frames recomputed curves.
+    fn recomputing_frames(&self) -> usize {
+        self.frames
+            .iter()
+            .filter(|&&(_, recomputed)| recomputed)
+            .count()
+    }
+}
+
+/// Main application structure.
+struct App {
+    // Open plots; only the active one is shown and updated.
+    tabs: Vec<PlotTab>,
+    active_tab: usize,
+    // Identifier given to the next new tab.
+    next_tab_id: u64,
+    // Whether to project the cursor position onto the axes as temporary tick labels.
+    show_cursor_ticks: bool,
+    // Whether the plot updates automatically as you type.
+    auto_update: bool,
+    // PNG export settings.
+    export_path: String,
+    export_width: u32,
+    export_height: u32,
+    // Stroke width of exported curves, in pixels per 400 pixels of image height.
+    export_line_width: f32,
+    // Receives progress and the result of an export running on a background thread.
+    export_job: Option<mpsc::Receiver<ExportEvent>>,
+    // Frames written so far by a running animation export.
+    export_progress: Option<(usize, usize)>,
+    // Animation export: how many frames, and where to write them.
+    animation_frames: usize,
+    animation_dir: String,
+    // Outcome of the last export, shown next to the export button.
+    export_status: Option<Result<String, String>>,
+    // Scale factor for the whole interface, on top of the monitor's native scaling.
+    ui_scale: f32,
+    // Function whose panel is under the pointer; its curve is emphasized.
+    hovered_function: Option<usize>,
+    // Whether the session is written on exit and restored on the next launch.
+    autosave: bool,
+    // Whether the first expression box starts focused with its text selected,
+    // and whether that is still to happen (on the first frame).
+    focus_on_startup: bool,
+    startup_focus_pending: bool,
+    // Dark or light theme; switching adjusts unlocked curve colors to stay visible.
+    dark_mode: bool,
+    // Theme loaded from a file (replacing the built-in light and dark ones), the
+    // file's path, and why loading it last failed.
+    theme: Option<theme::Theme>,
+    theme_path: String,
+    theme_error: Option<String>,
+    // Set when the visuals must be re-applied even though dark mode didn't change.
+    restyle: bool,
+    // Accessibility mode: `theme::high_contrast` as the theme, with thicker
+    // curves and larger text. Any other theme choice turns it off.
+    high_contrast: bool,
+    // Pan and zoom controls of the plot, which can be turned off to fix the view.
+    interaction: PlotInteraction,
+    // Reflects the whole view across y = x, swapping the roles of the axes.
+    mirror_diagonal: bool,
+    // Magnified inset that follows the cursor over the plot, and its zoom factor.
+    show_loupe: bool,
+    loupe_zoom: f32,
+    // Measure mode: clicking the plot picks up to two points to compare.
+    measure_mode: bool,
+    measure_points: Vec<[f64; 2]>,
+    // Hides the controls so the plot fills the window (toggled with F11).
+    presentation_mode: bool,
+    // Whether the "Paste expressions" dialog is open, and the text pasted into it.
+    show_paste_dialog: bool,
+    paste_text: String,
+    // Whether the function reference window is open.
+    show_function_help: bool,
+    // Whether the command palette (Ctrl+P) is open, and its filter text.
+    show_command_palette: bool,
+    command_filter: String,
+    // Expressions file that replaces the functions of a tab (by id) whenever
+    // it changes, the path typed for it, and the outcome of the last reload:
+    // the lines that failed to parse, or why the file couldn't be read.
+    watch: Option<(u64, watch::FileWatch)>,
+    watch_path: String,
+    watch_status: Option<Result<Vec<(usize, String)>, String>>,
+    // Position of the quality slider in `QUALITY_PRESETS`; the settings it
+    // sets can still be changed one by one afterwards.
+    quality: usize,
+    // Overlay with the frame rate, frame time and whether curves were
+    // recomputed, for diagnosing stutter.
+    show_performance: bool,
+    frame_stats: FrameStats,
+}
+
+impl Default for App {
+    fn default() -> Self {
+        Self {
+            tabs: vec![PlotTab::new(0)],
+            active_tab: 0,
+            next_tab_id: 1,
+            show_cursor_ticks: true,
+            auto_update: true,
+            export_path: "plot.png".to_owned(),
+            export_width: 1920,
+            export_height: 1080,
+            export_line_width: 2.0,
+            export_job: None,
+            export_progress: None,
+            animation_frames: 30,
+            animation_dir: "frames".to_owned(),
+            export_status: None,
+            ui_scale: 1.0,
+            hovered_function: None,
+            autosave: true,
+            focus_on_startup: true,
+            startup_focus_pending: false,
+            dark_mode: false,
+            theme: None,
+            theme_path: String::new(),
+            theme_error: None,
+            restyle: false,
+            high_contrast: false,
+            interaction: PlotInteraction::default(),
+            mirror_diagonal: false,
+            show_loupe: false,
+            loupe_zoom: 4.0,
+            measure_mode: false,
+            measure_points: Vec::new(),
+            presentation_mode: false,
+            show_paste_dialog: false,
+            paste_text: String::new(),
+            show_function_help: false,
+            show_command_palette: false,
+            command_filter: String::new(),
+            watch: None,
+            watch_path: "functions.txt".to_owned(),
+            watch_status: None,
+            quality: DEFAULT_QUALITY,
+            show_performance: false,
+            frame_stats: FrameStats::default(),
+        }
+    }
+}
+
+impl App {
+    /// Adjusts every unlocked function color to stand out against `background`.
+    ///
+    /// Colors are adjusted from each function's palette color, not its current
+    /// one, so switching back to a theme restores the colors it had.
+    fn fit_colors_to_background(&mut self, background: egui::Color32) {
+        for tab in &mut self.tabs {
+            for func in &mut tab.functions {
+                if !func.color_locked {
+                    let color = function_color(&tab.palette, func.palette_slot);
+                    func.color = ensure_contrast(color, background);
+                }
+            }
+        }
+    }
+
+    /// Create the app, restoring saved preferences when available.
+    fn new(cc: &eframe::CreationContext<'_>) -> Self {
+        let mut app = Self::default();
+        if let Some(storage) = cc.storage {
+            app.ui_scale = eframe::get_value(storage, UI_SCALE_KEY).unwrap_or(1.0);
+            app.autosave = eframe::get_value(storage, AUTOSAVE_KEY).unwrap_or(true);
+            app.focus_on_startup = eframe::get_value(storage, STARTUP_FOCUS_KEY).unwrap_or(true);
+            app.dark_mode = eframe::get_value(storage, DARK_MODE_KEY).unwrap_or(false);
+            app.theme_path = eframe::get_value(storage, THEME_KEY).unwrap_or_default();
+            if eframe::get_value(storage, HIGH_CONTRAST_KEY).unwrap_or(false) {
+                app.set_high_contrast(true);
+            }
+        }
+        // A missing or unreadable autosave leaves the built-in defaults in place.
+        if app.autosave {
+            if let Some(session) = session::load_autosave() {
+                session.restore(&mut app);
+            }
+        }
+        if !app.theme_path.is_empty() {
+            app.run_command(Command::LoadTheme);
+        }
+        app.startup_focus_pending = app.focus_on_startup;
+        app
+    }
+
+    /// The tab currently shown.
+    fn tab(&self) -> &PlotTab {
+        &self.tabs[self.active_tab]
+    }
+
+    fn tab_mut(&mut self) -> &mut PlotTab {
+        &mut self.tabs[self.active_tab]
+    }
+
+    /// Run an action picked from the command palette.
+    fn run_command(&mut self, command: Command) {
+        let auto_update = self.auto_update;
+        match command {
+            Command::AddFunction => {
+                let tab = self.tab_mut();
+                tab.add_function();
+                if auto_update {
+                    tab.resample();
+                }
+            }
+            Command::TogglePresentation => self.presentation_mode = !self.presentation_mode,
+            Command::PasteExpressions => self.show_paste_dialog = true,
+            Command::FunctionHelp => self.show_function_help = true,
+            Command::Plot => self.tab_mut().resample(),
+            Command::ToggleAutoUpdate => self.auto_update = !self.auto_update,
+            Command::Snapshot => self.tab_mut().take_snapshot(),
+            Command::ClearSnapshot => self.tab_mut().snapshots.clear(),
+            Command::ExportPng => self.start_export(ImageFormat::Png),
+            Command::ExportSvg => self.start_export(ImageFormat::Svg),
+            Command::ExportAnimation => self.start_animation_export(),
+            Command::ExportArchive => self.start_archive_export(),
+            Command::LoadTheme => {
+                self.theme_error = None;
+                match theme::load_theme(Path::new(self.theme_path.trim())) {
+                    Ok(theme) => self.apply_theme(Some(theme)),
+                    Err(e) => self.theme_error = Some(e),
+                }
+            }
+            Command::ClearTheme => self.apply_theme(None),
+            Command::ToggleHighContrast => self.set_high_contrast(!self.high_contrast),
+            Command::TogglePerformance => self.show_performance = !self.show_performance,
+            Command::WatchFile => self.start_watch(),
+            Command::Unwatch => {
+                self.watch = None;
+                self.watch_status = None;
+            }
+        }
+    }
+
+    /// Start watching `watch_path`, loading it into the active tab right away
+    /// and again after every change.
+    fn start_watch(&mut self) {
+        let path = PathBuf::from(self.watch_path.trim());
+        match watch::FileWatch::new(&path) {
+            Ok(file_watch) => {
+                self.watch = Some((self.tab().id, file_watch));
+                self.reload_watched();
+            }
+            Err(e) => self.watch_status = Some(Err(e)),
+        }
+    }
+
+    /// Read the watched file into its tab. A file that can't be read (e.g.
+    /// mid-save) leaves the functions as they are.
+    fn reload_watched(&mut self) {
+        let Some((id, file_watch)) = &self.watch else {
+            return;
+        };
+        let Some(tab) = self.tabs.iter_mut().find(|tab| tab.id == *id) else {
+            self.watch = None;
+            self.watch_status = Some(Err("The watched tab was closed".to_owned()));
+            return;
+        };
+        let path = &file_watch.path;
+        self.watch_status = Some(
+            std::fs::read_to_string(path)
+                .map(|text| tab.load_expressions(&text))
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e)),
+        );
+        if self.auto_update {
+            tab.resample();
+        }
+    }
+
+    /// Reload the watched file once a burst of changes to it has settled.
+    fn poll_watch(&mut self, ctx: &egui::Context) {
+        let Some((_, file_watch)) = &mut self.watch else {
+            return;
+        };
+        if file_watch.changed(ctx.input(|i| i.time)) {
+            self.reload_watched();
+        }
+        // Changes arrive on another thread; look for them a few times a second.
+        ctx.request_repaint_after(std::time::Duration::from_secs_f64(watch::DEBOUNCE / 2.0));
+    }
+
+    /// Switches to `theme` (or back to the built-in light/dark themes) and
+    /// recolors the functions with its palette.
+    fn apply_theme(&mut self, theme: Option<theme::Theme>) {
+        let palette = match &theme {
+            Some(theme) if !theme.palette.is_empty() => theme.palette.clone(),
+            _ => PALETTE.to_vec(),
+        };
+        for tab in &mut self.tabs {
+            tab.set_palette(palette.clone());
+        }
+        if let Some(theme) = &theme {
+            self.dark_mode = theme.visuals.dark_mode;
+        }
+        self.theme = theme;
+        self.high_contrast = false;
+        self.restyle = true;
+    }
+
+    /// Switches high-contrast mode on, or back to the built-in light/dark themes.
+    fn set_high_contrast(&mut self, on: bool) {
+        if on {
+            self.apply_theme(Some(theme::high_contrast()));
+        } else {
+            self.apply_theme(None);
+        }
+        self.high_contrast = on;
+    }
+
+    /// Show the command palette window and run the selected command.
+    fn command_palette(&mut self, ctx: &egui::Context) {
+        if ctx.input(|i| i.modifiers.command && i.key_pressed(egui::Key::P)) {
+            self.show_command_palette = !self.show_command_palette;
+            self.command_filter.clear();
+        }
+        if !self.show_command_palette {
+            return;
+        }
+
+        let filter = self.command_filter.to_lowercase();
+        let matches: Vec<Command> = Command::ALL
+            .into_iter()
+            .filter(|c| c.label().to_lowercase().contains(&filter))
+            .collect();
+        let mut selected = None;
+        egui::Window::new("Command Palette")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 40.0))
+            .show(ctx, |ui| {
+                let response = ui.text_edit_singleline(&mut self.command_filter);
+                response.request_focus();
+                // Enter runs the first match.
+                if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    selected = matches.first().copied();
+                }
+                for &command in &matches {
+                    if ui.selectable_label(false, command.label()).clicked() {
+                        selected = Some(command);
+                    }
+                }
+                if matches.is_empty() {
+                    ui.label("No matching commands");
+                }
+            });
+
+        if ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+            self.show_command_palette = false;
+        }
+        if let Some(command) = selected {
+            self.show_command_palette = false;
+            self.run_command(command);
+        }
+    }
+
+    /// Show the active tab's small multiples: a grid of small plots of one
+    /// function, one per parameter value, with their axes linked.
+    fn small_multiples_window(&mut self, ctx: &egui::Context) {
+        let tab = &mut self.tabs[self.active_tab];
+        let Some(Ok(cells)) = &tab.small_multiple_cells else {
+            return;
+        };
+        let settings = &tab.small_multiples;
+        let color = tab
+            .functions
+            .get(settings.function)
+            .map_or(egui::Color32::GRAY, |f| tab.display_color(f));
+        // Every cell starts on the same view so the curves compare directly.
+        let (x_min, x_max, y_min, y_max) = cells.iter().flat_map(|(_, points)| points).fold(
+            (
+                f64::INFINITY,
+                f64::NEG_INFINITY,
+                f64::INFINITY,
+                f64::NEG_INFINITY,
+            ),
+            |(x0, x1, y0, y1), &[x, y]| (x0.min(x), x1.max(x), y0.min(y), y1.max(y)),
+        );
+        let link = egui::Id::new(("small_multiples", tab.id));
+        let mut open = true;
+        egui::Window::new(format!("Small multiples of f{}", settings.function + 1))
+            .open(&mut open)
+            .show(ctx, |ui| {
+                egui::Grid::new(link).show(ui, |ui| {
+                    for (k, (value, points)) in cells.iter().enumerate() {
+                        ui.vertical(|ui| {
+                            ui.label(format!("{} = {}", settings.variable.trim(), value));
+                            let mut plot = Plot::new(("small_multiple", tab.id, k))
+                                .width(SMALL_MULTIPLE_SIZE.x)
+                                .height(SMALL_MULTIPLE_SIZE.y)
+                                .link_axis(link, true, true);
+                            if x_min <= x_max {
+                                plot = plot
+                                    .include_x(x_min)
+                                    .include_x(x_max)
+                                    .include_y(y_min)
+                                    .include_y(y_max);
+                            }
+                            plot.show(ui, |plot_ui| {
+                                plot_ui.line(Line::new(points.clone()).color(color).width(1.5));
+                            });
+                        });
+                        if (k + 1) % settings.columns.max(1) == 0 {
+                            ui.end_row();
+                        }
+                    }
+                });
+            });
+        if !open {
+            tab.small_multiples.enabled = false;
+            tab.small_multiple_cells = None;
+        }
+    }
+
+    /// Show the frame rate, frame time and recomputes in the top-right
+    /// corner, above everything else.
+    fn performance_overlay(&self, ctx: &egui::Context) {
+        if !self.show_performance {
+            return;
+        }
+        let stats = &self.frame_stats;
+        egui::Area::new("performance_overlay")
+            .anchor(egui::Align2::RIGHT_TOP, egui::vec2(-8.0, 8.0))
+            .order(egui::Order::Foreground)
+            .interactable(false)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    let fps = stats
+                        .fps()
+                        .map_or("–".to_owned(), |fps| format!("{:.0}", fps));
+                    let frame_time = stats
+                        .frame_time
+                        .map_or("–".to_owned(), |t| format!("{:.1} ms", 1000.0 * t));
+                    ui.monospace(format!("FPS:        {}", fps));
+                    ui.monospace(format!("Frame time: {}", frame_time));
+                    ui.monospace(format!(
+                        "Recomputed: {} ({} of {} frames)",
+                        if stats.recomputed() { "yes" } else { "no" },
+                        stats.recomputing_frames(),
+                        stats.frames.len()
+                    ));
+                    if self.auto_update {
+                        ui.small("Real-time update repaints every frame and recomputes when an input changes");
+                    }
+                });
+            });
+    }
+
+    /// Show the function reference: every function and constant that
+    /// expressions can use, with an example of each.
+    fn function_help(&mut self, ctx: &egui::Context) {
+        let tab = &self.tabs[self.active_tab];
+        egui::Window::new("Functions help")
+            .open(&mut self.show_function_help)
+            .vscroll(true)
+            .show(ctx, |ui| {
+                egui::Grid::new("function_help")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for function in FUNCTIONS {
+                            let arguments = match function.arity {
+                                Some(1) => "x",
+                                Some(2) => "y, x",
+                                _ => "a, b, …",
+                            };
+                            ui.monospace(format!("{}({})", function.name, arguments));
+                            ui.label(function.description);
+                            ui.monospace(function.example);
+                            ui.end_row();
+                        }
+                    });
+                ui.separator();
+                ui.label("Constants:");
+                egui::Grid::new("constant_help")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for (name, description) in BUILTIN_CONSTANTS {
+                            ui.monospace(*name);
+                            ui.label(*description);
+                            ui.end_row();
+                        }
+                        for constant in &tab.constants {
+                            ui.monospace(constant.name.trim());
+                            ui.label(format!("Named constant, now {}", constant.value));
+                            ui.end_row();
+                        }
+                    });
+                ui.separator();
+                ui.label("Operators: + - * / % ^, with parentheses for grouping.");
+            });
+    }
+
+    /// Show the dialog that turns pasted lines into new functions.
+    fn paste_dialog(&mut self, ctx: &egui::Context) {
+        if !self.show_paste_dialog {
+            return;
+        }
+        let mut open = true;
+        let mut confirmed = false;
+        let tab = &self.tabs[self.active_tab];
+        let lines = tab.check_pasted(&self.paste_text);
+        let valid = lines.iter().filter(|(_, _, error)| error.is_none()).count();
+        let room = tab.max_functions.saturating_sub(tab.functions.len());
+        let count = valid.min(room);
+        egui::Window::new("Paste expressions")
+            .open(&mut open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.label("One expression per line:");
+                ui.add(
+                    egui::TextEdit::multiline(&mut self.paste_text)
+                        .code_editor()
+                        .desired_rows(8),
+                );
+                for (number, _, error) in &lines {
+                    if let Some(error) = error {
+                        ui.colored_label(egui::Color32::RED, format!("Line {}: {}", number, error));
+                    }
+                }
+                if valid > room {
+                    ui.colored_label(
+                        egui::Color32::from_rgb(200, 120, 0),
+                        format!("Only {} more functions fit in this plot", room),
+                    );
+                }
+                let label = match count {
+                    1 => "Will create 1 function".to_owned(),
+                    n => format!("Will create {} functions", n),
+                };
+                ui.label(label);
+                if ui
+                    .add_enabled(count > 0, egui::Button::new("Create"))
+                    .clicked()
+                {
+                    confirmed = true;
+                }
+            });
+
+        if confirmed {
+            let expressions: Vec<String> = lines
+                .into_iter()
+                .filter(|(_, _, error)| error.is_none())
+                .map(|(_, line, _)| line)
+                .collect();
+            let auto_update = self.auto_update;
+            let tab = self.tab_mut();
+            for expression in expressions.into_iter().take(count) {
+                tab.add_function();
+                if let Some(func) = tab.functions.last_mut() {
+                    func.expression = expression;
+                }
+            }
+            if auto_update {
+                tab.resample();
+            }
+            self.paste_text.clear();
+            open = false;
+        }
+        self.show_paste_dialog = open;
+    }
+
+    /// Start writing the current plot on a background thread.
+    ///
+    /// The file is `export_path` with its extension set to match the format.
+    fn start_export(&mut self, format: ImageFormat) {
+        if self.export_job.is_some() {
+            return;
+        }
+        // The scene is a copy taken now, so later edits don't affect the file being written.
+        let mut scene = match self.tab().export_scene() {
+            Ok(scene) => scene,
+            Err(e) => {
+                self.export_status = Some(Err(e));
+                return;
+            }
+        };
+        scene.set_line_width(self.export_line_width);
+        let mut path = PathBuf::from(self.export_path.trim());
+        path.set_extension(match format {
+            ImageFormat::Png => "png",
+            ImageFormat::Svg => "svg",
+        });
+        let (width, height) = (self.export_width, self.export_height);
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let result = match format {
+                ImageFormat::Png => export::write_png(&path, &scene, width, height),
+                ImageFormat::Svg => export::write_svg(&path, &scene, width, height),
+            }
+            .map(|()| format!("Saved {}", path.display()));
+            // The receiver may be gone if the app was closed; nothing to report then.
+            let _ = sender.send(ExportEvent::Done(result));
+        });
+        self.export_job = Some(receiver);
+        self.export_status = None;
+    }
+
+    /// Start writing a zip of the session, the curve data and a PNG on a background thread.
+    fn start_archive_export(&mut self) {
+        if self.export_job.is_some() {
+            return;
+        }
+        let mut scene = match self.tab().export_scene() {
+            Ok(scene) => scene,
+            Err(e) => {
+                self.export_status = Some(Err(e));
+                return;
+            }
+        };
+        scene.set_line_width(self.export_line_width);
+        let session_json = session::Session::capture(self).to_json();
+        let mut path = PathBuf::from(self.export_path.trim());
+        path.set_extension("zip");
+        let (width, height) = (self.export_width, self.export_height);
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let result = export::write_archive(&path, &scene, &session_json, width, height)
+                .map(|()| format!("Saved {}", path.display()));
+            let _ = sender.send(ExportEvent::Done(result));
+        });
+        self.export_job = Some(receiver);
+        self.export_status = None;
+    }
+
+    /// Start rendering the animation frames for the selected constant on a background thread.
+    fn start_animation_export(&mut self) {
+        if self.export_job.is_some() {
+            return;
+        }
+        let tab = self.tab();
+        let constant = match tab.animation_constant {
+            Some(i) if i < tab.constants.len() => i,
+            _ => {
+                self.export_status = Some(Err("Choose a constant to animate".to_owned()));
+                return;
+            }
+        };
+        let scene = match tab.export_scene() {
+            Ok(scene) => scene,
+            Err(e) => {
+                self.export_status = Some(Err(e));
+                return;
+            }
+        };
+        let job = AnimationExport {
+            scene,
+            functions: tab.functions.clone(),
+            constants: tab.constants.clone(),
+            options: tab.parse_options.clone(),
+            num_points: tab.num_points,
+            sampling: tab.sampling,
+            constant,
+            frames: self.animation_frames,
+            directory: PathBuf::from(self.animation_dir.trim()),
+            size: (self.export_width, self.export_height),
+            line_width: self.export_line_width,
+        };
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let result = job.run(&sender);
+            let _ = sender.send(ExportEvent::Done(result));
+        });
+        self.export_job = Some(receiver);
+        self.export_progress = None;
+        self.export_status = None;
+    }
+
+    /// Check whether a running export has finished.
+    fn poll_export(&mut self, ctx: &egui::Context) {
+        let Some(ref receiver) = self.export_job else {
+            return;
+        };
+        loop {
+            match receiver.try_recv() {
+                Ok(ExportEvent::Progress(done, total)) => {
+                    self.export_progress = Some((done, total))
+                }
+                Ok(ExportEvent::Done(result)) => {
+                    self.export_status = Some(result);
+                    self.export_progress = None;
+                    self.export_job = None;
+                    return;
+                }
+                // Keep repainting so the spinner animates and completion is noticed.
+                Err(mpsc::TryRecvError::Empty) => {
+                    ctx.request_repaint();
+                    return;
+                }
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    self.export_status = Some(Err("Export stopped unexpectedly".to_owned()));
+                    self.export_progress = None;
+                    self.export_job = None;
+                    return;
+                }
+            }
+        }
+    }
+}
+
+impl eframe::App for App {
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        // When real-time update is enabled, recompute on any frame where an input
+        // may have changed. Widgets that don't report their edits are caught by
+        // treating every click, key press or scroll as a possible change.
+        if self.auto_update {
+            let interacted = ctx.input(|i| {
+                i.events.iter().any(|event| match event {
+                    egui::Event::PointerMoved(_) => i.pointer.any_down(),
+                    _ => true,
+                })
+            });
+            let tab = self.tab_mut();
+            tab.needs_resample |= interacted;
+            tab.update_functions();
+        }
+
+        self.poll_export(ctx);
+        self.poll_watch(ctx);
+
+        // Apply the UI scale, but not mid-drag so the slider doesn't move under the pointer.
+        let native_scale = frame.info().native_pixels_per_point.unwrap_or(1.0);
+        let target_scale = native_scale * self.ui_scale;
+        if (ctx.pixels_per_point() - target_scale).abs() > 1e-3
+            && !ctx.input(|i| i.pointer.any_down())
+        {
+            ctx.set_pixels_per_point(target_scale);
+        }
+
+        // Switch themes, then nudge curve colors that would blend into the new background.
+        if std::mem::take(&mut self.restyle) || ctx.style().visuals.dark_mode != self.dark_mode {
+            // Toggling dark mode goes back to the built-in themes.
+            if self
+                .theme
+                .as_ref()
+                .is_some_and(|theme| theme.visuals.dark_mode != self.dark_mode)
+            {
+                let dark_mode = self.dark_mode;
+                self.apply_theme(None);
+                self.dark_mode = dark_mode;
+            }
+            let visuals = match &self.theme {
+                Some(theme) => theme.visuals.clone(),
+                None if self.dark_mode => egui::Visuals::dark(),
+                None => light_visuals(),
+            };
+            self.fit_colors_to_background(visuals.extreme_bg_color);
+            let mut style = (*ctx.style()).clone();
+            style.visuals = visuals;
+            style.text_styles = egui::Style::default().text_styles;
+            if self.high_contrast {
+                for font in style.text_styles.values_mut() {
+                    font.size *= HIGH_CONTRAST_FONT_SCALE;
+                }
+            }
+            ctx.set_style(style);
+        }
+
+        let mut pending_command = None;
+        if ctx.input(|i| i.key_pressed(egui::Key::F11)) {
+            pending_command = Some(Command::TogglePresentation);
+        }
         egui::CentralPanel::default().show(ctx, |ui| {
-            // --- Header ---
-            ui.heading("Rust Function Plotter - Multiple Functions");
+            if self.presentation_mode {
+                // Slim toolbar so the plot can take the rest of the window.
+                ui.horizontal(|ui| {
+                    if ui.button("Exit presentation (F11)").clicked() {
+                        pending_command = Some(Command::TogglePresentation);
+                    }
+                });
+            } else {
+                // --- Header ---
+                ui.heading("Rust Function Plotter - Multiple Functions");
 
-            // --- Domain Settings ---
-            ui.group(|ui| {
-                ui.label("Domain Settings:");
+                // --- Tabs ---
                 ui.horizontal(|ui| {
-                    ui.label("x min:");
-                    let changed1 = ui.text_edit_singleline(&mut self.x_min_input).changed();
-                    ui.label("x max:");
-                    let changed2 = ui.text_edit_singleline(&mut self.x_max_input).changed();
-                    if (changed1 || changed2) && self.auto_update {
-                        self.update_functions();
+                    let mut close = None;
+                    for (i, tab) in self.tabs.iter().enumerate() {
+                        if ui
+                            .selectable_label(i == self.active_tab, tab.display_name(i))
+                            .clicked()
+                        {
+                            self.active_tab = i;
+                        }
+                        if self.tabs.len() > 1
+                            && ui.small_button("×").on_hover_text("Close tab").clicked()
+                        {
+                            close = Some(i);
+                        }
+                    }
+                    if ui.button("+").on_hover_text("New tab").clicked() {
+                        let mut new_tab = PlotTab::new(self.next_tab_id);
+                        if let Some(theme) = self.theme.as_ref().filter(|t| !t.palette.is_empty()) {
+                            new_tab.set_palette(theme.palette.clone());
+                        }
+                        self.tabs.push(new_tab);
+                        self.next_tab_id += 1;
+                        self.active_tab = self.tabs.len() - 1;
+                    }
+                    if let Some(i) = close {
+                        self.tabs.remove(i);
+                        if self.active_tab > i || self.active_tab == self.tabs.len() {
+                            self.active_tab -= 1;
+                        }
+                    }
+                });
+            }
+            let tab = &mut self.tabs[self.active_tab];
+
+            // The controls are hidden in presentation mode.
+            if !self.presentation_mode {
+                // --- Domain Settings ---
+                ui.group(|ui| {
+                    ui.label("Domain Settings:");
+                    ui.horizontal(|ui| {
+                        ui.label("x min:");
+                        let changed1 = numeric_edit(ui, &mut tab.x_min_input);
+                        ui.label("x max:");
+                        let changed2 = numeric_edit(ui, &mut tab.x_max_input);
+                        let mut preset = false;
+                        egui::ComboBox::from_id_source(("x_presets", tab.id))
+                            .selected_text("Presets")
+                            .show_ui(ui, |ui| {
+                                for (label, min, max) in X_PRESETS {
+                                    if ui.selectable_label(false, label).clicked() {
+                                        tab.x_min_input = min.to_string();
+                                        tab.x_max_input = max.to_string();
+                                        preset = true;
+                                        tab.reset_view = true;
+                                    }
+                                }
+                            });
+                        let fitted = ui
+                            .button("Fit one period")
+                            .on_hover_text("Detect the period of the selected function and show one")
+                            .clicked()
+                            && tab.fit_one_period();
+                        if (changed1 || changed2 || preset || fitted) && self.auto_update {
+                            tab.resample();
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("y min:");
+                        let changed1 = numeric_edit(ui, &mut tab.y_min_input);
+                        ui.label("y max:");
+                        let changed2 = numeric_edit(ui, &mut tab.y_max_input);
+                        let mut preset = false;
+                        egui::ComboBox::from_id_source(("y_presets", tab.id))
+                            .selected_text("Presets")
+                            .show_ui(ui, |ui| {
+                                // A fixed range only shows if autoscaling is off.
+                                for (label, min, max) in Y_PRESETS {
+                                    if ui.selectable_label(false, label).clicked() {
+                                        tab.y_min_input = min.to_string();
+                                        tab.y_max_input = max.to_string();
+                                        tab.autoscale = Autoscale::Off;
+                                        preset = true;
+                                        tab.reset_view = true;
+                                    }
+                                }
+                                if ui.selectable_label(false, "Auto").clicked() {
+                                    tab.autoscale = Autoscale::Nice;
+                                    preset = true;
+                                    tab.reset_view = true;
+                                }
+                            });
+                        let fitted = ui
+                            .button("Robust fit")
+                            .on_hover_text("Fit y to the middle 90% of the sampled values, ignoring spikes")
+                            .clicked()
+                            && tab.robust_fit_y();
+                        if (changed1 || changed2 || preset || fitted) && self.auto_update {
+                            tab.resample();
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Go to x:");
+                        ui.add(egui::TextEdit::singleline(&mut tab.goto_x).desired_width(60.0));
+                        ui.label("y:");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut tab.goto_y)
+                                .desired_width(60.0)
+                                .hint_text("optional"),
+                        );
+                        let x = parse_finite(&tab.goto_x);
+                        // A y that is typed but invalid blocks the jump rather than being dropped.
+                        let y = parse_finite(&tab.goto_y);
+                        let y_ok = tab.goto_y.trim().is_empty() || y.is_some();
+                        if ui
+                            .add_enabled(x.is_some() && y_ok, egui::Button::new("Go"))
+                            .clicked()
+                        {
+                            if let Some(x) = x {
+                                tab.center_on(x, y);
+                                if self.auto_update {
+                                    tab.resample();
+                                }
+                            }
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Padding:");
+                        ui.add(egui::Slider::new(&mut tab.plot_padding, 0.0..=25.0).suffix("%"));
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Autoscale y:");
+                        egui::ComboBox::from_id_source(("autoscale", tab.id))
+                            .selected_text(tab.autoscale.label())
+                            .show_ui(ui, |ui| {
+                                for mode in [Autoscale::Off, Autoscale::Tight, Autoscale::Nice] {
+                                    ui.selectable_value(&mut tab.autoscale, mode, mode.label());
+                                }
+                            });
+                    });
+                    ui.checkbox(
+                        &mut tab.auto_swap_bounds,
+                        "Swap reversed bounds automatically",
+                    );
+                    if let Some(ref err) = tab.domain_error {
+                        ui.colored_label(egui::Color32::RED, err);
+                    } else if let Some(ref notice) = tab.domain_notice {
+                        ui.colored_label(egui::Color32::from_rgb(70, 130, 180), notice);
+                    }
+                });
+                ui.separator();
+
+                // --- Additional Controls ---
+                ui.group(|ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("Plot Title:");
+                        ui.text_edit_singleline(&mut tab.plot_title);
+                        ui.checkbox(&mut self.show_cursor_ticks, "Cursor tick labels")
+                            .on_hover_text(
+                                "Over the plot, Ctrl+Shift+C copies the cursor position \
+                                 and R finds the root nearest the cursor",
+                            );
+                        if ui.checkbox(&mut self.measure_mode, "Measure").changed() {
+                            self.measure_points.clear();
+                        }
+                        ui.checkbox(&mut self.show_loupe, "Loupe");
+                        ui.add_enabled(
+                            self.show_loupe,
+                            egui::Slider::new(&mut self.loupe_zoom, 2.0..=16.0)
+                                .logarithmic(true)
+                                .suffix("×"),
+                        );
+                        ui.checkbox(&mut tab.minor_grid, "Show minor grid");
+                        ui.add_enabled(
+                            tab.minor_grid,
+                            egui::DragValue::new(&mut tab.grid_subdivisions)
+                                .clamp_range(2..=10)
+                                .suffix(" subdivisions"),
+                        );
+                        ui.checkbox(&mut tab.polar_grid, "Polar grid")
+                            .on_hover_text("Always shown while a function is in polar mode");
+                        ui.checkbox(&mut self.mirror_diagonal, "Mirror about y = x")
+                            .on_hover_text(
+                                "Swaps x and y of everything drawn, showing inverse relations",
+                            );
+                        ui.checkbox(&mut tab.legend_range, "Show range in legend");
+                        ui.label("UI Scale:");
+                        ui.add(egui::Slider::new(&mut self.ui_scale, 0.5..=3.0).step_by(0.05));
+                        ui.checkbox(&mut self.autosave, "Restore session on startup");
+                        ui.checkbox(&mut self.focus_on_startup, "Focus f1 on startup")
+                            .on_hover_text("Start with the first expression selected, ready to type over");
+                        ui.checkbox(&mut self.dark_mode, "Dark mode");
+                        let mut high_contrast = self.high_contrast;
+                        if ui
+                            .checkbox(&mut high_contrast, "High contrast")
+                            .on_hover_text("Bright curves on black, thicker lines and larger text")
+                            .changed()
+                        {
+                            pending_command = Some(Command::ToggleHighContrast);
+                        }
+                        ui.checkbox(&mut self.show_performance, "Performance overlay")
+                            .on_hover_text("Frame rate, frame time and whether the curves were recomputed");
+                    });
+                    if let Some(Err(e)) = &tab.root {
+                        ui.colored_label(egui::Color32::RED, e);
                     }
+                    ui.horizontal(|ui| {
+                        ui.label("Tick labels in multiples of:");
+                        for (axis, ticks) in [("x", &mut tab.x_ticks), ("y", &mut tab.y_ticks)] {
+                            ui.checkbox(&mut ticks.enabled, axis);
+                            ui.add_enabled_ui(ticks.enabled, |ui| {
+                                ui.add(egui::TextEdit::singleline(&mut ticks.label).desired_width(30.0))
+                                    .on_hover_text("Name written in the labels");
+                                ui.label("=");
+                                ui.add(egui::TextEdit::singleline(&mut ticks.value).desired_width(60.0))
+                                    .on_hover_text("Value of one unit; any constant expression");
+                            });
+                            if let Some(Err(e)) = ticks.unit(&tab.parse_options, &tab.constants) {
+                                ui.colored_label(egui::Color32::RED, e);
+                            }
+                        }
+                        if ui
+                            .button("π labels")
+                            .on_hover_text("Label the x-axis in multiples of π")
+                            .clicked()
+                        {
+                            tab.x_ticks = TickUnit {
+                                enabled: true,
+                                ..TickUnit::pi()
+                            };
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Theme file:");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut self.theme_path)
+                                .hint_text("theme.json"),
+                        );
+                        if ui.button("Load").clicked() {
+                            pending_command = Some(Command::LoadTheme);
+                        }
+                        if let Some(theme) = &self.theme {
+                            if ui.button("Clear").clicked() {
+                                pending_command = Some(Command::ClearTheme);
+                            }
+                            if !theme.name.is_empty() {
+                                ui.label(format!("Using \"{}\"", theme.name));
+                            }
+                        }
+                        if let Some(e) = &self.theme_error {
+                            ui.colored_label(egui::Color32::RED, e);
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Plot interaction:");
+                        ui.checkbox(&mut self.interaction.drag, "Drag");
+                        ui.checkbox(&mut self.interaction.zoom, "Zoom");
+                        ui.checkbox(&mut self.interaction.scroll, "Scroll");
+                        ui.checkbox(&mut self.interaction.boxed_zoom, "Box zoom");
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Quality:");
+                        let slider = egui::Slider::new(&mut self.quality, 0..=QUALITY_PRESETS.len() - 1)
+                            .custom_formatter(|v, _| QUALITY_PRESETS[v as usize].label.to_owned())
+                            .custom_parser(|text| {
+                                QUALITY_PRESETS
+                                    .iter()
+                                    .position(|p| p.label.eq_ignore_ascii_case(text.trim()))
+                                    .map(|i| i as f64)
+                            });
+                        if ui
+                            .add(slider)
+                            .on_hover_text("Trades drawing quality for speed: sets the sample points, antialiasing, following the view, point reduction and smoothing")
+                            .changed()
+                        {
+                            let preset = &QUALITY_PRESETS[self.quality];
+                            ctx.tessellation_options_mut(|o| o.feathering = preset.antialiasing);
+                            tab.apply_quality(preset);
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Sample Points:");
+                        let mut changed = ui
+                            .add(
+                                egui::Slider::new(&mut tab.num_points, MIN_POINTS..=MAX_POINTS)
+                                    .text("points"),
+                            )
+                            .on_hover_text("Alt+scroll over the plot also changes this")
+                            .changed();
+                        let mut log_sampling = tab.sampling == Sampling::Log;
+                        if ui
+                            .checkbox(&mut log_sampling, "Log sampling")
+                            .on_hover_text("Space samples evenly in log(x), denser near x min")
+                            .changed()
+                        {
+                            tab.sampling = if log_sampling {
+                                Sampling::Log
+                            } else {
+                                Sampling::Linear
+                            };
+                            changed = true;
+                        }
+                        if ui
+                            .checkbox(&mut tab.follow_view, "Follow view")
+                            .on_hover_text("Resample over the visible x range after panning or zooming")
+                            .changed()
+                        {
+                            tab.view_x = None;
+                            tab.view_pending = None;
+                            changed = true;
+                        }
+                        if changed && self.auto_update {
+                            tab.resample();
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        let mut changed = ui
+                            .checkbox(&mut tab.explicit_grid, "Explicit grid")
+                            .on_hover_text("Sample at start, start + step, … up to stop")
+                            .changed();
+                        ui.add_enabled_ui(tab.explicit_grid, |ui| {
+                            for (label, input) in [
+                                ("Start:", &mut tab.grid_start_input),
+                                ("Step:", &mut tab.grid_step_input),
+                                ("Stop:", &mut tab.grid_stop_input),
+                            ] {
+                                ui.label(label);
+                                changed |= ui
+                                    .add(egui::TextEdit::singleline(input).desired_width(60.0))
+                                    .changed();
+                            }
+                        });
+                        if changed && self.auto_update {
+                            tab.resample();
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        let mut changed = ui.checkbox(&mut tab.limit_y, "Limit |y| to").changed();
+                        changed |= ui
+                            .add_enabled(
+                                tab.limit_y,
+                                egui::Slider::new(&mut tab.y_limit, 1e3..=1e15).logarithmic(true),
+                            )
+                            .changed();
+                        if changed && self.auto_update {
+                            tab.resample();
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Point reduction tolerance:");
+                        let changed = ui
+                            .add(
+                                egui::Slider::new(&mut tab.decimate_tolerance, 1e-6..=1.0)
+                                    .logarithmic(true),
+                            )
+                            .changed();
+                        if changed && self.auto_update {
+                            tab.resample();
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Overlap tolerance:");
+                        let changed = ui
+                            .add(
+                                egui::Slider::new(&mut tab.overlap_tolerance, 1e-12..=1e-2)
+                                    .logarithmic(true),
+                            )
+                            .changed();
+                        if changed && self.auto_update {
+                            tab.resample();
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        let mut changed = ui
+                            .checkbox(
+                                &mut tab.parse_options.radix_literals,
+                                "Hex/binary literals (0xff, 0b1010)",
+                            )
+                            .changed();
+                        changed |= ui
+                            .checkbox(&mut tab.parse_options.rpn, "RPN input")
+                            .on_hover_text("Postfix notation, e.g. x 2 ^ x sin +")
+                            .changed();
+                        tab.needs_reparse |= changed;
+                        ui.label("Variable:");
+                        changed |= ui
+                            .add(
+                                egui::TextEdit::singleline(&mut tab.parse_options.variable)
+                                    .desired_width(40.0)
+                                    .hint_text("auto"),
+                            )
+                            .on_hover_text("Leave empty to detect it from each expression")
+                            .changed();
+                        if changed && self.auto_update {
+                            tab.resample();
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Max Functions:");
+                        ui.add(egui::Slider::new(&mut tab.max_functions, 1..=50));
+                    });
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.auto_update, "Real-time update");
+                        if !self.auto_update && ui.button("Plot").clicked() {
+                            tab.resample();
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Export:");
+                        ui.text_edit_singleline(&mut self.export_path);
+                        ui.add(egui::DragValue::new(&mut self.export_width).clamp_range(16..=8192));
+                        ui.label("×");
+                        ui.add(egui::DragValue::new(&mut self.export_height).clamp_range(16..=8192));
+                        ui.add(
+                            egui::DragValue::new(&mut self.export_line_width)
+                                .clamp_range(0.5..=40.0)
+                                .speed(0.1)
+                                .prefix("line "),
+                        )
+                        .on_hover_text("Curve width in the exported image, drawn with round joins");
+                        let running = self.export_job.is_some();
+                        if ui
+                            .add_enabled(!running, egui::Button::new("Export PNG"))
+                            .clicked()
+                        {
+                            pending_command = Some(Command::ExportPng);
+                        }
+                        if ui
+                            .add_enabled(!running, egui::Button::new("Export SVG"))
+                            .clicked()
+                        {
+                            pending_command = Some(Command::ExportSvg);
+                        }
+                        if ui
+                            .add_enabled(!running, egui::Button::new("Export archive"))
+                            .on_hover_text("A zip with the session, the curve data as CSV and a PNG")
+                            .clicked()
+                        {
+                            pending_command = Some(Command::ExportArchive);
+                        }
+                        if running {
+                            ui.spinner();
+                            if let Some((done, total)) = self.export_progress {
+                                ui.label(format!("frame {}/{}", done, total));
+                            }
+                        }
+                        match self.export_status {
+                            Some(Ok(ref message)) => {
+                                ui.label(message);
+                            }
+                            Some(Err(ref err)) => {
+                                ui.colored_label(egui::Color32::RED, err);
+                            }
+                            None => {}
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Animate:");
+                        let selected = tab
+                            .animation_constant
+                            .and_then(|i| tab.constants.get(i))
+                            .map_or("(none)".to_owned(), |c| c.name.clone());
+                        egui::ComboBox::from_id_source(("animation_constant", tab.id))
+                            .selected_text(selected)
+                            .show_ui(ui, |ui| {
+                                for (i, constant) in tab.constants.iter().enumerate() {
+                                    ui.selectable_value(
+                                        &mut tab.animation_constant,
+                                        Some(i),
+                                        &constant.name,
+                                    );
+                                }
+                            });
+                        ui.add(
+                            egui::DragValue::new(&mut self.animation_frames)
+                                .clamp_range(2..=1000)
+                                .suffix(" frames"),
+                        );
+                        ui.label("into");
+                        ui.text_edit_singleline(&mut self.animation_dir);
+                        if ui
+                            .add_enabled(
+                                self.export_job.is_none(),
+                                egui::Button::new("Export animation"),
+                            )
+                            .clicked()
+                        {
+                            pending_command = Some(Command::ExportAnimation);
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        if ui.button("Snapshot").clicked() {
+                            tab.take_snapshot();
+                        }
+                        if ui
+                            .add_enabled(
+                                !tab.snapshots.is_empty(),
+                                egui::Button::new("Clear Snapshot"),
+                            )
+                            .clicked()
+                        {
+                            tab.snapshots.clear();
+                        }
+                    });
                 });
-                ui.horizontal(|ui| {
-                    ui.label("y min:");
-                    let changed1 = ui.text_edit_singleline(&mut self.y_min_input).changed();
-                    ui.label("y max:");
-                    let changed2 = ui.text_edit_singleline(&mut self.y_max_input).changed();
-                    if (changed1 || changed2) && self.auto_update {
-                        self.update_functions();
+                ui.separator();
+
+                // --- Constants ---
+                ui.group(|ui| {
+                    ui.heading("Constants:");
+                    let mut remove_index = None;
+                    let mut changed = false;
+                    for (i, constant) in tab.constants.iter_mut().enumerate() {
+                        ui.horizontal(|ui| {
+                            changed |= ui
+                                .add(egui::TextEdit::singleline(&mut constant.name).desired_width(60.0))
+                                .changed();
+                            ui.label("=");
+                            changed |= ui
+                                .add(egui::Slider::new(
+                                    &mut constant.value,
+                                    constant.min..=constant.max,
+                                ))
+                                .changed();
+                            ui.label("range");
+                            changed |= ui
+                                .add(egui::DragValue::new(&mut constant.min).speed(0.1))
+                                .changed();
+                            changed |= ui
+                                .add(egui::DragValue::new(&mut constant.max).speed(0.1))
+                                .changed();
+                            if ui.button("Remove").clicked() {
+                                remove_index = Some(i);
+                            }
+                        });
+                    }
+                    if let Some(i) = remove_index {
+                        tab.constants.remove(i);
+                        tab.animation_constant = None;
+                        changed = true;
+                    }
+                    if ui.button("Add Constant").clicked() {
+                        let name = ["a", "b", "c", "k", "m", "n"]
+                            .into_iter()
+                            .find(|n| tab.constants.iter().all(|c| c.name != *n))
+                            .unwrap_or("a");
+                        tab.constants.push(Constant::new(name));
+                        changed = true;
+                    }
+                    if changed && self.auto_update {
+                        tab.resample();
                     }
                 });
-                if let Some(ref err) = self.domain_error {
-                    ui.colored_label(egui::Color32::RED, err);
-                }
-            });
-            ui.separator();
+                ui.separator();
 
-            // --- Additional Controls ---
-            ui.group(|ui| {
-                ui.horizontal(|ui| {
-                    ui.label("Sample Points:");
-                    let changed = ui
-                        .add(egui::Slider::new(&mut self.num_points, 100..=5000).text("points"))
-                        .changed();
-                    if changed && self.auto_update {
-                        self.update_functions();
+                // --- Functions List ---
+                ui.group(|ui| {
+                    ui.heading("Functions:");
+                    let mut remove_indices = Vec::new();
+                    let mut transformed = false;
+                    let functions_len = tab.functions.len();
+
+                    // Alt+Up/Down moves between expression boxes, Alt+Enter expands or
+                    // collapses the focused panel. Plain arrows are left to the text edits.
+                    let (nav_up, nav_down, nav_toggle) = ui.input(|i| {
+                        (
+                            i.modifiers.alt && i.key_pressed(egui::Key::ArrowUp),
+                            i.modifiers.alt && i.key_pressed(egui::Key::ArrowDown),
+                            i.modifiers.alt && i.key_pressed(egui::Key::Enter),
+                        )
+                    });
+                    tab.focused_function = tab.focused_function.min(functions_len.saturating_sub(1));
+                    let mut focus_target = None;
+                    if functions_len > 0 && (nav_up || nav_down) {
+                        tab.focused_function = if nav_up {
+                            (tab.focused_function + functions_len - 1) % functions_len
+                        } else {
+                            (tab.focused_function + 1) % functions_len
+                        };
+                        focus_target = Some(tab.focused_function);
+                    }
+                    // On startup the first expression is focused with its text
+                    // selected, so typing replaces it.
+                    let select_all = std::mem::take(&mut self.startup_focus_pending) && functions_len > 0;
+                    if select_all {
+                        tab.focused_function = 0;
+                        focus_target = Some(0);
+                    }
+
+                    let mut set_all_expanded = None;
+                    ui.horizontal(|ui| {
+                        if ui.button("Expand all").clicked() {
+                            set_all_expanded = Some(true);
+                        }
+                        if ui.button("Collapse all").clicked() {
+                            set_all_expanded = Some(false);
+                        }
+                    });
+
+                    let mut hovered_function = None;
+                    tab.sync_groups();
+                    let mut current_group = String::new();
+                    let mut rename = None;
+                    for i in tab.grouped_order() {
+                        // Each group's header row goes above its first member.
+                        let group = tab.functions[i].group.trim().to_owned();
+                        let k = tab.groups.iter().position(|g| g.name == group);
+                        if let Some(k) = k.filter(|_| group != current_group) {
+                            let members =
+                                tab.functions.iter().filter(|f| f.group.trim() == group).count();
+                            ui.horizontal(|ui| {
+                                let g = &mut tab.groups[k];
+                                let arrow = if g.collapsed { "▶" } else { "▼" };
+                                if ui.small_button(arrow).clicked() {
+                                    g.collapsed = !g.collapsed;
+                                }
+                                let mut name = match &tab.group_rename {
+                                    Some((old, draft)) if *old == g.name => draft.clone(),
+                                    _ => g.name.clone(),
+                                };
+                                let response =
+                                    ui.add(egui::TextEdit::singleline(&mut name).desired_width(100.0));
+                                if response.changed() {
+                                    tab.group_rename = Some((g.name.clone(), name));
+                                }
+                                if response.lost_focus() {
+                                    rename = tab.group_rename.take();
+                                }
+                                ui.checkbox(&mut g.visible, "Visible");
+                                let mut tinted = g.tint.is_some();
+                                if ui.checkbox(&mut tinted, "Tint").changed() {
+                                    g.tint = tinted.then_some(egui::Color32::GRAY);
+                                }
+                                if let Some(tint) = &mut g.tint {
+                                    ui.color_edit_button_srgba(tint);
+                                }
+                                ui.label(format!(
+                                    "{} function{}",
+                                    members,
+                                    if members == 1 { "" } else { "s" }
+                                ));
+                            });
+                            current_group = group;
+                        }
+                        if let Some(g) = k.map(|k| &mut tab.groups[k]) {
+                            if focus_target == Some(i) {
+                                g.collapsed = false;
+                            } else if g.collapsed {
+                                continue;
+                            }
+                        }
+                        let func = &mut tab.functions[i];
+                        let panel_id = ("function_panel", tab.id, i);
+                        let mut header = egui::CollapsingHeader::new(format!("Function {}", i + 1))
+                            .id_source(panel_id)
+                            .default_open(func.expanded);
+                        if focus_target == Some(i) {
+                            header = header.open(Some(true));
+                        } else if nav_toggle && tab.focused_function == i {
+                            header = header.open(Some(!func.expanded));
+                        } else if let Some(open) = set_all_expanded {
+                            header = header.open(Some(open));
+                        }
+                        let panel = header.show(ui, |ui| {
+                            ui.horizontal(|ui| {
+                                // Switching modes keeps every mode's inputs, so switching back restores them.
+                                egui::ComboBox::from_id_source(("plot_mode", tab.id, i))
+                                    .selected_text(func.mode.label())
+                                    .show_ui(ui, |ui| {
+                                        for mode in PlotMode::ALL {
+                                            transformed |= ui
+                                                .selectable_value(&mut func.mode, mode, mode.label())
+                                                .changed();
+                                        }
+                                    });
+                                let variable = match tab.parse_options.variable.trim() {
+                                    "" => "x",
+                                    name => name,
+                                };
+                                let (label, text) = match func.mode {
+                                    PlotMode::Cartesian => {
+                                        (format!("f{}({}) = ", i + 1, variable), &mut func.expression)
+                                    }
+                                    PlotMode::Parametric => {
+                                        ("x(t) =".to_owned(), &mut func.parametric.x)
+                                    }
+                                    PlotMode::Polar => ("r(theta) =".to_owned(), &mut func.polar.r),
+                                };
+                                ui.label(label);
+                                let expression_id = ui.make_persistent_id(("expression", tab.id, i));
+                                let length = text.chars().count();
+                                let response =
+                                    ui.add(egui::TextEdit::singleline(text).id(expression_id));
+                                transformed |= response.changed();
+                                if select_all && focus_target == Some(i) {
+                                    if let Some(mut state) = egui::TextEdit::load_state(ui.ctx(), expression_id) {
+                                        state.set_ccursor_range(Some(egui::text_edit::CCursorRange::two(
+                                            egui::text::CCursor::new(0),
+                                            egui::text::CCursor::new(length),
+                                        )));
+                                        egui::TextEdit::store_state(ui.ctx(), expression_id, state);
+                                    }
+                                }
+                                if func.mode == PlotMode::Parametric {
+                                    ui.label("y(t) =");
+                                    transformed |=
+                                        ui.text_edit_singleline(&mut func.parametric.y).changed();
+                                }
+                                if focus_target == Some(i) {
+                                    response.request_focus();
+                                }
+                                if response.has_focus() {
+                                    tab.focused_function = i;
+                                }
+                                // A hand-picked color is kept across theme switches.
+                                if ui.color_edit_button_srgba(&mut func.color).changed() {
+                                    func.color_locked = true;
+                                }
+                                ui.checkbox(&mut func.color_locked, "Lock color");
+                                ui.label("Group:");
+                                ui.add(
+                                    egui::TextEdit::singleline(&mut func.group)
+                                        .hint_text("none")
+                                        .desired_width(70.0),
+                                );
+                                if func.mode == PlotMode::Cartesian
+                                    && ui
+                                        .button("Copy LaTeX")
+                                        .on_hover_text("Copy the expression to the clipboard as LaTeX")
+                                        .clicked()
+                                {
+                                    let latex = func.latex(&tab.parse_options);
+                                    ui.output_mut(|o| o.copied_text = latex);
+                                }
+                                if functions_len > 1 && ui.button("Remove").clicked() {
+                                    remove_indices.push(i);
+                                }
+                            });
+                            let range = match func.mode {
+                                PlotMode::Cartesian => None,
+                                PlotMode::Parametric => Some((
+                                    "t",
+                                    &mut func.parametric.t_min,
+                                    &mut func.parametric.t_max,
+                                )),
+                                PlotMode::Polar => Some((
+                                    "theta",
+                                    &mut func.polar.theta_min,
+                                    &mut func.polar.theta_max,
+                                )),
+                            };
+                            if let Some((name, min, max)) = range {
+                                ui.horizontal(|ui| {
+                                    ui.label(format!("{} from", name));
+                                    transformed |= ui
+                                        .add(egui::TextEdit::singleline(min).desired_width(60.0))
+                                        .changed();
+                                    ui.label("to");
+                                    transformed |= ui
+                                        .add(egui::TextEdit::singleline(max).desired_width(60.0))
+                                        .changed();
+                                    ui.checkbox(&mut func.close_curve, "Close curve")
+                                        .on_hover_text("Join the end of the curve back to its start");
+                                });
+                            } else {
+                                ui.horizontal(|ui| {
+                                    ui.checkbox(&mut func.slice_enabled, "Slices of")
+                                        .on_hover_text(
+                                            "Draw one curve per value of a second variable in the expression",
+                                        );
+                                    ui.add(
+                                        egui::TextEdit::singleline(&mut func.slice_variable)
+                                            .desired_width(30.0),
+                                    );
+                                    ui.label("at");
+                                    ui.add(
+                                        egui::TextEdit::singleline(&mut func.slice_values)
+                                            .hint_text("0, 0.5, 1")
+                                            .desired_width(120.0),
+                                    );
+                                });
+                            }
+                            ui.horizontal(|ui| {
+                                let mut has_band = func.uncertainty_expr.is_some();
+                                if ui.checkbox(&mut has_band, "± δ(x) =").changed() {
+                                    func.uncertainty_expr = has_band.then(|| "0.1".to_owned());
+                                }
+                                if let Some(ref mut uncertainty) = func.uncertainty_expr {
+                                    ui.text_edit_singleline(uncertainty);
+                                }
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Limit as x →");
+                                ui.add(
+                                    egui::TextEdit::singleline(&mut func.limit_at).desired_width(60.0),
+                                );
+                                match &func.limit_result {
+                                    Some(Ok(text)) => {
+                                        ui.label(text);
+                                    }
+                                    Some(Err(e)) => {
+                                        ui.colored_label(egui::Color32::RED, e);
+                                    }
+                                    None => {}
+                                }
+                            });
+                            ui.horizontal(|ui| {
+                                ui.checkbox(&mut func.show_taylor, "Taylor polynomial about");
+                                ui.add(
+                                    egui::TextEdit::singleline(&mut func.taylor_center)
+                                        .desired_width(60.0),
+                                );
+                                ui.add(
+                                    egui::DragValue::new(&mut func.taylor_order)
+                                        .clamp_range(0..=MAX_TAYLOR_ORDER)
+                                        .prefix("order "),
+                                )
+                                .on_hover_text(
+                                    "Derivatives are estimated numerically; high orders are unstable",
+                                );
+                                if let Some(Err(e)) = &func.taylor {
+                                    ui.colored_label(egui::Color32::RED, e);
+                                }
+                            });
+                            ui.horizontal(|ui| {
+                                ui.checkbox(&mut func.show_symmetry, "Symmetry");
+                                if func.show_symmetry {
+                                    ui.label(match func.symmetry {
+                                        Some(symmetry) => symmetry.label(),
+                                        None => "f(-x) is undefined on the domain",
+                                    });
+                                    ui.checkbox(&mut func.show_reflection, "Show mirror image")
+                                        .on_hover_text("Draw the graph of f(-x) faintly");
+                                }
+                            });
+                            ui.horizontal(|ui| {
+                                egui::ComboBox::from_id_source(("sign_strip", tab.id, i))
+                                    .selected_text(func.sign_strip.label())
+                                    .show_ui(ui, |ui| {
+                                        for strip in
+                                            [SignStrip::Off, SignStrip::Value, SignStrip::Derivative]
+                                        {
+                                            ui.selectable_value(
+                                                &mut func.sign_strip,
+                                                strip,
+                                                strip.label(),
+                                            );
+                                        }
+                                    });
+                                ui.checkbox(&mut func.show_asymptotes, "Show asymptotes");
+                                ui.checkbox(&mut func.plot_inverse, "Plot inverse");
+                                ui.checkbox(&mut func.secondary_axis, "Secondary axis")
+                                    .on_hover_text(
+                                        "Rescale onto the primary range; read values on the right-hand scale",
+                                    );
+                                egui::ComboBox::from_id_source(("interpolation", tab.id, i))
+                                    .selected_text(func.interpolation.label())
+                                    .show_ui(ui, |ui| {
+                                        for style in Interpolation::ALL {
+                                            ui.selectable_value(
+                                                &mut func.interpolation,
+                                                style,
+                                                style.label(),
+                                            );
+                                        }
+                                    });
+                                if ui.checkbox(&mut func.decimate, "Reduce points").changed() {
+                                    transformed = true;
+                                }
+                                if ui
+                                    .checkbox(&mut func.rigorous, "Rigorous (interval)")
+                                    .on_hover_text(
+                                        "Bounds the function between samples with interval arithmetic \
+                                         and draws a vertical extent wherever it may spike",
+                                    )
+                                    .changed()
+                                {
+                                    transformed = true;
+                                }
+                                if ui
+                                    .checkbox(&mut func.complex_mode, "Complex mode")
+                                    .on_hover_text(
+                                        "Evaluates over the complex numbers, so sqrt(x) has values \
+                                         for x < 0; only the chosen real part is plotted",
+                                    )
+                                    .changed()
+                                {
+                                    transformed = true;
+                                }
+                                if func.complex_mode {
+                                    egui::ComboBox::from_id_source(("complex_part", tab.id, i))
+                                        .selected_text(func.complex_part.label())
+                                        .show_ui(ui, |ui| {
+                                            for part in ComplexPart::ALL {
+                                                transformed |= ui
+                                                    .selectable_value(
+                                                        &mut func.complex_part,
+                                                        part,
+                                                        part.label(),
+                                                    )
+                                                    .changed();
+                                            }
+                                        });
+                                }
+                                if let Some(note) = &func.rigorous_note {
+                                    ui.colored_label(egui::Color32::from_rgb(200, 120, 0), note);
+                                } else if func.rigorous {
+                                    ui.label(format!("{} extents", func.extents.len()));
+                                }
+                                if let Some(ref simplified) = func.simplified {
+                                    ui.label(format!(
+                                        "{} → {} points",
+                                        func.plot_points.len(),
+                                        simplified.len()
+                                    ));
+                                }
+                                if !func.breaks.is_empty() {
+                                    ui.label(format!(
+                                        "{} samples beyond the |y| limit hidden",
+                                        func.breaks.len()
+                                    ));
+                                }
+                                if func.plot_inverse && !is_monotonic(&func.plot_points) {
+                                    ui.colored_label(
+                                        egui::Color32::from_rgb(200, 120, 0),
+                                        "Not monotonic on this domain: the inverse is not a function",
+                                    );
+                                }
+                            });
+                            ui.horizontal(|ui| {
+                                let t = &mut func.transform;
+                                ui.label("b·f(a·(x − h)) + k:");
+                                transformed |= ui
+                                    .add(egui::Slider::new(&mut t.a, -5.0..=5.0).text("a"))
+                                    .changed();
+                                transformed |= ui
+                                    .add(egui::Slider::new(&mut t.b, -5.0..=5.0).text("b"))
+                                    .changed();
+                                transformed |= ui
+                                    .add(egui::Slider::new(&mut t.h, -10.0..=10.0).text("h"))
+                                    .changed();
+                                transformed |= ui
+                                    .add(egui::Slider::new(&mut t.k, -10.0..=10.0).text("k"))
+                                    .changed();
+                                if ui
+                                    .add_enabled(
+                                        *t != CurveTransform::default(),
+                                        egui::Button::new("Reset"),
+                                    )
+                                    .clicked()
+                                {
+                                    *t = CurveTransform::default();
+                                    transformed = true;
+                                }
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Transform:");
+                                if ui.button("1/").on_hover_text("Reciprocal: 1/(f)").clicked() {
+                                    func.wrap_expression("1/", "");
+                                    transformed = true;
+                                }
+                                if ui.button("±").on_hover_text("Negate: -(f)").clicked() {
+                                    func.wrap_expression("-", "");
+                                    transformed = true;
+                                }
+                                if ui.button("²").on_hover_text("Square: (f)^2").clicked() {
+                                    func.wrap_expression("", "^2");
+                                    transformed = true;
+                                }
+                            });
+                            if let [[x, y]] = func.plot_points[..] {
+                                ui.label(format!("f({}) = {}", x, y));
+                            }
+                            if let Some(ref err) = func.error_message {
+                                ui.horizontal(|ui| {
+                                    ui.colored_label(egui::Color32::RED, err);
+                                    let missing = preprocess::missing_closing_parens(&func.expression);
+                                    if missing > 0 && ui.button("Auto-fix").clicked() {
+                                        func.expression.push_str(&")".repeat(missing));
+                                        transformed = true;
+                                    }
+                                });
+                            }
+                        });
+                        let mut panel_rect = panel.header_response.rect;
+                        if let Some(body) = &panel.body_response {
+                            panel_rect = panel_rect.union(body.rect);
+                        }
+                        if ui.rect_contains_pointer(panel_rect) {
+                            hovered_function = Some(i);
+                        }
+                        // Track the panel state so it can be restored and toggled explicitly.
+                        let id = ui.make_persistent_id(panel_id);
+                        func.expanded = egui::collapsing_header::CollapsingState::load(ui.ctx(), id)
+                            .is_some_and(|state| state.is_open());
+                    }
+                    self.hovered_function = hovered_function;
+                    if let Some((old, new)) = rename {
+                        tab.rename_group(&old, &new);
+                    }
+                    for &(i, j) in &tab.coincident_pairs {
+                        ui.colored_label(
+                            egui::Color32::from_rgb(200, 120, 0),
+                            format!(
+                                "Note: Function {} and Function {} draw the same curve",
+                                i + 1,
+                                j + 1
+                            ),
+                        );
+                    }
+                    let removed = !remove_indices.is_empty();
+                    for &i in remove_indices.iter().rev() {
+                        tab.remove_function(i);
+                    }
+
+                    // Differences between pairs of functions.
+                    let mut differences_changed = false;
+                    let mut remove_difference = None;
+                    for (k, (i, j, color)) in tab.differences.iter_mut().enumerate() {
+                        ui.horizontal(|ui| {
+                            ui.color_edit_button_srgba(color);
+                            ui.label(format!("Function {} − Function {}", *i + 1, *j + 1));
+                            if ui.button("Remove").clicked() {
+                                remove_difference = Some(k);
+                            }
+                        });
+                    }
+                    if let Some(k) = remove_difference {
+                        tab.differences.remove(k);
+                        differences_changed = true;
+                    }
+                    if tab.functions.len() >= 2 {
+                        ui.horizontal(|ui| {
+                            let last = tab.functions.len() - 1;
+                            let (i, j) = &mut tab.difference_selection;
+                            *i = (*i).min(last);
+                            *j = (*j).min(last);
+                            ui.label("Difference: Function");
+                            ui.add(
+                                egui::DragValue::new(i).custom_formatter(|n, _| format!("{}", n + 1.0)),
+                            );
+                            ui.label("− Function");
+                            ui.add(
+                                egui::DragValue::new(j).custom_formatter(|n, _| format!("{}", n + 1.0)),
+                            );
+                            *i = (*i).min(last);
+                            *j = (*j).min(last);
+                            if ui
+                                .add_enabled(*i != *j, egui::Button::new("Add difference"))
+                                .clicked()
+                            {
+                                let color = function_color(
+                                    &tab.palette,
+                                    tab.functions.len() + tab.differences.len(),
+                                );
+                                tab.differences.push((*i, *j, color));
+                                differences_changed = true;
+                            }
+                        });
+                    }
+
+                    if (transformed || removed || differences_changed) && self.auto_update {
+                        tab.resample();
+                    }
+                    let can_add = tab.functions.len() < tab.max_functions;
+                    if ui
+                        .add_enabled(can_add, egui::Button::new("Add Function"))
+                        .clicked()
+                    {
+                        tab.add_function();
+                        if self.auto_update {
+                            tab.resample();
+                        }
+                    }
+                    ui.horizontal(|ui| {
+                        if ui.button("Paste expressions…").clicked() {
+                            pending_command = Some(Command::PasteExpressions);
+                        }
+                        if ui.button("Functions help").clicked() {
+                            pending_command = Some(Command::FunctionHelp);
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Watch file:");
+                        match &self.watch {
+                            None => {
+                                ui.text_edit_singleline(&mut self.watch_path);
+                                if ui
+                                    .button("Watch")
+                                    .on_hover_text("Load one expression per line, and reload whenever the file is saved")
+                                    .clicked()
+                                {
+                                    pending_command = Some(Command::WatchFile);
+                                }
+                            }
+                            Some((_, file_watch)) => {
+                                ui.label(file_watch.path.display().to_string());
+                                if ui.button("Unwatch").clicked() {
+                                    pending_command = Some(Command::Unwatch);
+                                }
+                            }
+                        }
+                        if let Some(Err(e)) = &self.watch_status {
+                            ui.colored_label(egui::Color32::RED, e);
+                        }
+                    });
+                    if let Some(Ok(errors)) = &self.watch_status {
+                        for (number, error) in errors {
+                            ui.colored_label(egui::Color32::RED, format!("Line {}: {}", number, error));
+                        }
                     }
                 });
+                ui.separator();
+
+                // --- Calculator ---
                 ui.horizontal(|ui| {
-                    ui.checkbox(&mut self.auto_update, "Real-time update");
-                    if !self.auto_update {
-                        if ui.button("Plot").clicked() {
-                            self.update_functions();
+                    ui.label("Calculator:");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut tab.calculator_input)
+                            .hint_text("sin(pi/4) * sqrt(2)"),
+                    );
+                    // Re-evaluated every frame so it follows the constant sliders.
+                    let result = match tab.calculator_input.trim() {
+                        "" => None,
+                        text => Some(evaluate_constant(text, &tab.parse_options, &tab.constants)),
+                    };
+                    match result {
+                        Some(Ok(value)) => {
+                            ui.label(format!("= {}", value));
+                            if ui.small_button("Copy").clicked() {
+                                ui.output_mut(|o| o.copied_text = value.to_string());
+                            }
                         }
+                        Some(Err(e)) => {
+                            ui.colored_label(egui::Color32::RED, e.to_string());
+                        }
+                        None => {}
                     }
                 });
-            });
-            ui.separator();
-
-            // --- Functions List ---
-            ui.group(|ui| {
-                ui.heading("Functions:");
-                let mut remove_indices = Vec::new();
-                let functions_len = self.functions.len();
-                for (i, func) in self.functions.iter_mut().enumerate() {
-                    ui.collapsing(format!("Function {}", i + 1), |ui| {
-                        ui.horizontal(|ui| {
-                            ui.label("f(x) = ");
-                            ui.text_edit_singleline(&mut func.expression);
-                            if functions_len > 1 {
-                                if ui.button("Remove").clicked() {
-                                    remove_indices.push(i);
+                ui.separator();
+
+                // --- Data Series ---
+                ui.group(|ui| {
+                    ui.heading("Data Series:");
+                    ui.horizontal(|ui| {
+                        ui.label("File:");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut tab.data_path)
+                                .hint_text("x,y per line (CSV or whitespace)"),
+                        );
+                        if ui.button("Load").clicked() {
+                            let path = PathBuf::from(tab.data_path.trim());
+                            match data::load_points(&path) {
+                                Ok(points) => {
+                                    let name = path.file_stem().map_or_else(
+                                        || "data".to_owned(),
+                                        |stem| stem.to_string_lossy().into_owned(),
+                                    );
+                                    let color = function_color(
+                                        &tab.palette,
+                                        tab.functions.len() + tab.data_series.len(),
+                                    );
+                                    tab.data_series.push(DataSeries {
+                                        name,
+                                        points,
+                                        color,
+                                        smooth: false,
+                                        window: 5,
+                                    });
+                                    tab.data_error = None;
                                 }
+                                Err(e) => tab.data_error = Some(e),
+                            }
+                        }
+                    });
+                    if let Some(e) = &tab.data_error {
+                        ui.colored_label(egui::Color32::RED, e);
+                    }
+                    let mut remove_index = None;
+                    for (k, series) in tab.data_series.iter_mut().enumerate() {
+                        ui.horizontal(|ui| {
+                            ui.color_edit_button_srgba(&mut series.color);
+                            ui.label(format!("{} ({} points)", series.name, series.points.len()));
+                            ui.checkbox(&mut series.smooth, "Moving average over");
+                            ui.add_enabled(
+                                series.smooth,
+                                egui::DragValue::new(&mut series.window)
+                                    .clamp_range(2..=101)
+                                    .suffix(" points"),
+                            );
+                            if ui.button("Remove").clicked() {
+                                remove_index = Some(k);
+                            }
+                        });
+                    }
+                    if let Some(k) = remove_index {
+                        tab.data_series.remove(k);
+                        tab.update_residuals();
+                    }
+                    if !tab.data_series.is_empty() {
+                        ui.horizontal(|ui| {
+                            ui.label("Fit the constants of Function");
+                            let mut changed = ui
+                                .add(
+                                    egui::DragValue::new(&mut tab.fit_function)
+                                        .clamp_range(0..=tab.functions.len().saturating_sub(1))
+                                        .custom_formatter(|n, _| format!("{}", n + 1.0)),
+                                )
+                                .changed();
+                            ui.label("to series");
+                            changed |= ui
+                                .add(
+                                    egui::DragValue::new(&mut tab.fit_series)
+                                        .clamp_range(0..=tab.data_series.len() - 1)
+                                        .custom_formatter(|n, _| format!("{}", n + 1.0)),
+                                )
+                                .changed();
+                            if ui.button("Fit").clicked() {
+                                tab.fit_to_data();
+                            }
+                            changed |= ui
+                                .checkbox(&mut tab.show_residuals, "Show residuals")
+                                .changed();
+                            if changed {
+                                tab.update_residuals();
                             }
                         });
-                        if let Some(ref err) = func.error_message {
-                            ui.colored_label(egui::Color32::RED, err);
+                        match &tab.residuals {
+                            Ok(points) => {
+                                let values: Vec<f64> = points.iter().map(|p| p[1]).collect();
+                                if let Some(stats) = data::residual_stats(&values) {
+                                    ui.label(format!(
+                                        "Residuals: mean {:.6}, RMS {:.6}, max |r| {:.6}",
+                                        stats.mean, stats.rms, stats.max
+                                    ));
+                                }
+                            }
+                            Err(e) => {
+                                ui.colored_label(egui::Color32::RED, e);
+                            }
+                        }
+                        match &tab.fit_result {
+                            Some(Ok(summary)) => {
+                                ui.label(summary);
+                            }
+                            Some(Err(e)) => {
+                                ui.colored_label(egui::Color32::RED, e);
+                            }
+                            None => {}
+                        }
+                    }
+                });
+                ui.separator();
+
+                // --- Riemann Sum ---
+                ui.group(|ui| {
+                    let settings = &mut tab.riemann;
+                    let mut changed = false;
+                    ui.horizontal(|ui| {
+                        changed |= ui.checkbox(&mut settings.enabled, "Riemann sum").changed();
+                        ui.label("of Function");
+                        changed |= ui
+                            .add(
+                                egui::DragValue::new(&mut settings.function)
+                                    .clamp_range(0..=tab.functions.len().saturating_sub(1))
+                                    .custom_formatter(|n, _| format!("{}", n + 1.0)),
+                            )
+                            .changed();
+                        ui.label("over a =");
+                        changed |= ui
+                            .add(egui::TextEdit::singleline(&mut settings.a_input).desired_width(50.0))
+                            .changed();
+                        ui.label("b =");
+                        changed |= ui
+                            .add(egui::TextEdit::singleline(&mut settings.b_input).desired_width(50.0))
+                            .changed();
+                    });
+                    ui.horizontal(|ui| {
+                        changed |= ui
+                            .add(egui::Slider::new(&mut settings.count, 1..=200).text("rectangles"))
+                            .changed();
+                        egui::ComboBox::from_id_source(("riemann_rule", tab.id))
+                            .selected_text(settings.rule.label())
+                            .show_ui(ui, |ui| {
+                                for rule in RiemannRule::ALL {
+                                    changed |= ui
+                                        .selectable_value(&mut settings.rule, rule, rule.label())
+                                        .changed();
+                                }
+                            });
+                        match &tab.riemann_result {
+                            Some(Ok(sum)) => {
+                                ui.label(format!("Sum ≈ {:.6}", sum));
+                            }
+                            Some(Err(e)) => {
+                                ui.colored_label(egui::Color32::RED, e);
+                            }
+                            None => {}
+                        }
+                    });
+                    if changed {
+                        tab.update_riemann();
+                    }
+                });
+                ui.separator();
+
+                // --- Inequality ---
+                ui.group(|ui| {
+                    let settings = &mut tab.inequality;
+                    let last = tab.functions.len().saturating_sub(1);
+                    let mut changed = false;
+                    ui.horizontal(|ui| {
+                        changed |= ui.checkbox(&mut settings.enabled, "Shade where").changed();
+                        ui.label("f");
+                        changed |= ui
+                            .add(
+                                egui::DragValue::new(&mut settings.left)
+                                    .clamp_range(0..=last)
+                                    .custom_formatter(|n, _| format!("{}", n + 1.0)),
+                            )
+                            .changed();
+                        let symbol = if settings.greater { ">" } else { "<" };
+                        if ui.button(symbol).on_hover_text("Flip the comparison").clicked() {
+                            settings.greater = !settings.greater;
+                            changed = true;
+                        }
+                        ui.label("f");
+                        changed |= ui
+                            .add(
+                                egui::DragValue::new(&mut settings.right)
+                                    .clamp_range(0..=last)
+                                    .custom_formatter(|n, _| format!("{}", n + 1.0)),
+                            )
+                            .changed();
+                    });
+                    match &tab.inequality_intervals {
+                        Some(Ok(intervals)) if intervals.is_empty() => {
+                            ui.label("Holds nowhere on the domain");
+                        }
+                        Some(Ok(intervals)) => {
+                            let span = intervals.last().unwrap().1 - intervals[0].0;
+                            let decimals = decimals_for_span(span);
+                            let text = intervals
+                                .iter()
+                                .map(|(a, b)| format!("({:.*}, {:.*})", decimals, a, decimals, b))
+                                .collect::<Vec<_>>()
+                                .join(" ∪ ");
+                            ui.label(format!("Holds for x in {}", text));
+                        }
+                        Some(Err(e)) => {
+                            ui.colored_label(egui::Color32::RED, e);
+                        }
+                        None => {}
+                    }
+                    if changed {
+                        tab.update_inequality();
+                    }
+                });
+                ui.separator();
+
+                // --- Small Multiples ---
+                ui.group(|ui| {
+                    let settings = &mut tab.small_multiples;
+                    let mut changed = false;
+                    ui.horizontal(|ui| {
+                        changed |= ui
+                            .checkbox(&mut settings.enabled, "Small multiples of")
+                            .on_hover_text("One small plot per parameter value, side by side")
+                            .changed();
+                        ui.label("f");
+                        changed |= ui
+                            .add(
+                                egui::DragValue::new(&mut settings.function)
+                                    .clamp_range(0..=tab.functions.len().saturating_sub(1))
+                                    .custom_formatter(|n, _| format!("{}", n + 1.0)),
+                            )
+                            .changed();
+                        ui.label("over");
+                        changed |= ui
+                            .add(egui::TextEdit::singleline(&mut settings.variable).desired_width(30.0))
+                            .changed();
+                        ui.label("=");
+                        changed |= ui
+                            .add(egui::TextEdit::singleline(&mut settings.values).desired_width(120.0))
+                            .on_hover_text("Values and start:step:stop ranges, separated by commas")
+                            .changed();
+                        ui.label("in");
+                        ui.add(
+                            egui::DragValue::new(&mut settings.columns)
+                                .clamp_range(1..=6)
+                                .suffix(" columns"),
+                        );
+                    });
+                    if let Some(Err(e)) = &tab.small_multiple_cells {
+                        ui.colored_label(egui::Color32::RED, e);
+                    }
+                    if changed {
+                        tab.update_small_multiples();
+                    }
+                });
+                ui.separator();
+
+                // --- Fourier Series ---
+                ui.group(|ui| {
+                    let settings = &mut tab.fourier;
+                    let mut changed = false;
+                    ui.horizontal(|ui| {
+                        changed |= ui
+                            .checkbox(&mut settings.enabled, "Fourier series")
+                            .on_hover_text("Approximates the function, taking the domain as one period")
+                            .changed();
+                        ui.label("of Function");
+                        changed |= ui
+                            .add(
+                                egui::DragValue::new(&mut settings.function)
+                                    .clamp_range(0..=tab.functions.len().saturating_sub(1))
+                                    .custom_formatter(|n, _| format!("{}", n + 1.0)),
+                            )
+                            .changed();
+                        changed |= ui
+                            .add(egui::Slider::new(&mut settings.terms, 1..=50).text("terms"))
+                            .changed();
+                        if let Err(e) = &tab.fourier_points {
+                            ui.colored_label(egui::Color32::RED, e);
                         }
                     });
+                    if changed {
+                        tab.update_fourier();
+                    }
+                });
+                ui.separator();
+            }
+
+            // --- Plot Area ---
+            // Non-finite bounds would corrupt the view, so they fall back to the defaults.
+            let x_min = parse_finite(&tab.x_min_input).unwrap_or(-10.0);
+            let x_max = parse_finite(&tab.x_max_input).unwrap_or(10.0);
+            let mut y_min = parse_finite(&tab.y_min_input).unwrap_or(-10.0);
+            let mut y_max = parse_finite(&tab.y_max_input).unwrap_or(10.0);
+            if let Some((lo, hi)) = tab.data_y_range() {
+                match tab.autoscale {
+                    Autoscale::Off => {}
+                    Autoscale::Tight => (y_min, y_max) = (lo, hi),
+                    Autoscale::Nice => (y_min, y_max) = nice_bounds(lo, hi),
+                }
+            }
+            // Secondary range paired with the primary range it is mapped onto.
+            let secondary = tab.secondary_range().map(|range| (range, (y_min, y_max)));
+            if !tab.plot_title.trim().is_empty() {
+                ui.vertical_centered(|ui| ui.heading(tab.plot_title.trim()));
+            }
+            let pad_x = (x_max - x_min).abs() * tab.plot_padding / 100.0;
+            let pad_y = (y_max - y_min).abs() * tab.plot_padding / 100.0;
+            // Everything is drawn through `mirror_point`, which swaps x and y
+            // while the view is mirrored about y = x.
+            let mirror = self.mirror_diagonal;
+            // Leave room below the plot for the sign strips, which follow the
+            // x-axis and so are left out while it is vertical.
+            const STRIP_HEIGHT: f32 = 10.0;
+            let strips: Vec<&FunctionPlot> = tab
+                .functions
+                .iter()
+                .filter(|f| {
+                    f.sign_strip != SignStrip::Off && !f.plot_inverse && !mirror && tab.is_shown(f)
+                })
+                .collect();
+            let strips_height = strips.len() as f32 * (STRIP_HEIGHT + 2.0);
+            // Alt+scroll changes the sample count instead of panning.
+            let (alt, scroll) = ui.input(|i| (i.modifiers.alt, i.scroll_delta.y));
+            let [low, high] = [[x_min - pad_x, y_min - pad_y], [x_max + pad_x, y_max + pad_y]]
+                .map(|corner| mirror_point(corner, mirror));
+            // The id stays fixed so renaming the plot doesn't reset its view.
+            let mut plot = Plot::new(("Function Plot", tab.id))
+                .height((ui.available_height() - strips_height).max(100.0))
+                .data_aspect(1.0)
+                .include_x(low[0])
+                .include_x(high[0])
+                .include_y(low[1])
+                .include_y(high[1])
+                .legend(Legend::default())
+                .allow_drag(self.interaction.drag)
+                .allow_zoom(self.interaction.zoom)
+                .allow_scroll(self.interaction.scroll && !alt)
+                .allow_boxed_zoom(self.interaction.boxed_zoom);
+            if std::mem::take(&mut tab.reset_view) {
+                plot = plot.reset();
+            }
+            if tab.minor_grid {
+                // egui draws lines with a larger step size more strongly.
+                let subdivisions = tab.grid_subdivisions;
+                plot = plot
+                    .x_grid_spacer(uniform_grid_spacer(move |input| {
+                        grid_steps(input, subdivisions)
+                    }))
+                    .y_grid_spacer(uniform_grid_spacer(move |input| {
+                        grid_steps(input, subdivisions)
+                    }));
+            }
+            // Ticks in multiples of a constant replace the spacing above on their axis.
+            let tick_unit = |ticks: &TickUnit| {
+                let unit = ticks.unit(&tab.parse_options, &tab.constants)?.ok()?;
+                Some((unit, ticks.label.trim().to_owned()))
+            };
+            let (x_ticks, y_ticks) = match mirror {
+                true => (&tab.y_ticks, &tab.x_ticks),
+                false => (&tab.x_ticks, &tab.y_ticks),
+            };
+            if let Some((unit, label)) = tick_unit(x_ticks) {
+                plot = plot
+                    .x_grid_spacer(uniform_grid_spacer(move |input| unit_grid_steps(input, unit)))
+                    .x_axis_formatter(move |value, _| format_unit_tick(value, unit, &label));
+            }
+            if let Some((unit, label)) = tick_unit(y_ticks) {
+                plot = plot
+                    .y_grid_spacer(uniform_grid_spacer(move |input| unit_grid_steps(input, unit)))
+                    .y_axis_formatter(move |value, _| format_unit_tick(value, unit, &label));
+            }
+            let polar_grid =
+                tab.polar_grid || tab.functions.iter().any(|f| f.mode == PlotMode::Polar);
+            let grid_color = self
+                .theme
+                .as_ref()
+                .and_then(|theme| theme.grid)
+                .unwrap_or(egui::Color32::GRAY.gamma_multiply(0.5));
+            // Cursor position at which R was pressed, to look for a root there.
+            let mut root_request = None;
+            let plot_response = plot.show(ui, |plot_ui| {
+                    let view = |p: [f64; 2]| mirror_point(p, mirror);
+                    let line_scale = match self.high_contrast {
+                        true => HIGH_CONTRAST_LINE_SCALE,
+                        false => 1.0,
+                    };
+                    // Screen rectangles of the labels drawn so far, so later ones can avoid them.
+                    let mut labels = Vec::new();
+                    // The polar grid goes behind everything else; it looks the same mirrored.
+                    if polar_grid {
+                        let bounds = plot_ui.plot_bounds();
+                        let (min, max) = (bounds.min(), bounds.max());
+                        let radii = polar_grid_radii(min, max);
+                        for &r in &radii {
+                            let circle = (0..=POLAR_CIRCLE_POINTS).map(|k| {
+                                let angle =
+                                    std::f64::consts::TAU * k as f64 / POLAR_CIRCLE_POINTS as f64;
+                                [r * angle.cos(), r * angle.sin()]
+                            });
+                            plot_ui.line(
+                                Line::new(PlotPoints::from_iter(circle))
+                                    .color(grid_color)
+                                    .width(1.0),
+                            );
+                        }
+                        // Spokes reach the farthest corner of the view.
+                        let outer = min[0].abs().max(max[0].abs()).hypot(min[1].abs().max(max[1].abs()));
+                        for k in 0..POLAR_SPOKES {
+                            let angle = std::f64::consts::TAU * k as f64 / POLAR_SPOKES as f64;
+                            let spoke = vec![[0.0, 0.0], [outer * angle.cos(), outer * angle.sin()]];
+                            plot_ui.line(Line::new(PlotPoints::new(spoke)).color(grid_color).width(1.0));
+                        }
+                        // Radii are labelled along the direction of the view's center,
+                        // or along the positive x axis while the origin is in view.
+                        let origin_visible =
+                            min[0] <= 0.0 && max[0] >= 0.0 && min[1] <= 0.0 && max[1] >= 0.0;
+                        let angle = match origin_visible {
+                            true => 0.0,
+                            false => (min[1] + max[1]).atan2(min[0] + max[0]),
+                        };
+                        let decimals = decimals_for_span(outer);
+                        for &r in &radii {
+                            place_label(
+                                plot_ui,
+                                &mut labels,
+                                PlotPoint::new(r * angle.cos(), r * angle.sin()),
+                                format!("{:.*}", decimals, r),
+                                egui::Color32::GRAY,
+                                egui::Align2::LEFT_BOTTOM,
+                            );
+                        }
+                    }
+                    // Snapshots go first so the live curves are drawn on top.
+                    for (points, color) in &tab.snapshots {
+                        let line = Line::new(PlotPoints::from_iter(points.iter().map(|&p| view(p))))
+                            .color(color.gamma_multiply(0.3))
+                            .width(1.5);
+                        plot_ui.line(line);
+                    }
+                    // The inequality's region too, in the left-hand function's color,
+                    // unless either side is in a hidden group.
+                    let sides = [tab.inequality.left, tab.inequality.right]
+                        .map(|i| tab.functions.get(i).filter(|f| tab.is_shown(f)));
+                    if let [Some(func), Some(_)] = sides {
+                        for shape in &tab.inequality_shapes {
+                            plot_ui.polygon(
+                                Polygon::new(PlotPoints::from_iter(shape.iter().map(|&p| view(p))))
+                                    .color(tab.display_color(func))
+                                    .fill_alpha(0.25)
+                                    .width(0.0),
+                            );
+                        }
+                    }
+                    // Riemann slices sit under the curves, tinted like their function.
+                    if let Some(func) = tab.functions.get(tab.riemann.function) {
+                        for shape in &tab.riemann_shapes {
+                            plot_ui.polygon(
+                                Polygon::new(PlotPoints::from_iter(shape.iter().map(|&p| view(p))))
+                                    .color(func.color)
+                                    .fill_alpha(0.2)
+                                    .width(1.0),
+                            );
+                        }
+                    }
+                    for (i, func) in tab.functions.iter().enumerate() {
+                        if !tab.is_shown(func) {
+                            continue;
+                        }
+                        let place = |p: [f64; 2]| {
+                            let [x, y] = func.display_point(p);
+                            view(match secondary {
+                                Some((from, to)) if func.secondary_axis => {
+                                    [x, secondary_to_primary(y, from, to)]
+                                }
+                                _ => [x, y],
+                            })
+                        };
+                        // While a panel is hovered, its curve stands out and the rest fade.
+                        let name = func.legend_label(i, tab.legend_range);
+                        let base = tab.display_color(func);
+                        let (color, width) = match self.hovered_function {
+                            Some(h) if h == i => (base, 3.5),
+                            Some(_) => (base.gamma_multiply(0.3), 2.0),
+                            None => (base, 2.0),
+                        };
+                        let width = width * line_scale;
+                        // Uncertainty band, one quad per sample interval (egui only fills convex polygons).
+                        for (lower, upper) in
+                            func.band_lower.windows(2).zip(func.band_upper.windows(2))
+                        {
+                            let quad = [lower[0], lower[1], upper[1], upper[0]].map(place).to_vec();
+                            plot_ui.polygon(
+                                Polygon::new(PlotPoints::new(quad))
+                                    .color(func.color)
+                                    .fill_alpha(0.15)
+                                    .width(0.0),
+                            );
+                        }
+                        if let [point] = func.plot_points[..] {
+                            plot_ui.points(
+                                Points::new(vec![place(point)])
+                                    .name(&name)
+                                    .color(color)
+                                    .radius(width * 2.0),
+                            );
+                        } else {
+                            for segment in split_at_breaks(func.drawn_points(), &func.breaks) {
+                                // Interpolation only changes the drawing; the samples stay as computed.
+                                let mut points = match func.mode {
+                                    PlotMode::Cartesian => func.interpolation.apply(segment),
+                                    _ => segment.to_vec(),
+                                };
+                                points.extend(func.closing_point());
+                                let line =
+                                    Line::new(PlotPoints::from_iter(points.iter().map(|&p| place(p))))
+                                .name(&name)
+                                .color(color)
+                                .width(width);
+                                plot_ui.line(line);
+                            }
+                        }
+                        for &[x, low, high] in &func.extents {
+                            plot_ui.line(
+                                Line::new(vec![place([x, low]), place([x, high])])
+                                    .color(color)
+                                    .width(width),
+                            );
+                        }
+                        // Slices fade from the function's color so their order stays readable.
+                        let variable = func.slice_variable.trim();
+                        for (k, (value, points)) in func.slices.iter().enumerate() {
+                            let shade =
+                                color.gamma_multiply(1.0 - 0.6 * k as f32 / func.slices.len() as f32);
+                            plot_ui.line(
+                                Line::new(PlotPoints::from_iter(points.iter().map(|&p| place(p))))
+                                    .name(format!("f{} ({} = {})", i + 1, variable, value))
+                                    .color(shade)
+                                    .width(width),
+                            );
+                            if let Some(&last) = points.last() {
+                                let [x, y] = place(last);
+                                place_label(
+                                    plot_ui,
+                                    &mut labels,
+                                    PlotPoint::new(x, y),
+                                    format!("{} = {}", variable, value),
+                                    shade,
+                                    egui::Align2::RIGHT_BOTTOM,
+                                );
+                            }
+                        }
+                        if func.show_symmetry && func.show_reflection && func.symmetry.is_some() {
+                            let mirrored = func.drawn_points().iter().map(|&[x, y]| place([-x, y]));
+                            plot_ui.line(
+                                Line::new(PlotPoints::from_iter(mirrored))
+                                    .name(format!("f{}(-x)", i + 1))
+                                    .color(func.color.gamma_multiply(0.35))
+                                    .width(width),
+                            );
+                        }
+                        if let Some(Ok(points)) = &func.taylor {
+                            plot_ui.line(
+                                Line::new(PlotPoints::from_iter(points.iter().map(|&p| place(p))))
+                                    .name(format!(
+                                        "f{} Taylor, order {} about {}",
+                                        i + 1,
+                                        func.taylor_order,
+                                        func.taylor_center.trim()
+                                    ))
+                                    .color(contrasting_shade(func.color))
+                                    .style(LineStyle::dashed_loose())
+                                    .width(2.0),
+                            );
+                        }
+                        let asymptote_color = func.color.gamma_multiply(0.5);
+                        for &x in &func.asymptotes {
+                            data_axis_line(
+                                plot_ui,
+                                x,
+                                !func.plot_inverse,
+                                mirror,
+                                asymptote_color,
+                                LineStyle::dashed_loose(),
+                            );
+                        }
+                    }
+
+                    // Data points, with their smoothed curve (if any) on top.
+                    for series in &tab.data_series {
+                        plot_ui.points(
+                            Points::new(series.points.iter().map(|&p| view(p)).collect::<Vec<_>>())
+                                .name(&series.name)
+                                .color(series.color)
+                                .radius(2.5),
+                        );
+                        if series.smooth {
+                            plot_ui.line(
+                                Line::new(PlotPoints::from_iter(
+                                    data::moving_average(&series.points, series.window)
+                                        .into_iter()
+                                        .map(view),
+                                ))
+                                    .name(format!("{} (moving average)", series.name))
+                                    .color(series.color)
+                                    .width(2.0),
+                            );
+                        }
+                    }
+
+                    // Residuals as stems from the x-axis, in the data series' color.
+                    if let (Some(series), Ok(points)) =
+                        (tab.data_series.get(tab.fit_series), &tab.residuals)
+                    {
+                        for &[x, r] in points {
+                            plot_ui.line(
+                                Line::new(vec![view([x, 0.0]), view([x, r])])
+                                    .color(series.color.gamma_multiply(0.6))
+                                    .width(1.0),
+                            );
+                        }
+                        plot_ui.points(
+                            Points::new(points.iter().map(|&p| view(p)).collect::<Vec<_>>())
+                                .name(format!("{} residuals", series.name))
+                                .color(series.color)
+                                .shape(egui::plot::MarkerShape::Diamond)
+                                .radius(3.0),
+                        );
+                    }
+
+                    // The Fourier approximation is dashed, on top of the curve it approximates.
+                    if let (Some(func), Ok(points)) =
+                        (tab.functions.get(tab.fourier.function), &tab.fourier_points)
+                    {
+                        if !points.is_empty() {
+                            plot_ui.line(
+                                Line::new(PlotPoints::from_iter(points.iter().map(|&p| view(p))))
+                                    .name(format!("Fourier series, {} terms", tab.fourier.terms))
+                                    .color(func.color)
+                                    .style(LineStyle::dashed_dense())
+                                    .width(2.0),
+                            );
+                        }
+                    }
+
+                    for (&(i, j, color), points) in
+                        tab.differences.iter().zip(&tab.difference_points)
+                    {
+                        let Some(&last) = points.last() else {
+                            continue;
+                        };
+                        let [x, y] = view(last);
+                        plot_ui.line(
+                            Line::new(PlotPoints::from_iter(points.iter().map(|&p| view(p))))
+                                .color(color)
+                                .width(1.5),
+                        );
+                        place_label(
+                            plot_ui,
+                            &mut labels,
+                            PlotPoint::new(x, y),
+                            format!("f{} − f{}", i + 1, j + 1),
+                            color,
+                            egui::Align2::LEFT_BOTTOM,
+                        );
+                    }
+
+                    // Guides added from the context menu.
+                    let guide_color = egui::Color32::from_rgb(110, 110, 210);
+                    for &x in &tab.guides {
+                        data_axis_line(plot_ui, x, true, mirror, guide_color, LineStyle::dashed_dense());
+                        // Labelled at the top of the view, or at its left while mirrored.
+                        let bounds = plot_ui.plot_bounds();
+                        let [label_x, label_y] = match mirror {
+                            true => [bounds.min()[0], x],
+                            false => [x, bounds.max()[1]],
+                        };
+                        place_label(
+                            plot_ui,
+                            &mut labels,
+                            PlotPoint::new(label_x, label_y),
+                            format!("x = {}", x),
+                            guide_color,
+                            egui::Align2::LEFT_TOP,
+                        );
+                    }
+
+                    // Measurement: a third click starts over, Escape clears.
+                    if self.measure_mode {
+                        if plot_ui.plot_clicked() {
+                            if let Some(pointer) = plot_ui.pointer_coordinate() {
+                                if self.measure_points.len() == 2 {
+                                    self.measure_points.clear();
+                                } else {
+                                    self.measure_points.push(view([pointer.x, pointer.y]));
+                                }
+                            }
+                        }
+                        if plot_ui.ctx().input(|i| i.key_pressed(egui::Key::Escape)) {
+                            self.measure_points.clear();
+                        }
+                        let marker = egui::Color32::from_rgb(230, 120, 0);
+                        plot_ui.points(
+                            Points::new(self.measure_points.iter().map(|&p| view(p)).collect::<Vec<_>>())
+                                .color(marker)
+                                .radius(4.0),
+                        );
+                        if let [a, b] = self.measure_points[..] {
+                            plot_ui.line(Line::new(vec![view(a), view(b)]).color(marker).width(1.5));
+                            let [x, y] = view([0.5 * (a[0] + b[0]), 0.5 * (a[1] + b[1])]);
+                            let middle = PlotPoint::new(x, y);
+                            plot_ui.text(
+                                Text::new(middle, describe_measurement(a, b))
+                                    .color(marker)
+                                    .anchor(egui::Align2::LEFT_BOTTOM),
+                            );
+                        }
+                    }
+
+                    if let Some(Ok((i, x))) = tab.root {
+                        let color = tab.functions.get(i).map_or(egui::Color32::GRAY, |f| f.color);
+                        let [root_x, root_y] = view([x, 0.0]);
+                        plot_ui.points(
+                            Points::new(vec![[root_x, root_y]])
+                                .color(color)
+                                .shape(egui::plot::MarkerShape::Circle)
+                                .radius(5.0),
+                        );
+                        place_label(
+                            plot_ui,
+                            &mut labels,
+                            PlotPoint::new(root_x, root_y),
+                            format!("f{} root ≈ {:.10}", i + 1, x),
+                            color,
+                            egui::Align2::LEFT_BOTTOM,
+                        );
+                    }
+
+                    // Ctrl+Shift+C copies the position under the cursor, R looks
+                    // for a root near it.
+                    if let Some(pointer) = plot_ui.pointer_coordinate() {
+                        let find_root = !plot_ui.ctx().wants_keyboard_input()
+                            && plot_ui.ctx().input_mut(|i| {
+                                i.consume_key(egui::Modifiers::NONE, egui::Key::R)
+                            });
+                        if find_root {
+                            root_request = Some(view([pointer.x, pointer.y]));
+                        }
+                        let copy = plot_ui.ctx().input_mut(|i| {
+                            i.consume_key(
+                                egui::Modifiers::COMMAND | egui::Modifiers::SHIFT,
+                                egui::Key::C,
+                            )
+                        });
+                        if copy {
+                            let bounds = plot_ui.plot_bounds();
+                            let [width, height] = view([bounds.width(), bounds.height()]);
+                            let text = format_coordinates(view([pointer.x, pointer.y]), width, height);
+                            plot_ui.ctx().output_mut(|o| o.copied_text = text);
+                        }
+                    }
+                    // Project the cursor onto the axes as temporary tick labels.
+                    if self.show_cursor_ticks {
+                        if let Some(pointer) = plot_ui.pointer_coordinate() {
+                            let bounds = plot_ui.plot_bounds();
+                            let [left, bottom] = bounds.min();
+                            let guide = egui::Color32::from_gray(150).gamma_multiply(0.5);
+                            plot_ui.vline(VLine::new(pointer.x).color(guide));
+                            plot_ui.hline(HLine::new(pointer.y).color(guide));
+                            let x_label =
+                                format!("{:.*}", decimals_for_span(bounds.width()), pointer.x);
+                            let y_label =
+                                format!("{:.*}", decimals_for_span(bounds.height()), pointer.y);
+                            plot_ui.text(
+                                Text::new(PlotPoint::new(pointer.x, bottom), x_label)
+                                    .anchor(egui::Align2::CENTER_BOTTOM),
+                            );
+                            plot_ui.text(
+                                Text::new(PlotPoint::new(left, pointer.y), y_label)
+                                    .anchor(egui::Align2::LEFT_CENTER),
+                            );
+                        }
+                    }
+                });
+
+            // --- Secondary Axis ---
+            // Tick labels for the secondary range along the right edge of the plot,
+            // which no longer matches it while the view is mirrored.
+            if let Some(((s0, s1), primary)) = secondary.filter(|_| !mirror) {
+                let transform = plot_response.transform;
+                let frame = *transform.frame();
+                let painter = ui.painter_at(frame);
+                let step = export::grid_step(s1 - s0, 8.0);
+                for value in export::grid_lines(s0, s1, step) {
+                    let y = secondary_to_primary(value, (s0, s1), primary);
+                    let pos = transform.position_from_point(&PlotPoint::new(0.0, y));
+                    painter.text(
+                        egui::pos2(frame.right() - 4.0, pos.y),
+                        egui::Align2::RIGHT_CENTER,
+                        export::format_tick(value, step),
+                        egui::FontId::monospace(11.0),
+                        ui.visuals().strong_text_color(),
+                    );
+                }
+            }
+
+            // --- Loupe ---
+            // A magnified copy of the region under the cursor, drawn on top of the plot.
+            if let Some(pointer) = plot_response
+                .response
+                .hover_pos()
+                .filter(|_| self.show_loupe)
+            {
+                const LOUPE_SIZE: f32 = 160.0;
+                let frame = *plot_response.transform.frame();
+                let center = plot_response.transform.value_from_position(pointer);
+                let bounds = plot_response.transform.bounds();
+                // Plot units covered by the inset: its size in screen points, shrunk by the zoom.
+                let span_x = bounds.width() * (LOUPE_SIZE / self.loupe_zoom / frame.width()) as f64;
+                let span_y =
+                    bounds.height() * (LOUPE_SIZE / self.loupe_zoom / frame.height()) as f64;
+
+                // Keep the inset inside the plot frame, flipping it to the other side of the cursor.
+                let mut min = pointer + egui::vec2(20.0, 20.0);
+                if min.x + LOUPE_SIZE > frame.right() {
+                    min.x = pointer.x - 20.0 - LOUPE_SIZE;
                 }
-                for &i in remove_indices.iter().rev() {
-                    self.functions.remove(i);
+                if min.y + LOUPE_SIZE > frame.bottom() {
+                    min.y = pointer.y - 20.0 - LOUPE_SIZE;
                 }
-                if ui.button("Add Function").clicked() {
-                    let default_colors = vec![
-                        egui::Color32::from_rgb(220, 20, 60),
-                        egui::Color32::from_rgb(65, 105, 225),
-                        egui::Color32::from_rgb(34, 139, 34),
-                        egui::Color32::from_rgb(255, 140, 0),
-                        egui::Color32::from_rgb(138, 43, 226),
-                    ];
-                    let color = default_colors[self.functions.len() % default_colors.len()];
-                    self.functions.push(FunctionPlot::new("x", color));
-                    if self.auto_update {
-                        self.update_functions();
+                let inset = egui::Rect::from_min_size(min, egui::vec2(LOUPE_SIZE, LOUPE_SIZE));
+                let to_inset = |[x, y]: [f64; 2]| {
+                    let u = ((x - center.x) / span_x + 0.5).clamp(-10.0, 10.0) as f32;
+                    let v = ((y - center.y) / span_y + 0.5).clamp(-10.0, 10.0) as f32;
+                    egui::pos2(
+                        inset.left() + u * inset.width(),
+                        inset.bottom() - v * inset.height(),
+                    )
+                };
+
+                let painter = ui
+                    .ctx()
+                    .layer_painter(egui::LayerId::new(
+                        egui::Order::Foreground,
+                        egui::Id::new(("loupe", tab.id)),
+                    ))
+                    .with_clip_rect(inset);
+                painter.rect_filled(inset, 0.0, ui.visuals().extreme_bg_color);
+                for func in tab.functions.iter().filter(|f| tab.is_shown(f)) {
+                    for segment in split_at_breaks(func.drawn_points(), &func.breaks) {
+                        let points: Vec<egui::Pos2> = segment
+                            .iter()
+                            .map(|&p| to_inset(mirror_point(func.display_point(p), mirror)))
+                            .collect();
+                        painter.add(egui::Shape::line(
+                            points,
+                            egui::Stroke::new(2.0, tab.display_color(func)),
+                        ));
                     }
                 }
-            });
-            ui.separator();
+                let guide = egui::Stroke::new(1.0, egui::Color32::from_gray(150));
+                painter.line_segment(
+                    [
+                        egui::pos2(inset.center().x, inset.top()),
+                        egui::pos2(inset.center().x, inset.bottom()),
+                    ],
+                    guide,
+                );
+                painter.line_segment(
+                    [
+                        egui::pos2(inset.left(), inset.center().y),
+                        egui::pos2(inset.right(), inset.center().y),
+                    ],
+                    guide,
+                );
+                painter.rect_stroke(inset, 0.0, egui::Stroke::new(1.0, egui::Color32::GRAY));
+            }
 
-            // --- Plot Area ---
-            let x_min = self.x_min_input.trim().parse::<f64>().unwrap_or(-10.0);
-            let x_max = self.x_max_input.trim().parse::<f64>().unwrap_or(10.0);
-            let y_min = self.y_min_input.trim().parse::<f64>().unwrap_or(-10.0);
-            let y_max = self.y_max_input.trim().parse::<f64>().unwrap_or(10.0);
-            Plot::new("Function Plot")
-                .data_aspect(1.0)
-                .include_x(x_min)
-                .include_x(x_max)
-                .include_y(y_min)
-                .include_y(y_max)
-                .show(ui, |plot_ui| {
-                    for func in &self.functions {
-                        if !func.plot_points.is_empty() {
-                            let line = Line::new(PlotPoints::from_iter(func.plot_points.iter().copied()))
-                                .color(func.color)
-                                .width(2.0);
-                            plot_ui.line(line);
-                        }
+            // --- Sign Strips ---
+            // One row per selected function, aligned with the plot's x-axis:
+            // green where the sign is positive, red where it is negative.
+            let transform = plot_response.transform;
+            let frame = *transform.frame();
+            for func in strips {
+                let (rect, _) = ui.allocate_exact_size(
+                    egui::vec2(ui.available_width(), STRIP_HEIGHT),
+                    egui::Sense::hover(),
+                );
+                let painter =
+                    ui.painter_at(rect.intersect(egui::Rect::everything_below(rect.top())));
+                painter.rect_stroke(
+                    egui::Rect::from_x_y_ranges(frame.x_range(), rect.y_range()),
+                    0.0,
+                    egui::Stroke::new(1.0, tab.display_color(func)),
+                );
+                for (x0, x1, sign) in sign_segments(&func.plot_points, func.sign_strip) {
+                    let color = if sign > 0.0 {
+                        egui::Color32::from_rgb(60, 170, 60)
+                    } else if sign < 0.0 {
+                        egui::Color32::from_rgb(210, 60, 60)
+                    } else {
+                        egui::Color32::GRAY
+                    };
+                    let left = transform.position_from_point_x(x0).max(frame.left());
+                    let right = transform.position_from_point_x(x1).min(frame.right());
+                    if left < right {
+                        painter.rect_filled(
+                            egui::Rect::from_x_y_ranges(left..=right, rect.y_range()),
+                            0.0,
+                            color,
+                        );
+                    }
+                }
+                ui.add_space(2.0);
+            }
+
+            // --- Context Menu ---
+            // The menu acts on where it was opened, not where the pointer is now.
+            let response = &plot_response.response;
+            if response.secondary_clicked() {
+                if let Some(pos) = response.interact_pointer_pos() {
+                    let point = plot_response.transform.value_from_position(pos);
+                    tab.context_point = Some(mirror_point([point.x, point.y], mirror));
+                }
+            }
+            if let Some(point) = tab.context_point {
+                let bounds = plot_response.transform.bounds();
+                let [width, height] = mirror_point([bounds.width(), bounds.height()], mirror);
+                response.clone().context_menu(|ui| {
+                    if ui.button("Add vertical guide here").clicked() {
+                        let scale = 10f64.powi(decimals_for_span(width) as i32);
+                        tab.guides.push((point[0] * scale).round() / scale);
+                        ui.close_menu();
+                    }
+                    if !tab.guides.is_empty() && ui.button("Remove guides").clicked() {
+                        tab.guides.clear();
+                        ui.close_menu();
+                    }
+                    if ui.button("Measure from here").clicked() {
+                        self.measure_mode = true;
+                        self.measure_points = vec![point];
+                        ui.close_menu();
+                    }
+                    if ui.button("Copy coordinate").clicked() {
+                        let text = format_coordinates(point, width, height);
+                        ui.output_mut(|o| o.copied_text = text);
+                        ui.close_menu();
+                    }
+                    if ui.button("Zoom to fit").clicked() {
+                        tab.reset_view = true;
+                        ui.close_menu();
                     }
                 });
+            }
+
+            // --- Empty Plot Message ---
+            if let Some(message) = tab.empty_plot_message() {
+                ui.painter_at(plot_response.response.rect).text(
+                    plot_response.response.rect.center(),
+                    egui::Align2::CENTER_CENTER,
+                    message,
+                    egui::FontId::proportional(16.0),
+                    ui.visuals().warn_fg_color,
+                );
+            }
+
+            // --- Scroll to Resample ---
+            if alt && scroll != 0.0 && plot_response.response.hovered() {
+                let num_points = scrolled_num_points(tab.num_points, scroll);
+                if num_points != tab.num_points {
+                    tab.num_points = num_points;
+                    tab.resample();
+                }
+            }
+
+            // --- Nearest Root ---
+            if let Some(pointer) = root_request {
+                tab.root = Some(tab.root_near(pointer));
+            }
+
+            // --- Level of Detail ---
+            if tab.follow_view {
+                let bounds = plot_response.transform.bounds();
+                let [min, max] = [bounds.min(), bounds.max()].map(|b| mirror_point(b, mirror)[0]);
+                let visible = (min, max);
+                if tab.follow_view(visible, ui.input(|i| i.time)) {
+                    ctx.request_repaint_after(std::time::Duration::from_secs_f64(VIEW_DEBOUNCE));
+                }
+            }
         });
 
+        if let Some(command) = pending_command {
+            self.run_command(command);
+        }
+        self.paste_dialog(ctx);
+        self.function_help(ctx);
+        self.small_multiples_window(ctx);
+        self.command_palette(ctx);
+
+        let recomputes = self.tabs.iter().map(|tab| tab.recomputes).sum();
+        let now = ctx.input(|i| i.time);
+        self.frame_stats
+            .record(now, frame.info().cpu_usage, recomputes);
+        self.performance_overlay(ctx);
+
         if self.auto_update {
             ctx.request_repaint();
         }
     }
+
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, UI_SCALE_KEY, &self.ui_scale);
+        eframe::set_value(storage, AUTOSAVE_KEY, &self.autosave);
+        eframe::set_value(storage, STARTUP_FOCUS_KEY, &self.focus_on_startup);
+        eframe::set_value(storage, DARK_MODE_KEY, &self.dark_mode);
+        eframe::set_value(storage, HIGH_CONTRAST_KEY, &self.high_contrast);
+        let theme_path = match self.theme {
+            Some(_) if !self.high_contrast => self.theme_path.trim(),
+            _ => "",
+        };
+        eframe::set_value(storage, THEME_KEY, &theme_path);
+        // eframe calls this periodically as well as on exit.
+        if self.autosave {
+            if let Err(e) = session::write_autosave(&session::Session::capture(self)) {
+                eprintln!("Autosave failed: {}", e);
+            }
+        }
+    }
 }
 
 fn main() {
-    let mut native_options = eframe::NativeOptions::default();
-    native_options.initial_window_size = Some(egui::vec2(1000.0, 700.0));
+    // Any command-line arguments select the headless renderer instead of the GUI.
+    if std::env::args_os().len() > 1 {
+        use clap::Parser;
+        let args = cli::CliArgs::parse();
+        if let Err(e) = cli::run(&args) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let native_options = eframe::NativeOptions {
+        initial_window_size: Some(egui::vec2(1000.0, 700.0)),
+        ..Default::default()
+    };
 
-    eframe::run_native(
+    if let Err(e) = eframe::run_native(
         "Rust Function Plotter - Multiple Functions",
         native_options,
         Box::new(|cc| {
             // --- Custom Styling ---
-            let mut style: egui::Style = (*cc.egui_ctx.style()).clone();
-            style.visuals.dark_mode = false; // Light mode
-            style.visuals.window_fill = egui::Color32::from_rgb(250, 250, 250);
-            style.visuals.widgets.noninteractive.bg_fill = egui::Color32::from_rgb(230, 230, 230);
-            style.visuals.widgets.hovered.bg_fill = egui::Color32::from_rgb(220, 220, 255);
-            style.visuals.widgets.active.bg_fill = egui::Color32::from_rgb(200, 200, 250);
-            cc.egui_ctx.set_style(style);
-            Box::new(App::default())
+            // Light mode; the app switches to dark visuals itself when asked.
+            cc.egui_ctx.set_visuals(light_visuals());
+            Box::new(App::new(cc))
         }),
-    );
+    ) {
+        eprintln!("Failed to start the application: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn robust_y_range_ignores_spikes() {
+        let mut values: Vec<f64> = (0..100).map(|i| i as f64 / 100.0).collect();
+        values.push(1e9);
+        values.push(f64::NAN);
+        // The 5th to 95th percentiles are 0.05 and 0.95, rounded out to halves.
+        assert_eq!(robust_y_range(values), Some((-0.5, 1.5)));
+        assert_eq!(robust_y_range(vec![f64::INFINITY]), None);
+    }
+
+    #[test]
+    fn robust_fit_uses_the_drawn_axes() {
+        let mut tab = PlotTab::new(0);
+        tab.functions = vec![FunctionPlot::new("x + 100", egui::Color32::WHITE)];
+        tab.functions[0].plot_inverse = true;
+        tab.x_min_input = "0".to_owned();
+        tab.x_max_input = "1".to_owned();
+        tab.resample();
+        assert!(tab.robust_fit_y());
+        // The inverse is drawn with x up the vertical axis.
+        let y_max: f64 = tab.y_max_input.parse().unwrap();
+        assert!(y_max < 10.0, "{}", y_max);
+    }
+
+    #[test]
+    fn high_contrast_theme_meets_contrast_guidelines() {
+        let theme = theme::high_contrast();
+        let background = theme.visuals.extreme_bg_color;
+        assert_eq!(background, egui::Color32::BLACK);
+        assert_eq!(contrast_ratio(theme.visuals.text_color(), background), 21.0);
+        // WCAG AAA for text and curves, AA for the red error messages.
+        for color in theme.palette {
+            assert!(contrast_ratio(color, background) >= 7.0, "{:?}", color);
+        }
+        assert!(contrast_ratio(theme.visuals.warn_fg_color, background) >= 7.0);
+        assert!(contrast_ratio(theme.visuals.error_fg_color, background) >= 7.0);
+        assert!(contrast_ratio(egui::Color32::RED, background) >= 4.5);
+
+        let mut app = App::default();
+        app.set_high_contrast(true);
+        assert!(app.dark_mode);
+        assert_eq!(app.tab().functions[0].color, egui::Color32::YELLOW);
+        app.apply_theme(None);
+        assert!(!app.high_contrast);
+    }
+
+    #[test]
+    fn mirroring_swaps_coordinates_and_undoes_itself() {
+        assert_eq!(mirror_point([1.0, 2.0], true), [2.0, 1.0]);
+        assert_eq!(mirror_point([1.0, 2.0], false), [1.0, 2.0]);
+        let view = mirror_point([3.0, -4.0], true);
+        assert_eq!(mirror_point(view, true), [3.0, -4.0]);
+    }
+
+    #[test]
+    fn ticks_are_labelled_as_fractions_of_the_unit() {
+        use std::f64::consts::PI;
+        let label = |value: f64| format_unit_tick(value, PI, "π");
+        assert_eq!(label(0.5 * PI), "π/2");
+        assert_eq!(label(1.5 * PI), "3π/2");
+        assert_eq!(label(-PI), "-π");
+        assert_eq!(label(2.0 * PI), "2π");
+        assert_eq!(label(0.0), "0");
+        assert_eq!(label(1.0), "0.318π");
+        assert_eq!(format_unit_tick(-2.5, 1.0, "k"), "-5k/2");
+    }
+
+    #[test]
+    fn nice_bounds_round_outwards() {
+        assert_eq!(nice_bounds(-0.93, 0.97), (-1.0, 1.0));
+        assert_eq!(nice_bounds(3.0, 47.0), (0.0, 50.0));
+        assert_eq!(nice_bounds(2.5, 2.5), (1.0, 4.0));
+    }
+
+    #[test]
+    fn non_finite_bounds_are_rejected() {
+        for input in ["inf", "-inf", "nan", "NaN"] {
+            let mut tab = PlotTab {
+                x_max_input: input.to_owned(),
+                ..PlotTab::new(0)
+            };
+            tab.resample();
+            assert_eq!(
+                tab.domain_error.as_deref(),
+                Some("x_max must be a finite number")
+            );
+            assert_eq!(parse_finite(input), None);
+            assert!(tab.export_scene().is_err());
+        }
+    }
+
+    #[test]
+    fn simplify_polyline_keeps_corners() {
+        let line: Vec<[f64; 2]> = (0..=10).map(|i| [i as f64, 2.0 * i as f64]).collect();
+        assert_eq!(
+            simplify_polyline(&line, 1e-9),
+            vec![[0.0, 0.0], [10.0, 20.0]]
+        );
+
+        let peak: Vec<[f64; 2]> = (0..=10)
+            .map(|i| [i as f64, -(i as f64 - 5.0).abs()])
+            .collect();
+        assert_eq!(
+            simplify_polyline(&peak, 1e-9),
+            vec![[0.0, -5.0], [5.0, 0.0], [10.0, -5.0]]
+        );
+    }
+
+    #[test]
+    fn pchip_does_not_overshoot() {
+        let step = [[0.0, 0.0], [1.0, 0.0], [2.0, 1.0], [3.0, 1.0]];
+        let smooth = pchip_resample(&step, 10);
+        assert_eq!(smooth.len(), 31);
+        // Passes through every sample and stays within the data range.
+        for p in &step {
+            assert!(smooth.contains(p));
+        }
+        assert!(smooth.iter().all(|p| (0.0..=1.0).contains(&p[1])));
+        assert!(smooth.windows(2).all(|w| w[1][1] >= w[0][1]));
+    }
+
+    #[test]
+    fn riemann_rules_on_identity() {
+        let sum = |rule| riemann_sum(|x| x, 0.0, 1.0, 4, rule).1;
+        assert_eq!(sum(RiemannRule::Left), 0.375);
+        assert_eq!(sum(RiemannRule::Right), 0.625);
+        assert_eq!(sum(RiemannRule::Midpoint), 0.5);
+        assert_eq!(sum(RiemannRule::Trapezoid), 0.5);
+        // The slice crossing zero is split in two.
+        let (shapes, total) = riemann_sum(|x| x, -0.5, 0.5, 1, RiemannRule::Trapezoid);
+        assert_eq!(shapes.len(), 2);
+        assert_eq!(total, 0.0);
+    }
+
+    #[test]
+    fn independent_variable_can_be_renamed() {
+        let t = ParseOptions {
+            variable: "t".to_owned(),
+            ..ParseOptions::default()
+        };
+        assert_eq!(compile_expression("2*t", &t, &[]).unwrap()(3.0), 6.0);
+        assert!(compile_expression("2*x", &t, &[]).is_err());
+
+        let auto = ParseOptions {
+            variable: String::new(),
+            ..ParseOptions::default()
+        };
+        assert_eq!(compile_expression("s^2", &auto, &[]).unwrap()(3.0), 9.0);
+        assert_eq!(
+            compile_expression("pi", &auto, &[]).unwrap()(3.0),
+            std::f64::consts::PI
+        );
+        assert!(compile_expression("s*t", &auto, &[]).is_err());
+    }
+
+    #[test]
+    fn y_limit_breaks_the_curve() {
+        let mut func = FunctionPlot::new("x^3", egui::Color32::WHITE);
+        func.update(
+            -4.0,
+            4.0,
+            8,
+            Sampling::Linear,
+            &ParseOptions::default(),
+            &[],
+        );
+        func.limit_y(10.0);
+        assert_eq!(func.breaks, vec![-4.0, -3.0, 3.0, 4.0]);
+        let segments = split_at_breaks(&func.plot_points, &func.breaks);
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].len(), 5);
+
+        let points = [[-2.0, 0.0], [-1.0, 0.0], [1.0, 0.0], [2.0, 0.0]];
+        assert_eq!(split_at_breaks(&points, &[0.0]).len(), 2);
+    }
+
+    #[test]
+    fn domain_validation_messages() {
+        let tab = |x_min: &str, x_max: &str, y_min: &str, y_max: &str| PlotTab {
+            x_min_input: x_min.to_owned(),
+            x_max_input: x_max.to_owned(),
+            y_min_input: y_min.to_owned(),
+            y_max_input: y_max.to_owned(),
+            auto_swap_bounds: false,
+            ..PlotTab::new(0)
+        };
+        let error = |t: PlotTab| t.validate_domain().unwrap_err().to_string();
+
+        assert_eq!(error(tab("abc", "1", "-1", "1")), "Invalid x_min value");
+        assert_eq!(error(tab("0", "1", "low", "1")), "Invalid y_min value");
+        assert_eq!(error(tab("0", "1", "-1", "")), "Invalid y_max value");
+        assert_eq!(
+            error(tab("2", "1", "-1", "1")),
+            "x_min must not be greater than x_max"
+        );
+        assert_eq!(
+            error(tab("0", "1", "1", "1")),
+            "y_min must be less than y_max"
+        );
+        assert_eq!(
+            error(tab("0", "1", "2", "1")),
+            "y_min must be less than y_max"
+        );
+        assert_eq!(
+            tab("1", "1", "-1", "1").validate_domain(),
+            Ok((1.0, 1.0, -1.0, 1.0))
+        );
+
+        let swapped = PlotTab {
+            auto_swap_bounds: true,
+            ..tab("2", "1", "3", "-3")
+        };
+        assert_eq!(swapped.validate_domain(), Ok((1.0, 2.0, -3.0, 3.0)));
+    }
+
+    #[test]
+    fn valid_domain_clears_the_error() {
+        let mut tab = PlotTab {
+            x_min_input: "oops".to_owned(),
+            ..PlotTab::new(0)
+        };
+        tab.resample();
+        assert_eq!(tab.domain_error.as_deref(), Some("Invalid x_min value"));
+
+        tab.x_min_input = "-5".to_owned();
+        tab.resample();
+        assert_eq!(tab.domain_error, None);
+        assert!(tab.functions.iter().any(|f| !f.plot_points.is_empty()));
+    }
+
+    #[test]
+    fn pasted_lines_are_checked_individually() {
+        let lines = PlotTab::new(0).check_pasted("sin(x)\n\n  x^2 \ncos(x\n");
+        let summary: Vec<(usize, &str, bool)> = lines
+            .iter()
+            .map(|(n, line, error)| (*n, line.as_str(), error.is_some()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![(1, "sin(x)", false), (3, "x^2", false), (4, "cos(x", true)]
+        );
+    }
+
+    #[test]
+    fn removing_a_function_keeps_the_other_colors() {
+        let mut tab = PlotTab::new(0);
+        tab.add_function();
+        let colors: Vec<_> = tab.functions.iter().map(|f| f.color).collect();
+        tab.remove_function(1);
+        assert_eq!(
+            tab.functions.iter().map(|f| f.color).collect::<Vec<_>>(),
+            vec![colors[0], colors[2]]
+        );
+        // The freed color goes to the next new function instead of a duplicate.
+        tab.add_function();
+        assert_eq!(tab.functions[2].color, colors[1]);
+        // Switching palettes follows each function's slot, not its position.
+        tab.remove_function(0);
+        tab.set_palette(PALETTE.to_vec());
+        assert_eq!(tab.functions[0].color, colors[2]);
+    }
+
+    #[test]
+    fn removing_a_function_renumbers_the_inequality() {
+        let mut tab = PlotTab::new(0);
+        tab.add_function();
+        tab.inequality = InequalitySettings {
+            enabled: true,
+            left: 1,
+            right: 2,
+            greater: true,
+        };
+        tab.remove_function(0);
+        assert!(tab.inequality.enabled);
+        assert_eq!((tab.inequality.left, tab.inequality.right), (0, 1));
+        tab.remove_function(1);
+        assert!(!tab.inequality.enabled);
+        assert_eq!((tab.inequality.left, tab.inequality.right), (0, 1));
+    }
+
+    #[test]
+    fn watched_expressions_replace_the_functions() {
+        let mut tab = PlotTab::new(0);
+        tab.functions[0].color_locked = true;
+        let errors = tab.load_expressions("x^2\nsin(\n\ncos(x)\n");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, 2);
+        let expressions: Vec<&str> = tab
+            .functions
+            .iter()
+            .map(|f| f.expression.as_str())
+            .collect();
+        assert_eq!(expressions, vec!["x^2", "sin(", "cos(x)"]);
+        // Existing functions keep their settings.
+        assert!(tab.functions[0].color_locked);
+
+        tab.load_expressions("tan(x)");
+        assert_eq!(tab.functions.len(), 1);
+        // An empty file keeps one function to edit.
+        tab.load_expressions("");
+        assert_eq!(tab.functions.len(), 1);
+    }
+
+    #[test]
+    fn limits_are_classified() {
+        use OneSidedLimit::*;
+        let (left, right) = estimate_limit(&|x: f64| x.sin() / x, 0.0);
+        assert!(
+            matches!((left, right), (Finite(a), Finite(b)) if (a - 1.0).abs() < 1e-9 && (b - 1.0).abs() < 1e-9)
+        );
+        assert_eq!(
+            estimate_limit(&|x: f64| x.signum(), 0.0),
+            (Finite(-1.0), Finite(1.0))
+        );
+        assert_eq!(
+            estimate_limit(&|x: f64| 1.0 / x, 0.0),
+            (Infinite(-1.0), Infinite(1.0))
+        );
+        assert_eq!(estimate_limit(&|x: f64| (1.0 / x).sin(), 0.0).0, Diverges);
+
+        assert_eq!(
+            describe_limit((Finite(1.0), Finite(1.0))),
+            "left = 1.000000, right = 1.000000, limit = 1.000000"
+        );
+        assert!(describe_limit((Finite(-1.0), Finite(1.0))).ends_with("limit = does not exist"));
+        assert!(describe_limit((Infinite(1.0), Infinite(1.0))).ends_with("limit = ∞"));
+    }
+
+    #[test]
+    fn functions_can_reference_each_other() {
+        let mut tab = PlotTab::new(0);
+        tab.functions = vec![
+            FunctionPlot::new("x^2", egui::Color32::WHITE),
+            FunctionPlot::new("f1(x + 1) - 1", egui::Color32::WHITE),
+            FunctionPlot::new("f2(2*x)", egui::Color32::WHITE),
+        ];
+        tab.functions[0].transform.k = 3.0;
+        tab.resample();
+        let f3 = tab.functions[2]
+            .evaluator(&tab.parse_options, &tab.constants)
+            .unwrap();
+        // f3(1) = f2(2) = f1(3) - 1 = (9 + 3) - 1
+        assert_eq!(f3(1.0), 11.0);
+
+        tab.functions[0].expression = "f3(x)".to_owned();
+        tab.functions
+            .push(FunctionPlot::new("f9(x)", egui::Color32::WHITE));
+        tab.resample();
+        assert_eq!(
+            tab.functions[1].error_message.as_deref(),
+            Some("Reference error: circular reference f2 → f1 → f3 → f2")
+        );
+        assert_eq!(
+            tab.functions[3].error_message.as_deref(),
+            Some("Reference error: no function named 'f9'")
+        );
+    }
+
+    #[test]
+    fn log_sampling_is_denser_near_x_min() {
+        let xs: Vec<f64> = (0..=3)
+            .map(|i| Sampling::Log.x(1.0, 1000.0, i, 3))
+            .collect();
+        for (x, expected) in xs.iter().zip([1.0, 10.0, 100.0, 1000.0]) {
+            assert!((x - expected).abs() < 1e-9);
+        }
+        assert_eq!(Sampling::Log.x(-1.0, 1.0, 1, 2), 0.0);
+
+        let tab = PlotTab {
+            sampling: Sampling::Log,
+            ..PlotTab::new(0)
+        };
+        assert_eq!(
+            tab.validate_domain(),
+            Err(PlotError::Domain(
+                "x_min must be positive for log sampling".to_owned()
+            ))
+        );
+    }
+
+    #[test]
+    fn plot_errors_are_typed() {
+        let options = ParseOptions::default();
+        let error = |text| compile_expression(text, &options, &[]).err().unwrap();
+        assert_eq!(
+            error("sin(x"),
+            PlotError::Parse("missing 1 closing parenthesis".to_owned())
+        );
+        assert!(matches!(error("x + y"), PlotError::Bind(_)));
+        assert_eq!(
+            error("sin(x").to_string(),
+            "Parse error: missing 1 closing parenthesis"
+        );
+
+        // Sampling that outlasts its time limit stops with a timeout.
+        let sample = |limit| sample_function_within(&|x| x, 0.0, 1.0, 100, Sampling::Linear, limit);
+        assert_eq!(sample(Duration::ZERO), Err(PlotError::Timeout));
+        assert_eq!(sample(SAMPLE_TIME_LIMIT).map(|p| p.len()), Ok(101));
+    }
+
+    #[test]
+    fn center_on_keeps_the_zoom_width() {
+        let mut tab = PlotTab::new(0);
+        tab.center_on(3.0, None);
+        assert_eq!(
+            (tab.x_min_input.as_str(), tab.x_max_input.as_str()),
+            ("-7", "13")
+        );
+        assert_eq!(tab.y_min_input, "-10.0");
+        tab.center_on(0.5, Some(1.0));
+        assert_eq!(
+            (tab.x_min_input.as_str(), tab.x_max_input.as_str()),
+            ("-9.5", "10.5")
+        );
+        assert_eq!(
+            (tab.y_min_input.as_str(), tab.y_max_input.as_str()),
+            ("-9", "11")
+        );
+        assert!(tab.reset_view);
+    }
+
+    #[test]
+    fn step_interpolation_and_frac() {
+        let points = [[0.0, 0.0], [1.0, 1.0], [2.0, 3.0]];
+        assert_eq!(
+            Interpolation::StepHorizontal.apply(&points),
+            vec![[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [2.0, 1.0], [2.0, 3.0]]
+        );
+        assert_eq!(
+            Interpolation::StepVertical.apply(&points),
+            vec![[0.0, 0.0], [0.0, 1.0], [1.0, 1.0], [1.0, 3.0], [2.0, 3.0]]
+        );
+
+        let frac = compile_expression("frac(x) + floor(x)", &ParseOptions::default(), &[]).unwrap();
+        assert_eq!(frac(-1.25), -1.25);
+    }
+
+    #[test]
+    fn measurement_readout() {
+        assert_eq!(
+            describe_measurement([1.0, 1.0], [4.0, 5.0]),
+            "Δx = 3.0000, Δy = 4.0000, distance = 5.0000, slope = 1.3333"
+        );
+        assert!(describe_measurement([1.0, 1.0], [1.0, 2.0]).ends_with("slope = undefined"));
+    }
+
+    #[test]
+    fn unknown_functions_suggest_a_known_name() {
+        let options = ParseOptions::default();
+        let error = compile_expression("sine(x) + 1", &options, &[])
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Binding error: unknown function 'sine'; did you mean 'sin'?"
+        );
+        assert_eq!(closest_function("sqr"), Some("sqrt"));
+        assert_eq!(closest_function("foobar"), None);
+        assert!(compile_expression("foobar(x)", &options, &[]).is_err());
+        assert!(compile_expression("frac(x)", &options, &[]).is_ok());
+    }
+
+    #[test]
+    fn function_reference_examples_all_work() {
+        let options = ParseOptions::default();
+        for function in FUNCTIONS {
+            let f = compile_expression(function.example, &options, &[]);
+            assert!(f.is_ok(), "{}: {}", function.name, function.example);
+        }
+        for (name, _) in BUILTIN_CONSTANTS {
+            assert!(meval::Context::new().get_var(name).is_some(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn secondary_axis_maps_range_onto_primary() {
+        let (from, to) = ((0.0, 1000.0), (-1.0, 1.0));
+        assert_eq!(secondary_to_primary(0.0, from, to), -1.0);
+        assert_eq!(secondary_to_primary(500.0, from, to), 0.0);
+        assert_eq!(secondary_to_primary(1000.0, from, to), 1.0);
+
+        let mut tab = PlotTab::new(0);
+        tab.functions = vec![
+            FunctionPlot::new("sin(x)", egui::Color32::WHITE),
+            FunctionPlot::new("exp(x)", egui::Color32::WHITE),
+        ];
+        tab.functions[1].secondary_axis = true;
+        tab.x_min_input = "0".to_owned();
+        tab.x_max_input = "3".to_owned();
+        tab.resample();
+        assert_eq!(tab.secondary_range(), Some((0.0, 25.0)));
+        let (lo, hi) = tab.data_y_range().unwrap();
+        assert!(lo >= -1.0 && hi <= 1.0);
+        // An inverted curve spans its x values vertically.
+        tab.functions[1].plot_inverse = true;
+        let (lo, hi) = tab.secondary_range().unwrap();
+        assert!(lo <= 0.0 && (3.0..=5.0).contains(&hi));
+    }
+
+    #[test]
+    fn legend_shows_the_range_on_request() {
+        let mut tab = PlotTab::new(0);
+        tab.functions = vec![FunctionPlot::new("2*x", egui::Color32::WHITE)];
+        tab.x_min_input = "-1".to_owned();
+        tab.x_max_input = "3".to_owned();
+        tab.resample();
+        let func = &tab.functions[0];
+        assert_eq!(func.legend_label(0, false), "f1: 2*x");
+        assert_eq!(func.legend_label(0, true), "f1: 2*x [-2, 6]");
+    }
+
+    #[test]
+    fn following_the_view_waits_for_it_to_settle() {
+        let mut tab = PlotTab::new(0);
+        tab.functions = vec![FunctionPlot::new("x", egui::Color32::WHITE)];
+        tab.follow_view = true;
+        tab.resample();
+        // Small moves keep the samples; a zoom waits out the debounce.
+        assert!(!tab.follow_view((-10.5, 10.5), 0.0));
+        assert!(tab.follow_view((-1.0, 1.0), 1.0));
+        assert!(tab.follow_view((-1.0, 1.0), 1.1));
+        assert!(!tab.follow_view((-1.0, 1.0), 1.0 + VIEW_DEBOUNCE));
+        let points = &tab.functions[0].plot_points;
+        assert_eq!(points.first().unwrap()[0], -1.0);
+        assert_eq!(points.last().unwrap()[0], 1.0);
+        assert!(!tab.follow_view((-1.0, 1.0), 5.0));
+    }
+
+    #[test]
+    fn explicit_grid_steps_from_start_to_stop() {
+        let mut tab = PlotTab::new(0);
+        tab.functions = vec![FunctionPlot::new("x", egui::Color32::WHITE)];
+        tab.explicit_grid = true;
+        tab.grid_start_input = "1".to_owned();
+        tab.grid_step_input = "-0.25".to_owned();
+        tab.grid_stop_input = "0".to_owned();
+        tab.resample();
+        let xs: Vec<f64> = tab.functions[0].plot_points.iter().map(|p| p[0]).collect();
+        // Sampled downwards from 1, but stored in ascending order.
+        assert_eq!(xs, vec![0.0, 0.25, 0.5, 0.75, 1.0]);
+
+        tab.grid_step_input = "0.25".to_owned();
+        tab.resample();
+        assert_eq!(
+            tab.domain_error.as_deref(),
+            Some("Grid step must be negative to go from 1 to 0")
+        );
+        tab.grid_step_input = "-1e-9".to_owned();
+        assert!(tab.parse_explicit_grid().is_err());
+
+        // Curves clipped by the y limit still break at the gaps.
+        tab.functions = vec![FunctionPlot::new("x^2 - 2", egui::Color32::WHITE)];
+        tab.grid_start_input = "3".to_owned();
+        tab.grid_step_input = "-0.5".to_owned();
+        tab.grid_stop_input = "-3".to_owned();
+        tab.limit_y = true;
+        tab.y_limit = 1.0;
+        tab.resample();
+        let func = &tab.functions[0];
+        let segments = split_at_breaks(&func.plot_points, &func.breaks);
+        assert_eq!(
+            segments,
+            vec![
+                &[[-1.5, 0.25], [-1.0, -1.0]][..],
+                &[[1.0, -1.0], [1.5, 0.25]][..]
+            ]
+        );
+    }
+
+    #[test]
+    fn colors_are_nudged_to_contrast_with_the_theme() {
+        let dark = egui::Visuals::dark().extreme_bg_color;
+        let navy = egui::Color32::from_rgb(20, 30, 120);
+        assert!(contrast_ratio(navy, dark) < MIN_CONTRAST);
+        let adjusted = ensure_contrast(navy, dark);
+        assert!(contrast_ratio(adjusted, dark) >= MIN_CONTRAST);
+        assert!(relative_luminance(adjusted) > relative_luminance(navy));
+        // Colors that already stand out are left alone.
+        assert_eq!(ensure_contrast(navy, egui::Color32::WHITE), navy);
+
+        let mut app = App::default();
+        app.tabs[0].set_palette(vec![navy, navy]);
+        app.tabs[0].functions[1].color = egui::Color32::BLACK;
+        app.tabs[0].functions[1].color_locked = true;
+        app.fit_colors_to_background(dark);
+        assert_eq!(app.tabs[0].functions[0].color, adjusted);
+        assert_eq!(app.tabs[0].functions[1].color, egui::Color32::BLACK);
+        // Going back to a light theme restores the palette color.
+        app.fit_colors_to_background(egui::Color32::WHITE);
+        assert_eq!(app.tabs[0].functions[0].color, navy);
+    }
+
+    #[test]
+    fn prev_refers_to_the_function_above() {
+        let mut tab = PlotTab::new(0);
+        tab.functions = vec![
+            FunctionPlot::new("x^2", egui::Color32::WHITE),
+            FunctionPlot::new("prev + 1", egui::Color32::WHITE),
+            FunctionPlot::new("2*prev", egui::Color32::WHITE),
+        ];
+        tab.x_min_input = "3".to_owned();
+        tab.x_max_input = "3".to_owned();
+        tab.resample();
+        assert_eq!(tab.functions[1].plot_points, vec![[3.0, 10.0]]);
+        assert_eq!(tab.functions[2].plot_points, vec![[3.0, 20.0]]);
+
+        tab.functions[0].expression = "prev".to_owned();
+        tab.resample();
+        assert_eq!(
+            tab.functions[0].error_message.as_deref(),
+            Some("Reference error: 'prev' used in f1, which has no previous function")
+        );
+    }
+
+    #[test]
+    fn references_are_reported_in_rpn_mode() {
+        let mut tab = PlotTab::new(0);
+        tab.parse_options.rpn = true;
+        tab.functions = vec![
+            FunctionPlot::new("x 2 ^", egui::Color32::WHITE),
+            FunctionPlot::new("prev 1 +", egui::Color32::WHITE),
+        ];
+        tab.resample();
+        assert_eq!(tab.functions[0].error_message, None);
+        assert_eq!(
+            tab.functions[1].error_message.as_deref(),
+            Some("Reference error: references aren't supported in RPN input")
+        );
+        assert!(tab.functions[1].plot_points.is_empty());
+    }
+
+    #[test]
+    fn fourier_coefficients_of_known_series() {
+        use std::f64::consts::PI;
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-6;
+        // 3 + cos(x) is its own series.
+        let c = fourier_coefficients(|x| 3.0 + x.cos(), 0.0, 2.0 * PI, 3);
+        assert!(close(c[0].0, 6.0) && close(c[1].0, 1.0) && close(c[2].0, 0.0));
+        assert!(close(
+            fourier_partial_sum(&c, 0.0, 2.0 * PI, 1.0),
+            3.0 + 1f64.cos()
+        ));
+        // x on [-π, π] has the series 2 (sin x - sin 2x / 2 + sin 3x / 3 - …).
+        let c = fourier_coefficients(|x| x, -PI, PI, 3);
+        let expected = 2.0 * (1f64.sin() - 2f64.sin() / 2.0 + 3f64.sin() / 3.0);
+        assert!((fourier_partial_sum(&c, -PI, PI, 1.0) - expected).abs() < 1e-3);
+    }
+
+    #[test]
+    fn plot_modes_keep_their_own_inputs() {
+        let mut func = FunctionPlot::new("x^2", egui::Color32::WHITE);
+        func.mode = PlotMode::Polar;
+        func.polar.r = "2".to_owned();
+        func.update(
+            -1.0,
+            1.0,
+            4,
+            Sampling::Linear,
+            &ParseOptions::default(),
+            &[],
+        );
+        assert_eq!(func.plot_points.len(), 5);
+        assert!(func
+            .plot_points
+            .iter()
+            .all(|&[x, y]| (x.hypot(y) - 2.0).abs() < 1e-12));
+
+        func.mode = PlotMode::Parametric;
+        func.parametric.x = "t".to_owned();
+        func.parametric.y = "t^2".to_owned();
+        func.parametric.t_max = "1".to_owned();
+        func.update(
+            -1.0,
+            1.0,
+            4,
+            Sampling::Linear,
+            &ParseOptions::default(),
+            &[],
+        );
+        assert_eq!(func.plot_points.last(), Some(&[1.0, 1.0]));
+
+        // Back in Cartesian mode the expression is untouched.
+        func.mode = PlotMode::Cartesian;
+        assert_eq!(func.expression, "x^2");
+        assert_eq!(func.polar.r, "2");
+    }
+
+    #[test]
+    fn arrow_keys_step_by_the_last_decimal() {
+        assert_eq!(step_number("10", true, false).as_deref(), Some("11"));
+        assert_eq!(step_number("-10.0", true, true).as_deref(), Some("-9.0"));
+        assert_eq!(step_number("0.25", false, false).as_deref(), Some("0.24"));
+        assert_eq!(step_number(" 0.05 ", false, true).as_deref(), Some("-0.05"));
+        assert_eq!(step_number("1e3", true, false), None);
+        assert_eq!(step_number("abc", true, false), None);
+    }
+
+    #[test]
+    fn constants_are_fitted_to_data() {
+        let mut tab = PlotTab::new(0);
+        tab.functions = vec![FunctionPlot::new("a*exp(b*x)", egui::Color32::WHITE)];
+        tab.constants = vec![Constant::new("a"), Constant::new("b")];
+        tab.constants[0].value = 1.0;
+        tab.constants[1].value = 0.1;
+        let points = (0..20)
+            .map(|i| {
+                let x = i as f64 * 0.1;
+                [x, 3.0 * (-0.7 * x).exp()]
+            })
+            .collect();
+        tab.data_series.push(DataSeries {
+            name: "decay".to_owned(),
+            points,
+            color: egui::Color32::WHITE,
+            smooth: false,
+            window: 5,
+        });
+        tab.fit_to_data();
+        assert!(matches!(tab.fit_result, Some(Ok(_))));
+        assert!((tab.constants[0].value - 3.0).abs() < 1e-6);
+        assert!((tab.constants[1].value + 0.7).abs() < 1e-6);
+        assert!(tab.constants[1].min <= -0.7);
+
+        tab.functions[0].expression = "2*x".to_owned();
+        tab.fit_to_data();
+        assert_eq!(
+            tab.fit_result,
+            Some(Err("f1 uses no constants to fit".to_owned()))
+        );
+    }
+
+    #[test]
+    fn residuals_are_data_minus_function() {
+        let mut tab = PlotTab::new(0);
+        tab.functions = vec![FunctionPlot::new("2*x", egui::Color32::WHITE)];
+        tab.data_series.push(DataSeries {
+            name: "data".to_owned(),
+            points: vec![[0.0, 0.5], [1.0, 2.0], [2.0, 3.0]],
+            color: egui::Color32::WHITE,
+            smooth: false,
+            window: 5,
+        });
+        tab.show_residuals = true;
+        tab.update_residuals();
+        assert_eq!(tab.residuals, Ok(vec![[0.0, 0.5], [1.0, 0.0], [2.0, -1.0]]));
+    }
+
+    #[test]
+    fn labels_are_nudged_clear_of_each_other() {
+        let label =
+            |x: f32, y: f32| egui::Rect::from_min_size(egui::pos2(x, y), egui::vec2(40.0, 10.0));
+        let mut placed = vec![label(0.0, 0.0)];
+        assert_eq!(
+            free_label_spot(&placed, label(100.0, 0.0)),
+            Some(label(100.0, 0.0))
+        );
+        // An overlapping label moves up first; touching edges are fine.
+        assert_eq!(
+            free_label_spot(&placed, label(10.0, -5.0)),
+            Some(label(10.0, -15.0))
+        );
+        placed.push(label(10.0, -15.0));
+        assert_eq!(
+            free_label_spot(&placed, label(10.0, -5.0)),
+            Some(label(10.0, -25.0))
+        );
+        // With no room within reach, the label is left out.
+        placed.extend((-4..=4).map(|k| label(0.0, k as f32 * 10.0)));
+        assert_eq!(free_label_spot(&placed, label(10.0, -5.0)), None);
+    }
+
+    #[test]
+    fn polar_grid_circles_cross_the_view() {
+        assert_eq!(polar_grid_radii([-1.0, -1.0], [1.0, 1.0]), vec![0.5, 1.0]);
+        assert_eq!(
+            polar_grid_radii([2.0, 3.0], [4.0, 5.0]),
+            vec![4.0, 5.0, 6.0]
+        );
+        assert!(polar_grid_radii([0.0, 0.0], [0.0, 0.0]).is_empty());
+    }
+
+    #[test]
+    fn coordinates_use_the_view_precision() {
+        assert_eq!(format_coordinates([1.23456, -0.5], 20.0, 2.0), "1.2, -0.50");
+        assert_eq!(
+            format_coordinates([1234.4, 2.0], 5000.0, 0.02),
+            "1234, 2.0000"
+        );
+    }
+
+    #[test]
+    fn slices_bind_each_value() {
+        let mut func = FunctionPlot::new("a*x", function_color(&PALETTE, 0));
+        func.slice_enabled = true;
+        func.slice_values = "1, 2".to_owned();
+        let constants = [Constant::new("a")];
+        func.update(
+            0.0,
+            1.0,
+            1,
+            Sampling::Linear,
+            &ParseOptions::default(),
+            &constants,
+        );
+        assert!(func.error_message.is_none());
+        assert!(func.plot_points.is_empty());
+        assert_eq!(
+            func.slices,
+            vec![
+                (1.0, vec![[0.0, 0.0], [1.0, 1.0]]),
+                (2.0, vec![[0.0, 0.0], [1.0, 2.0]]),
+            ]
+        );
+        assert_eq!(func.y_range, Some((0.0, 2.0)));
+
+        func.slice_values = "1, b".to_owned();
+        func.update(
+            0.0,
+            1.0,
+            1,
+            Sampling::Linear,
+            &ParseOptions::default(),
+            &constants,
+        );
+        assert!(func.error_message.is_some());
+    }
+
+    #[test]
+    fn small_multiples_sample_one_curve_per_value() {
+        assert_eq!(
+            parse_sweep_values("1, 2:0.5:3"),
+            Ok(vec![1.0, 2.0, 2.5, 3.0])
+        );
+        assert_eq!(parse_sweep_values("0:0.1:0.3").unwrap().len(), 4);
+        assert!(parse_sweep_values("1:-1:3").is_err());
+        assert_eq!(
+            parse_sweep_values("0:1:100"),
+            Err(format!("At most {} values", MAX_SMALL_MULTIPLES))
+        );
+        assert!(parse_sweep_values("").is_err());
+        assert_eq!(
+            parse_sweep_values("0:5e-324:1"),
+            Err(format!("At most {} values", MAX_SMALL_MULTIPLES))
+        );
+
+        let mut tab = PlotTab::new(0);
+        tab.functions[0].expression = "a*x".to_owned();
+        tab.small_multiples.enabled = true;
+        tab.small_multiples.values = "1, 2".to_owned();
+        tab.resample();
+        let cells = tab.small_multiple_cells.clone().unwrap().unwrap();
+        assert_eq!(cells.len(), 2);
+        let (value, points) = &cells[1];
+        assert_eq!(*value, 2.0);
+        let &[x, y] = points.last().unwrap();
+        assert_eq!(y, 2.0 * x);
+
+        // Removing a function above keeps the same curve; removing it turns them off.
+        tab.add_function();
+        tab.small_multiples.function = 1;
+        tab.remove_function(0);
+        assert_eq!(tab.small_multiples.function, 0);
+        assert!(tab.small_multiples.enabled);
+        tab.remove_function(0);
+        assert!(!tab.small_multiples.enabled);
+        tab.resample();
+        assert!(tab.small_multiple_cells.is_none());
+    }
+
+    #[test]
+    fn complex_mode_plots_the_chosen_part() {
+        let mut func = FunctionPlot::new("sqrt(x)", function_color(&PALETTE, 0));
+        let options = ParseOptions::default();
+        func.update(-4.0, 4.0, 8, Sampling::Linear, &options, &[]);
+        assert_eq!(func.plot_points.len(), 5);
+
+        func.complex_mode = true;
+        func.update(-4.0, 4.0, 8, Sampling::Linear, &options, &[]);
+        assert_eq!(func.plot_points.len(), 9);
+        assert_eq!(func.plot_points[0], [-4.0, 2.0]);
+        assert_eq!(func.formula(), "|sqrt(x)|");
+
+        func.complex_part = ComplexPart::Argument;
+        func.update(-4.0, 4.0, 8, Sampling::Linear, &options, &[]);
+        assert!((func.plot_points[0][1] - std::f64::consts::FRAC_PI_2).abs() < 1e-12);
+        assert_eq!(func.plot_points[8], [4.0, 0.0]);
+
+        func.expression = "floor(x)".to_owned();
+        func.update(-4.0, 4.0, 8, Sampling::Linear, &options, &[]);
+        assert_eq!(
+            func.error_message.as_deref(),
+            Some("Binding error: 'floor' has no complex version")
+        );
+    }
+
+    #[test]
+    fn rigorous_mode_finds_spikes_between_samples() {
+        let mut func = FunctionPlot::new("1/(1 + 1e8*(x - 0.05)^2)", function_color(&PALETTE, 0));
+        func.rigorous = true;
+        let options = ParseOptions::default();
+        func.update(0.0, 1.0, 10, Sampling::Linear, &options, &[]);
+        // Every sample misses the spike at 0.05, but its interval doesn't.
+        assert!(func.plot_points.iter().all(|p| p[1] < 0.01));
+        assert!(func
+            .extents
+            .iter()
+            .any(|&[x, _, high]| x == 0.05 && high >= 1.0));
+        assert_eq!(func.rigorous_note, None);
+
+        func.expression = "atan2(x, 1)".to_owned();
+        func.update(0.0, 1.0, 10, Sampling::Linear, &options, &[]);
+        assert!(func.extents.is_empty());
+        assert!(func.rigorous_note.is_some());
+        assert_eq!(func.plot_points.len(), 11);
+    }
+
+    #[test]
+    fn symmetry_is_classified() {
+        let symmetry = |f: &dyn Fn(f64) -> f64, x_min: f64| {
+            let points = sample_function(f, x_min, 3.0, 100, Sampling::Linear);
+            classify_symmetry(f, &points)
+        };
+        assert_eq!(symmetry(&|x| x.cos(), -3.0), Some(Symmetry::Even));
+        assert_eq!(symmetry(&|x| x * x * x, -3.0), Some(Symmetry::Odd));
+        assert_eq!(symmetry(&|x| x.exp(), -3.0), Some(Symmetry::Neither));
+        assert_eq!(symmetry(&|_| 0.0, -3.0), Some(Symmetry::Both));
+        // f(-x) is undefined for every sample of ln(x) on x > 0.
+        assert_eq!(symmetry(&|x| x.ln(), 0.5), None);
+    }
+
+    #[test]
+    fn taylor_coefficients_of_exp() {
+        let coefficients = taylor_coefficients(&f64::exp, 0.0, 4);
+        for (a, expected) in coefficients
+            .iter()
+            .zip([1.0, 1.0, 0.5, 1.0 / 6.0, 1.0 / 24.0])
+        {
+            assert!((a - expected).abs() < 1e-3, "{} vs {}", a, expected);
+        }
+        // Around c = 1, the cubic x^3 is reproduced exactly (up to rounding).
+        let cubic = taylor_coefficients(&|x: f64| x * x * x, 1.0, 3);
+        assert!((evaluate_taylor(&cubic, 1.0, 3.0) - 27.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn only_parametric_and_polar_curves_close() {
+        let mut func = FunctionPlot::new("x", function_color(&PALETTE, 0));
+        func.mode = PlotMode::Polar;
+        func.polar.r = "1".to_owned();
+        func.close_curve = true;
+        let options = ParseOptions::default();
+        func.update(-1.0, 1.0, 10, Sampling::Linear, &options, &[]);
+        assert_eq!(func.closing_point(), func.plot_points.first().copied());
+        assert!(func.closing_point().is_some());
+
+        func.mode = PlotMode::Cartesian;
+        func.update(-1.0, 1.0, 10, Sampling::Linear, &options, &[]);
+        assert_eq!(func.closing_point(), None);
+    }
+
+    #[test]
+    fn calculator_evaluates_constant_expressions() {
+        let options = ParseOptions::default();
+        let value = evaluate_constant("sin(pi/4) * sqrt(2)", &options, &[]).unwrap();
+        assert!((value - 1.0).abs() < 1e-12);
+        let a = Constant {
+            value: 3.0,
+            ..Constant::new("a")
+        };
+        assert_eq!(evaluate_constant("2*a", &options, &[a]).unwrap(), 6.0);
+        assert!(evaluate_constant("x + 1", &options, &[]).is_err());
+        assert!(evaluate_constant("sin(", &options, &[]).is_err());
+
+        // With the variable detected from each expression, unknown names
+        // must not bind as the variable either.
+        let detected = ParseOptions {
+            variable: String::new(),
+            ..ParseOptions::default()
+        };
+        assert_eq!(
+            evaluate_constant("foo + 1", &detected, &[]),
+            Err(PlotError::Bind("unknown variable 'foo'".to_owned()))
+        );
+        assert_eq!(evaluate_constant("e^0 + 1", &detected, &[]), Ok(2.0));
+    }
+
+    #[test]
+    fn periods_are_detected_by_autocorrelation() {
+        let sample = |f: fn(f64) -> f64| -> Vec<[f64; 2]> {
+            (0..=1000)
+                .map(|i| {
+                    let x = i as f64 * 0.02;
+                    [x, f(x)]
+                })
+                .collect()
+        };
+        let period = detect_period(&sample(f64::sin)).unwrap();
+        assert!((period - std::f64::consts::TAU).abs() < 1e-2, "{}", period);
+        let period = detect_period(&sample(|x| (3.0 * x).cos() + 0.5)).unwrap();
+        assert!(
+            (period - std::f64::consts::TAU / 3.0).abs() < 1e-2,
+            "{}",
+            period
+        );
+        assert_eq!(detect_period(&sample(|x| x * x)), None);
+        assert_eq!(detect_period(&sample(|_| 1.0)), None);
+    }
+
+    #[test]
+    fn parses_are_reused_until_the_text_or_options_change() {
+        let mut tab = PlotTab::new(0);
+        tab.functions = vec![FunctionPlot::new("0xff + x", egui::Color32::WHITE)];
+        tab.resample();
+        assert!(tab.functions[0].error_message.is_some());
+
+        tab.parse_options.radix_literals = true;
+        tab.needs_reparse = true;
+        tab.resample();
+        assert!(tab.functions[0].error_message.is_none());
+        assert!(!tab.needs_reparse);
+
+        tab.functions[0].expression = "0xff + 2*x".to_owned();
+        tab.resample();
+        let (text, parsed) = tab.functions[0].parsed.as_ref().unwrap();
+        assert_eq!(text, "0xff + 2*x");
+        assert!(parsed.is_ok());
+    }
+
+    #[test]
+    fn nearest_root_skips_poles() {
+        let sample = |f: &dyn Fn(f64) -> f64| sample_function(f, -3.0, 3.0, 30, Sampling::Linear);
+        let parabola = |x: f64| x * x - 2.0;
+        let points = sample(&parabola);
+        let root = nearest_root(&parabola, &points, 1.0).unwrap();
+        assert!((root - 2f64.sqrt()).abs() < 1e-12);
+        let root = nearest_root(&parabola, &points, -0.5).unwrap();
+        assert!((root + 2f64.sqrt()).abs() < 1e-12);
+
+        let reciprocal = |x: f64| 1.0 / (x - 0.07);
+        assert_eq!(nearest_root(&reciprocal, &sample(&reciprocal), 0.0), None);
+    }
+
+    #[test]
+    fn rpn_expressions_plot_like_infix() {
+        let options = ParseOptions {
+            rpn: true,
+            ..ParseOptions::default()
+        };
+        let f = compile_expression("x 2 ^ 1 x atan2 +", &options, &[]).unwrap();
+        assert_eq!(f(2.0), 4.0 + 1f64.atan2(2.0));
+        assert!(compile_expression("x +", &options, &[]).is_err());
+    }
+
+    #[test]
+    fn all_non_finite_samples_are_reported() {
+        let mut func = FunctionPlot::new("ln(x)", function_color(&PALETTE, 0));
+        let options = ParseOptions::default();
+        func.update(-3.0, -1.0, 10, Sampling::Linear, &options, &[]);
+        assert!(func.plot_points.is_empty());
+        assert_eq!(func.error_message.as_deref(), Some(NO_FINITE_VALUES));
+
+        // Moving back into the domain clears the message.
+        func.update(1.0, 3.0, 10, Sampling::Linear, &options, &[]);
+        assert!(func.error_message.is_none());
+        assert_eq!(func.plot_points.len(), 11);
+
+        // The same goes for curves in the other modes.
+        func.mode = PlotMode::Polar;
+        func.polar.r = "ln(-1 - theta)".to_owned();
+        func.update(1.0, 3.0, 10, Sampling::Linear, &options, &[]);
+        assert!(func.plot_points.is_empty());
+        assert_eq!(func.error_message.as_deref(), Some(NO_FINITE_VALUES));
+    }
+
+    #[test]
+    fn inequality_intervals_end_at_crossings() {
+        let xs = x_grid(-2.0, 2.0, 401);
+        let intervals = positive_intervals(&xs, &|x| x * x - 1.0);
+        assert_eq!(intervals.len(), 2);
+        let expected = [(-2.0, -1.0), (1.0, 2.0)];
+        for ((a, b), (ea, eb)) in intervals.into_iter().zip(expected) {
+            assert!(
+                (a - ea).abs() < 1e-3 && (b - eb).abs() < 1e-3,
+                "{} {}",
+                a,
+                b
+            );
+        }
+        // An undefined stretch splits the interval at the last defined samples.
+        let split = positive_intervals(&[0.0, 1.0, 2.0, 3.0], &|x| {
+            if x == 2.0 {
+                f64::NAN
+            } else {
+                1.0
+            }
+        });
+        assert_eq!(split, vec![(0.0, 1.0), (3.0, 3.0)]);
+        assert!(positive_intervals(&xs, &|_| -1.0).is_empty());
+    }
+
+    #[test]
+    fn x_grid_hits_both_endpoints_exactly() {
+        for (x_min, x_max) in [(-0.1, 0.7), (0.1, 1e12), (-1e-9, 3.3), (2.0, -7.3)] {
+            let grid = x_grid(x_min, x_max, 997);
+            assert_eq!(grid.len(), 998);
+            assert_eq!(grid[0], x_min);
+            assert_eq!(grid[997], x_max);
+        }
+        let points = sample_function(&|x| x, -0.1, 0.7, 1000, Sampling::Linear);
+        assert_eq!(points.last().unwrap()[0], 0.7);
+    }
+
+    #[test]
+    fn zero_num_points_does_not_panic() {
+        let mut func = FunctionPlot::new("x", function_color(&PALETTE, 0));
+        func.update(
+            -1.0,
+            1.0,
+            0,
+            Sampling::Linear,
+            &ParseOptions::default(),
+            &[],
+        );
+        assert!(func.error_message.is_none());
+        assert_eq!(func.plot_points, vec![[-1.0, -1.0], [1.0, 1.0]]);
+    }
+
+    #[test]
+    fn quality_presets_set_sampling() {
+        let mut tab = PlotTab::new(0);
+        tab.functions[1].interpolation = Interpolation::StepHorizontal;
+        tab.apply_quality(&QUALITY_PRESETS[0]);
+        assert_eq!(tab.num_points, MIN_POINTS);
+        assert!(tab.functions.iter().all(|f| f.decimate));
+        tab.apply_quality(&QUALITY_PRESETS[QUALITY_PRESETS.len() - 1]);
+        assert_eq!(tab.num_points, MAX_POINTS);
+        assert!(tab.follow_view);
+        assert!(tab.functions.iter().all(|f| !f.decimate));
+        // Smoothing follows the preset, but step styles are kept.
+        assert!(tab.functions[0].interpolation == Interpolation::Smooth);
+        assert!(tab.functions[1].interpolation == Interpolation::StepHorizontal);
+        assert_eq!(
+            QUALITY_PRESETS[DEFAULT_QUALITY].num_points,
+            PlotTab::new(0).num_points
+        );
+    }
+
+    #[test]
+    fn scrolling_scales_num_points() {
+        assert_eq!(scrolled_num_points(1000, SCROLL_PER_DOUBLING), 2000);
+        assert_eq!(scrolled_num_points(1000, -SCROLL_PER_DOUBLING), 500);
+        assert_eq!(scrolled_num_points(MAX_POINTS, 50.0), MAX_POINTS);
+        assert_eq!(scrolled_num_points(MIN_POINTS, -50.0), MIN_POINTS);
+    }
+
+    #[test]
+    fn empty_plot_explains_itself() {
+        let mut tab = PlotTab::new(0);
+        tab.resample();
+        assert_eq!(tab.empty_plot_message(), None);
+        for func in &mut tab.functions {
+            func.expression = "sin(".to_owned();
+        }
+        tab.resample();
+        assert_eq!(
+            tab.empty_plot_message().as_deref(),
+            Some("No valid functions to plot — check the expressions above")
+        );
+        tab.functions.clear();
+        tab.x_min_input = "abc".to_owned();
+        tab.resample();
+        assert_eq!(
+            tab.empty_plot_message().as_deref(),
+            Some("Nothing to plot: Invalid x_min value")
+        );
+    }
+
+    #[test]
+    fn groups_order_hide_and_tint_their_members() {
+        let mut tab = PlotTab::new(0);
+        tab.functions
+            .push(FunctionPlot::new("x^2", egui::Color32::BLACK));
+        tab.functions[0].group = "Logs".to_owned();
+        tab.functions[2].group = " Logs ".to_owned();
+        tab.sync_groups();
+        assert_eq!(tab.groups.len(), 1);
+        assert_eq!(tab.grouped_order(), vec![1, 0, 2]);
+
+        tab.groups[0].visible = false;
+        tab.groups[0].tint = Some(egui::Color32::WHITE);
+        assert!(!tab.is_shown(&tab.functions[2]));
+        assert!(tab.is_shown(&tab.functions[1]));
+        assert_eq!(
+            tab.display_color(&tab.functions[2]),
+            egui::Color32::from_rgb(128, 128, 128)
+        );
+        // Hidden functions don't take part in scaling the view.
+        tab.resample();
+        let (lo, hi) = tab.data_y_range().unwrap();
+        assert!(lo >= -1.0 && hi <= 1.0);
+        tab.functions[1].group = "Logs".to_owned();
+        assert_eq!(tab.data_y_range(), None);
+        assert_eq!(
+            tab.empty_plot_message().as_deref(),
+            Some("Every plotted function is in a hidden group")
+        );
+        tab.functions[1].group.clear();
+
+        tab.functions[0].group.clear();
+        tab.functions[2].group.clear();
+        tab.sync_groups();
+        assert!(tab.groups.is_empty());
+    }
+
+    #[test]
+    fn renaming_a_group_onto_another_merges_them() {
+        let mut tab = PlotTab::new(0);
+        tab.functions[0].group = "Logs".to_owned();
+        tab.functions[1].group = "Trig".to_owned();
+        tab.sync_groups();
+        tab.groups[0].tint = Some(egui::Color32::WHITE);
+
+        tab.rename_group("Logs", "  ");
+        assert_eq!(tab.functions[0].group, "Logs");
+        tab.rename_group("Logs", " Curves ");
+        assert_eq!(tab.functions[0].group, "Curves");
+        assert_eq!(tab.groups[0].name, "Curves");
+        assert_eq!(tab.groups[0].tint, Some(egui::Color32::WHITE));
+
+        tab.rename_group("Trig", "Curves");
+        tab.sync_groups();
+        assert_eq!(tab.groups.len(), 1);
+        assert_eq!(tab.groups[0].name, "Curves");
+        assert!(tab.functions.iter().all(|f| f.group == "Curves"));
+    }
+
+    #[test]
+    fn num_points_is_clamped_on_update() {
+        let mut tab = PlotTab {
+            num_points: 0,
+            ..PlotTab::new(0)
+        };
+        tab.resample();
+        assert_eq!(tab.num_points, MIN_POINTS);
+
+        tab.num_points = usize::MAX;
+        tab.resample();
+        assert_eq!(tab.num_points, MAX_POINTS);
+    }
+
+    #[test]
+    fn unchanged_frames_do_not_resample() {
+        let mut tab = PlotTab::new(0);
+        tab.update_functions();
+        let recomputes = tab.recomputes;
+        assert_eq!(recomputes, 1);
+        for _ in 0..10 {
+            tab.update_functions();
+        }
+        assert_eq!(tab.recomputes, recomputes);
+
+        tab.functions[1].expression = "cos(x)".to_owned();
+        tab.needs_resample = true;
+        tab.update_functions();
+        assert_eq!(tab.recomputes, recomputes + 1);
+        tab.needs_reparse = true;
+        tab.update_functions();
+        tab.update_functions();
+        assert_eq!(tab.recomputes, recomputes + 2);
+    }
+
+    #[test]
+    fn frame_stats_measure_fps_and_recomputes() {
+        let mut stats = FrameStats::default();
+        assert_eq!(stats.fps(), None);
+        let mut tab = PlotTab::new(0);
+        for frame in 0..=30 {
+            if frame % 3 == 0 {
+                tab.resample();
+            }
+            stats.record(frame as f64 / 60.0, Some(0.004), tab.recomputes);
+        }
+        assert!((stats.fps().unwrap() - 60.0).abs() < 1e-9);
+        assert!(stats.recomputed());
+        assert_eq!(stats.recomputing_frames(), 11);
+        // A pause drops the frames before it from the average.
+        stats.record(5.0, None, tab.recomputes);
+        stats.record(5.1, None, tab.recomputes);
+        assert!((stats.fps().unwrap() - 10.0).abs() < 1e-9);
+        assert!(!stats.recomputed());
+    }
+
+    #[test]
+    fn differences_match_points_across_gaps() {
+        let mut tab = PlotTab::new(0);
+        tab.functions = vec![
+            FunctionPlot::new("ln(x)", egui::Color32::WHITE),
+            FunctionPlot::new("sin(x)", egui::Color32::WHITE),
+        ];
+        tab.differences.push((0, 1, egui::Color32::WHITE));
+        tab.resample();
+        // ln(x) has no samples for x <= 0, so the curves only share x > 0.
+        let shared = tab.functions[0].plot_points.len();
+        assert!(shared > 0 && shared < tab.functions[1].plot_points.len());
+        let difference = &tab.difference_points[0];
+        assert_eq!(difference.len(), shared);
+        let [x, y] = difference[shared / 2];
+        assert!((y - (x.ln() - x.sin())).abs() < 1e-12);
+    }
 }
@@ -0,0 +1,356 @@
+//! Text-level rewrites applied to expressions before they are handed to meval.
+
+/// Replaces hexadecimal (`0xff`) and binary (`0b1010`) literals with their
+/// decimal value, since meval only understands decimal numbers.
+///
+/// Decimal and scientific literals (`1.5`, `2e10`) are left untouched.
+pub fn expand_radix_literals(expression: &str) -> Result<String, String> {
+    let chars: Vec<char> = expression.chars().collect();
+    let mut out = String::with_capacity(expression.len());
+    let mut i = 0;
+    while i < chars.len() {
+        // A literal only starts where a new token starts, so `x0b1` or `1.0x` are not touched.
+        let starts_token = i == 0
+            || !(chars[i - 1].is_alphanumeric() || chars[i - 1] == '_' || chars[i - 1] == '.');
+        let radix = match chars.get(i + 1) {
+            Some('x' | 'X') => 16,
+            Some('b' | 'B') => 2,
+            _ => 0,
+        };
+        if chars[i] != '0' || !starts_token || radix == 0 {
+            out.push(chars[i]);
+            i += 1;
+            continue;
+        }
+
+        let start = i + 2;
+        let mut end = start;
+        while end < chars.len() && chars[end].is_alphanumeric() {
+            end += 1;
+        }
+        let prefix: String = chars[i..start].iter().collect();
+        let digits: String = chars[start..end].iter().collect();
+        if digits.is_empty() {
+            return Err(format!("literal '{}' has no digits", prefix));
+        }
+        let value = u64::from_str_radix(&digits, radix)
+            .map_err(|_| format!("invalid literal '{}{}'", prefix, digits))?;
+        out.push_str(&value.to_string());
+        i = end;
+    }
+    Ok(out)
+}
+
+/// Returns true for characters that may appear inside an identifier.
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Splits `text` into identifier and non-identifier runs, calling `f` on every
+/// identifier and copying everything else through unchanged.
+///
+/// Numeric literals such as `2e10` are not treated as identifiers.
+fn map_identifiers(text: &str, mut f: impl FnMut(&str) -> String) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        if !is_ident_char(c) {
+            out.push(c);
+            continue;
+        }
+        let mut end = start + c.len_utf8();
+        while let Some(&(i, next)) = chars.peek() {
+            if !(is_ident_char(next) || (c.is_ascii_digit() && next == '.')) {
+                break;
+            }
+            end = i + next.len_utf8();
+            chars.next();
+        }
+        let token = &text[start..end];
+        if c.is_ascii_digit() {
+            out.push_str(token);
+        } else {
+            out.push_str(&f(token));
+        }
+    }
+    out
+}
+
+/// Returns every identifier (variable or function name) used in `text`, in order.
+pub fn identifiers(text: &str) -> Vec<String> {
+    let mut found = Vec::new();
+    map_identifiers(text, |ident| {
+        found.push(ident.to_owned());
+        ident.to_owned()
+    });
+    found
+}
+
+/// Replaces every whole-word occurrence of `name` in `text` with `replacement`.
+pub fn replace_identifier(text: &str, name: &str, replacement: &str) -> String {
+    map_identifiers(text, |ident| {
+        if ident == name {
+            replacement.to_owned()
+        } else {
+            ident.to_owned()
+        }
+    })
+}
+
+/// Replaces every call `name(argument)` for which `is_callee(name)` holds with
+/// `expand(name, argument)`.
+///
+/// Arguments are expanded before the call around them, so nested calls work.
+pub fn expand_calls(
+    text: &str,
+    is_callee: &dyn Fn(&str) -> bool,
+    expand: &mut dyn FnMut(&str, &str) -> Result<String, String>,
+) -> Result<String, String> {
+    let mut out = String::with_capacity(text.len());
+    let mut i = 0;
+    while let Some(c) = text[i..].chars().next() {
+        if !is_ident_char(c) {
+            out.push(c);
+            i += c.len_utf8();
+            continue;
+        }
+        let start = i;
+        let mut end = text[i..]
+            .find(|c: char| !is_ident_char(c))
+            .map_or(text.len(), |n| i + n);
+        let name = &text[start..end];
+        let open = end + (text[end..].len() - text[end..].trim_start().len());
+        if c.is_ascii_digit() || !is_callee(name) || !text[open..].starts_with('(') {
+            out.push_str(name);
+            i = end;
+            continue;
+        }
+        // Find the parenthesis closing this call.
+        let mut depth = 0usize;
+        end = text[open..]
+            .char_indices()
+            .find_map(|(j, c)| {
+                match c {
+                    '(' => depth += 1,
+                    ')' => depth -= 1,
+                    _ => {}
+                }
+                (depth == 0).then_some(open + j)
+            })
+            .ok_or_else(|| format!("missing ')' after '{}('", name))?;
+        let argument = expand_calls(&text[open + 1..end], is_callee, expand)?;
+        out.push_str(&expand(name, argument.trim())?);
+        i = end + 1;
+    }
+    Ok(out)
+}
+
+/// Expands `let name = expr;` prefixes by substituting each binding into the
+/// bindings after it and into the final expression.
+///
+/// For example `let g = sin(x); g^2 + g` becomes `(sin(x))^2 + (sin(x))`.
+pub fn expand_let_bindings(expression: &str) -> Result<String, String> {
+    let mut parts: Vec<&str> = expression.split(';').collect();
+    let body = parts.pop().unwrap_or_default();
+    if parts.is_empty() {
+        return Ok(expression.to_owned());
+    }
+
+    let mut bindings: Vec<(String, String)> = Vec::new();
+    for part in &parts {
+        let binding = part.trim().strip_prefix("let ").ok_or_else(|| {
+            format!(
+                "expected 'let name = expr' before ';', found '{}'",
+                part.trim()
+            )
+        })?;
+        let (name, value) = binding
+            .split_once('=')
+            .ok_or_else(|| format!("missing '=' in 'let {}'", binding.trim()))?;
+        let name = name.trim();
+        let valid_name = name
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_alphabetic() || c == '_')
+            && name.chars().all(is_ident_char);
+        if !valid_name {
+            return Err(format!("invalid binding name '{}'", name));
+        }
+        if bindings.iter().any(|(n, _)| n == name) {
+            return Err(format!("'{}' is bound more than once", name));
+        }
+        bindings.push((name.to_owned(), value.trim().to_owned()));
+    }
+
+    let names: Vec<&str> = bindings.iter().map(|(n, _)| n.as_str()).collect();
+    for (i, (name, value)) in bindings.iter().enumerate() {
+        for ident in identifiers(value) {
+            if ident == *name {
+                return Err(format!("'{}' refers to itself", name));
+            }
+            if names[i + 1..].contains(&ident.as_str()) {
+                return Err(format!("'{}' is used before it is defined", ident));
+            }
+        }
+    }
+
+    let mut result = body.trim().to_owned();
+    if result.is_empty() {
+        return Err("missing expression after let bindings".to_owned());
+    }
+    // Substitute the last binding first so earlier names introduced by it get expanded too.
+    for (name, value) in bindings.iter().rev() {
+        result = replace_identifier(&result, name, &format!("({})", value));
+    }
+    Ok(result)
+}
+
+/// Converts a postfix (RPN) expression such as `x 2 ^ x sin +` to fully
+/// parenthesized infix, here `((x ^ 2) + sin(x))`. `neg` negates the value
+/// on top of the stack.
+///
+/// `arity` gives the number of arguments each function name takes; any other
+/// token is an operand (a number, variable or constant).
+pub fn rpn_to_infix(text: &str, arity: &dyn Fn(&str) -> Option<usize>) -> Result<String, String> {
+    let mut stack: Vec<String> = Vec::new();
+    for token in text.split_whitespace() {
+        let operator = matches!(token, "+" | "-" | "*" | "/" | "^" | "%");
+        let count = match token {
+            _ if operator => 2,
+            "neg" => 1,
+            _ => arity(token).unwrap_or(0),
+        };
+        if stack.len() < count {
+            return Err(format!(
+                "'{}' needs {} operand{}",
+                token,
+                count,
+                if count == 1 { "" } else { "s" }
+            ));
+        }
+        let args = stack.split_off(stack.len() - count);
+        stack.push(match &args[..] {
+            [] => token.to_owned(),
+            [a] if token == "neg" => format!("(-{})", a),
+            [a, b] if operator => format!("({} {} {})", a, token, b),
+            _ => format!("{}({})", token, args.join(", ")),
+        });
+    }
+    match stack.len() {
+        0 => Err("empty expression".to_owned()),
+        1 => Ok(stack.remove(0)),
+        n => Err(format!("{} values left over; an operator is missing", n)),
+    }
+}
+
+/// Number of `)` that must be appended to close every open `(` in `text`.
+pub fn missing_closing_parens(text: &str) -> usize {
+    let mut depth = 0usize;
+    for c in text.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    depth
+}
+
+/// Describes unbalanced parentheses in `text` in plain words, if there are any.
+pub fn paren_problem(text: &str) -> Option<String> {
+    let mut depth = 0usize;
+    for (i, c) in text.chars().enumerate() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => {
+                return Some(format!("unmatched ')' at position {}", i + 1));
+            }
+            ')' => depth -= 1,
+            _ => {}
+        }
+    }
+    match depth {
+        0 => None,
+        1 => Some("missing 1 closing parenthesis".to_owned()),
+        n => Some(format!("missing {} closing parentheses", n)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn calls_are_expanded_innermost_first() {
+        let is_callee = |name: &str| name == "g";
+        let mut expand = |_: &str, arg: &str| Ok(format!("[{}]", arg));
+        assert_eq!(
+            expand_calls("g(x) + sin(g (g(2*x)))", &is_callee, &mut expand).unwrap(),
+            "[x] + sin([[2*x]])"
+        );
+        assert_eq!(
+            expand_calls("g + g2(x)", &is_callee, &mut expand).unwrap(),
+            "g + g2(x)"
+        );
+        assert!(expand_calls("g(x", &is_callee, &mut expand).is_err());
+    }
+
+    #[test]
+    fn radix_literals_are_expanded() {
+        assert_eq!(
+            expand_radix_literals("0xff + 0b1010*x").unwrap(),
+            "255 + 10*x"
+        );
+        assert_eq!(expand_radix_literals("1.0e3 + 0.5").unwrap(), "1.0e3 + 0.5");
+        assert!(expand_radix_literals("0x + 1").is_err());
+        assert!(expand_radix_literals("0b102").is_err());
+    }
+
+    #[test]
+    fn let_bindings_are_substituted() {
+        assert_eq!(
+            expand_let_bindings("let g = sin(x); let h = 2*g; h + g").unwrap(),
+            "(2*(sin(x))) + (sin(x))"
+        );
+        assert_eq!(expand_let_bindings("sin(x)").unwrap(), "sin(x)");
+        assert!(expand_let_bindings("let g = g + 1; g").is_err());
+        assert!(expand_let_bindings("let a = b; let b = x; a").is_err());
+    }
+
+    #[test]
+    fn rpn_is_converted_to_infix() {
+        let arity = |name: &str| match name {
+            "sin" => Some(1),
+            "max" => Some(2),
+            _ => None,
+        };
+        assert_eq!(
+            rpn_to_infix("x 2 ^ x sin +", &arity).unwrap(),
+            "((x ^ 2) + sin(x))"
+        );
+        assert_eq!(
+            rpn_to_infix("1 x neg max -2 *", &arity).unwrap(),
+            "(max(1, (-x)) * -2)"
+        );
+        assert_eq!(
+            rpn_to_infix("x +", &arity).unwrap_err(),
+            "'+' needs 2 operands"
+        );
+        assert!(rpn_to_infix("x 1", &arity).is_err());
+        assert!(rpn_to_infix("  ", &arity).is_err());
+    }
+
+    #[test]
+    fn paren_problems_are_described() {
+        assert_eq!(paren_problem("sin(x)"), None);
+        assert_eq!(
+            paren_problem("sin((x)").as_deref(),
+            Some("missing 1 closing parenthesis")
+        );
+        assert_eq!(
+            paren_problem("x)").as_deref(),
+            Some("unmatched ')' at position 2")
+        );
+        assert_eq!(missing_closing_parens("ln(abs(x"), 2);
+    }
+}
@@ -0,0 +1,446 @@
+//! Session files: the user's tabs and functions as JSON, used for the autosave.
+
+use crate::complex::ComplexPart;
+use crate::{
+    App, Constant, CurveTransform, FunctionGroup, FunctionPlot, Interpolation, ParametricInputs,
+    PlotMode, PlotTab, PolarInputs, Sampling,
+};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Everything needed to rebuild the open tabs.
+#[derive(Serialize, Deserialize)]
+pub struct Session {
+    tabs: Vec<TabState>,
+    active_tab: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TabState {
+    title: String,
+    x_min: String,
+    x_max: String,
+    y_min: String,
+    y_max: String,
+    num_points: usize,
+    #[serde(default)]
+    log_sampling: bool,
+    /// `[start, step, stop]` when sampling an explicit grid.
+    #[serde(default)]
+    explicit_grid: Option<[String; 3]>,
+    plot_padding: f64,
+    radix_literals: bool,
+    #[serde(default)]
+    rpn: bool,
+    /// Missing from sessions saved before the variable could be renamed.
+    #[serde(default = "default_variable")]
+    variable: String,
+    functions: Vec<FunctionState>,
+    constants: Vec<ConstantState>,
+    #[serde(default)]
+    groups: Vec<GroupState>,
+    #[serde(default)]
+    guides: Vec<f64>,
+}
+
+fn default_variable() -> String {
+    "x".to_owned()
+}
+
+#[derive(Serialize, Deserialize)]
+struct FunctionState {
+    /// One of "cartesian", "parametric" or "polar".
+    #[serde(default)]
+    mode: String,
+    expression: String,
+    /// `[x, y, t_min, t_max]` of the parametric inputs.
+    #[serde(default)]
+    parametric: Option<[String; 4]>,
+    /// `[r, theta_min, theta_max]` of the polar inputs.
+    #[serde(default)]
+    polar: Option<[String; 3]>,
+    /// sRGBA, unmultiplied.
+    color: [u8; 4],
+    #[serde(default)]
+    color_locked: bool,
+    /// Palette entry the color comes from; older sessions use the position.
+    #[serde(default)]
+    palette_slot: Option<usize>,
+    uncertainty: Option<String>,
+    plot_inverse: bool,
+    show_asymptotes: bool,
+    expanded: bool,
+    /// `[h, k, a, b]` of the curve transform.
+    transform: [f64; 4],
+    decimate: bool,
+    #[serde(default)]
+    interpolation: InterpolationState,
+    /// Written before step interpolation existed; `true` means smooth.
+    #[serde(default, skip_serializing)]
+    smooth: bool,
+    #[serde(default)]
+    secondary_axis: bool,
+    #[serde(default)]
+    close_curve: bool,
+    /// `[variable, values]` when drawing slices.
+    #[serde(default)]
+    slices: Option<[String; 2]>,
+    #[serde(default)]
+    group: String,
+    #[serde(default)]
+    rigorous: bool,
+    /// One of "modulus", "argument", "real" or "imaginary" in complex mode.
+    #[serde(default)]
+    complex: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+enum InterpolationState {
+    #[default]
+    Straight,
+    Smooth,
+    StepHorizontal,
+    StepVertical,
+}
+
+#[derive(Serialize, Deserialize)]
+struct GroupState {
+    name: String,
+    visible: bool,
+    collapsed: bool,
+    /// sRGBA, unmultiplied.
+    tint: Option<[u8; 4]>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ConstantState {
+    name: String,
+    value: f64,
+    min: f64,
+    max: f64,
+}
+
+impl Session {
+    /// Capture the user-editable state of every tab.
+    pub fn capture(app: &App) -> Self {
+        Self {
+            tabs: app.tabs.iter().map(TabState::capture).collect(),
+            active_tab: app.active_tab,
+        }
+    }
+
+    /// Replace the app's tabs with the saved ones and recompute their curves.
+    pub fn restore(self, app: &mut App) {
+        if self.tabs.is_empty() {
+            return;
+        }
+        app.tabs = self
+            .tabs
+            .into_iter()
+            .enumerate()
+            .map(|(id, tab)| tab.restore(id as u64))
+            .collect();
+        app.next_tab_id = app.tabs.len() as u64;
+        app.active_tab = self.active_tab.min(app.tabs.len() - 1);
+        for tab in &mut app.tabs {
+            tab.resample();
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        // Plain data with string keys always serializes.
+        serde_json::to_string_pretty(self).expect("session serializes to JSON")
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        serde_json::from_str(text).map_err(|e| format!("Invalid session file: {}", e))
+    }
+}
+
+impl TabState {
+    fn capture(tab: &PlotTab) -> Self {
+        Self {
+            title: tab.plot_title.clone(),
+            x_min: tab.x_min_input.clone(),
+            x_max: tab.x_max_input.clone(),
+            y_min: tab.y_min_input.clone(),
+            y_max: tab.y_max_input.clone(),
+            num_points: tab.num_points,
+            log_sampling: tab.sampling == Sampling::Log,
+            explicit_grid: tab.explicit_grid.then(|| {
+                [
+                    tab.grid_start_input.clone(),
+                    tab.grid_step_input.clone(),
+                    tab.grid_stop_input.clone(),
+                ]
+            }),
+            plot_padding: tab.plot_padding,
+            radix_literals: tab.parse_options.radix_literals,
+            rpn: tab.parse_options.rpn,
+            variable: tab.parse_options.variable.clone(),
+            functions: tab.functions.iter().map(FunctionState::capture).collect(),
+            constants: tab
+                .constants
+                .iter()
+                .map(|c| ConstantState {
+                    name: c.name.clone(),
+                    value: c.value,
+                    min: c.min,
+                    max: c.max,
+                })
+                .collect(),
+            groups: tab
+                .groups
+                .iter()
+                .map(|g| GroupState {
+                    name: g.name.clone(),
+                    visible: g.visible,
+                    collapsed: g.collapsed,
+                    tint: g.tint.map(|c| c.to_srgba_unmultiplied()),
+                })
+                .collect(),
+            guides: tab.guides.clone(),
+        }
+    }
+
+    fn restore(self, id: u64) -> PlotTab {
+        let mut tab = PlotTab::new(id);
+        tab.plot_title = self.title;
+        tab.x_min_input = self.x_min;
+        tab.x_max_input = self.x_max;
+        tab.y_min_input = self.y_min;
+        tab.y_max_input = self.y_max;
+        tab.num_points = self.num_points;
+        if self.log_sampling {
+            tab.sampling = Sampling::Log;
+        }
+        if let Some([start, step, stop]) = self.explicit_grid {
+            tab.explicit_grid = true;
+            tab.grid_start_input = start;
+            tab.grid_step_input = step;
+            tab.grid_stop_input = stop;
+        }
+        tab.plot_padding = self.plot_padding;
+        tab.parse_options.radix_literals = self.radix_literals;
+        tab.parse_options.rpn = self.rpn;
+        tab.parse_options.variable = self.variable;
+        tab.functions = self
+            .functions
+            .into_iter()
+            .enumerate()
+            .map(|(i, f)| f.restore(i))
+            .collect();
+        tab.constants = self
+            .constants
+            .into_iter()
+            .map(|c| Constant {
+                name: c.name,
+                value: c.value,
+                min: c.min,
+                max: c.max,
+            })
+            .collect();
+        tab.guides = self.guides;
+        tab.groups = self
+            .groups
+            .into_iter()
+            .map(|g| FunctionGroup {
+                name: g.name,
+                visible: g.visible,
+                collapsed: g.collapsed,
+                tint: g
+                    .tint
+                    .map(|[r, g, b, a]| egui::Color32::from_rgba_unmultiplied(r, g, b, a)),
+            })
+            .collect();
+        tab
+    }
+}
+
+impl FunctionState {
+    fn capture(f: &FunctionPlot) -> Self {
+        let t = f.transform;
+        let p = &f.parametric;
+        Self {
+            mode: match f.mode {
+                PlotMode::Cartesian => "cartesian",
+                PlotMode::Parametric => "parametric",
+                PlotMode::Polar => "polar",
+            }
+            .to_owned(),
+            expression: f.expression.clone(),
+            parametric: Some([p.x.clone(), p.y.clone(), p.t_min.clone(), p.t_max.clone()]),
+            polar: Some([
+                f.polar.r.clone(),
+                f.polar.theta_min.clone(),
+                f.polar.theta_max.clone(),
+            ]),
+            color: f.color.to_srgba_unmultiplied(),
+            color_locked: f.color_locked,
+            palette_slot: Some(f.palette_slot),
+            uncertainty: f.uncertainty_expr.clone(),
+            plot_inverse: f.plot_inverse,
+            show_asymptotes: f.show_asymptotes,
+            expanded: f.expanded,
+            transform: [t.h, t.k, t.a, t.b],
+            decimate: f.decimate,
+            interpolation: match f.interpolation {
+                Interpolation::Straight => InterpolationState::Straight,
+                Interpolation::Smooth => InterpolationState::Smooth,
+                Interpolation::StepHorizontal => InterpolationState::StepHorizontal,
+                Interpolation::StepVertical => InterpolationState::StepVertical,
+            },
+            smooth: false,
+            secondary_axis: f.secondary_axis,
+            close_curve: f.close_curve,
+            slices: f
+                .slice_enabled
+                .then(|| [f.slice_variable.clone(), f.slice_values.clone()]),
+            group: f.group.clone(),
+            rigorous: f.rigorous,
+            complex: f.complex_mode.then(|| {
+                match f.complex_part {
+                    ComplexPart::Modulus => "modulus",
+                    ComplexPart::Argument => "argument",
+                    ComplexPart::Real => "real",
+                    ComplexPart::Imaginary => "imaginary",
+                }
+                .to_owned()
+            }),
+        }
+    }
+
+    fn restore(self, index: usize) -> FunctionPlot {
+        let [r, g, b, a] = self.color;
+        let [h, k, scale_x, scale_y] = self.transform;
+        let mut f = FunctionPlot::new(
+            &self.expression,
+            egui::Color32::from_rgba_unmultiplied(r, g, b, a),
+        );
+        f.mode = match self.mode.as_str() {
+            "parametric" => PlotMode::Parametric,
+            "polar" => PlotMode::Polar,
+            _ => PlotMode::Cartesian,
+        };
+        if let Some([x, y, t_min, t_max]) = self.parametric {
+            f.parametric = ParametricInputs { x, y, t_min, t_max };
+        }
+        if let Some([r, theta_min, theta_max]) = self.polar {
+            f.polar = PolarInputs {
+                r,
+                theta_min,
+                theta_max,
+            };
+        }
+        f.color_locked = self.color_locked;
+        f.palette_slot = self.palette_slot.unwrap_or(index);
+        f.uncertainty_expr = self.uncertainty;
+        f.plot_inverse = self.plot_inverse;
+        f.show_asymptotes = self.show_asymptotes;
+        f.expanded = self.expanded;
+        f.transform = CurveTransform {
+            h,
+            k,
+            a: scale_x,
+            b: scale_y,
+        };
+        f.decimate = self.decimate;
+        f.interpolation = match self.interpolation {
+            _ if self.smooth => Interpolation::Smooth,
+            InterpolationState::Straight => Interpolation::Straight,
+            InterpolationState::Smooth => Interpolation::Smooth,
+            InterpolationState::StepHorizontal => Interpolation::StepHorizontal,
+            InterpolationState::StepVertical => Interpolation::StepVertical,
+        };
+        f.secondary_axis = self.secondary_axis;
+        f.close_curve = self.close_curve;
+        if let Some([variable, values]) = self.slices {
+            f.slice_enabled = true;
+            f.slice_variable = variable;
+            f.slice_values = values;
+        }
+        f.group = self.group;
+        f.rigorous = self.rigorous;
+        if let Some(part) = self.complex {
+            f.complex_mode = true;
+            f.complex_part = match part.as_str() {
+                "argument" => ComplexPart::Argument,
+                "real" => ComplexPart::Real,
+                "imaginary" => ComplexPart::Imaginary,
+                _ => ComplexPart::Modulus,
+            };
+        }
+        f
+    }
+}
+
+/// Where the autosave lives, inside the platform's config directory.
+pub fn autosave_path() -> Option<PathBuf> {
+    directories::ProjectDirs::from("", "", "function_plotter_gui")
+        .map(|dirs| dirs.config_dir().join("session.json"))
+}
+
+/// Read the autosaved session, if there is a readable, valid one.
+pub fn load_autosave() -> Option<Session> {
+    let text = std::fs::read_to_string(autosave_path()?).ok()?;
+    Session::from_json(&text).ok()
+}
+
+/// Write the session to the autosave location, creating the directory if needed.
+pub fn write_autosave(session: &Session) -> Result<(), String> {
+    let path = autosave_path().ok_or("No config directory available")?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    std::fs::write(&path, session.to_json())
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_keeps_functions() {
+        let mut app = App::default();
+        app.tabs[0].plot_title = "Demo".to_owned();
+        app.tabs[0].functions[1].transform.h = 2.0;
+        app.tabs[0].guides.push(1.5);
+        let json = Session::capture(&app).to_json();
+
+        let mut restored = App::default();
+        Session::from_json(&json).unwrap().restore(&mut restored);
+        let tab = &restored.tabs[0];
+        assert_eq!(tab.plot_title, "Demo");
+        assert_eq!(tab.functions.len(), 2);
+        assert_eq!(tab.functions[1].expression, "sin(x)");
+        assert_eq!(tab.functions[1].transform.h, 2.0);
+        assert_eq!(tab.functions[1].color, app.tabs[0].functions[1].color);
+        assert_eq!(tab.guides, vec![1.5]);
+    }
+
+    #[test]
+    fn old_smooth_flag_loads_as_smooth_interpolation() {
+        let json = Session::capture(&App::default()).to_json();
+        let mut value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let function = &mut value["tabs"][0]["functions"][0];
+        function.as_object_mut().unwrap().remove("interpolation");
+        function["smooth"] = serde_json::Value::Bool(true);
+
+        let mut restored = App::default();
+        Session::from_json(&value.to_string())
+            .unwrap()
+            .restore(&mut restored);
+        let functions = &restored.tabs[0].functions;
+        assert!(functions[0].interpolation == Interpolation::Smooth);
+        assert!(functions[1].interpolation == Interpolation::Straight);
+    }
+
+    #[test]
+    fn corrupt_json_is_an_error() {
+        assert!(Session::from_json("{\"tabs\": [").is_err());
+        assert!(Session::from_json("not json").is_err());
+    }
+}
@@ -0,0 +1,193 @@
+//! Color themes read from JSON files: interface colors, plot colors and the
+//! palette given to new functions.
+
+use serde::Deserialize;
+use std::path::Path;
+
+/// A theme file as written by the user. Every color is optional and falls
+/// back to the light or dark built-in theme.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ThemeFile {
+    #[serde(default)]
+    name: String,
+    /// Start from the dark built-in theme instead of the light one.
+    #[serde(default)]
+    dark: bool,
+    /// Panels and windows.
+    background: Option<String>,
+    text: Option<String>,
+    /// Selections and highlighted widgets.
+    accent: Option<String>,
+    plot_background: Option<String>,
+    grid: Option<String>,
+    #[serde(default)]
+    palette: Vec<String>,
+}
+
+/// A validated theme, ready to apply.
+pub struct Theme {
+    pub name: String,
+    pub visuals: egui::Visuals,
+    /// Color of the gridlines drawn by the app itself (egui derives its own
+    /// gridlines from the text and plot background colors).
+    pub grid: Option<egui::Color32>,
+    /// Colors for new functions, in order; empty keeps the built-in palette.
+    pub palette: Vec<egui::Color32>,
+}
+
+/// Parses a `#rrggbb` or `#rrggbbaa` color.
+pub fn parse_color(text: &str) -> Result<egui::Color32, String> {
+    let digits = text.trim().strip_prefix('#').unwrap_or(text.trim());
+    let invalid = || format!("invalid color '{}', expected #rrggbb", text);
+    // from_str_radix would also take a sign, as in "+f".
+    if !(digits.len() == 6 || digits.len() == 8) || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+    let channel = |i: usize| u8::from_str_radix(&digits[i..i + 2], 16).map_err(|_| invalid());
+    let alpha = if digits.len() == 8 { channel(6)? } else { 255 };
+    Ok(egui::Color32::from_rgba_unmultiplied(
+        channel(0)?,
+        channel(2)?,
+        channel(4)?,
+        alpha,
+    ))
+}
+
+/// Parses and validates the JSON text of a theme file.
+pub fn parse_theme(text: &str) -> Result<Theme, String> {
+    let file: ThemeFile =
+        serde_json::from_str(text).map_err(|e| format!("Invalid theme file: {}", e))?;
+    let color = |field: &str, value: &Option<String>| {
+        value
+            .as_deref()
+            .map(parse_color)
+            .transpose()
+            .map_err(|e| format!("{}: {}", field, e))
+    };
+
+    let mut visuals = if file.dark {
+        egui::Visuals::dark()
+    } else {
+        crate::light_visuals()
+    };
+    if let Some(background) = color("background", &file.background)? {
+        visuals.panel_fill = background;
+        visuals.window_fill = background;
+    }
+    if let Some(text) = color("text", &file.text)? {
+        visuals.override_text_color = Some(text);
+    }
+    if let Some(accent) = color("accent", &file.accent)? {
+        visuals.selection.bg_fill = accent;
+        visuals.hyperlink_color = accent;
+        visuals.widgets.hovered.bg_fill = accent.gamma_multiply(0.5);
+        visuals.widgets.active.bg_fill = accent;
+    }
+    if let Some(plot_background) = color("plot_background", &file.plot_background)? {
+        // egui fills plots (and text fields) with the extreme background color.
+        visuals.extreme_bg_color = plot_background;
+    }
+    let palette = file
+        .palette
+        .iter()
+        .enumerate()
+        .map(|(i, value)| parse_color(value).map_err(|e| format!("palette[{}]: {}", i, e)))
+        .collect::<Result<_, _>>()?;
+    Ok(Theme {
+        name: file.name,
+        visuals,
+        grid: color("grid", &file.grid)?,
+        palette,
+    })
+}
+
+/// Name of the theme returned by `high_contrast`.
+pub const HIGH_CONTRAST: &str = "High contrast";
+
+/// Bright colors for functions on the high-contrast theme's black background,
+/// each with a contrast ratio above 7 against it.
+const HIGH_CONTRAST_PALETTE: [egui::Color32; 8] = [
+    egui::Color32::from_rgb(255, 255, 0),   // Yellow
+    egui::Color32::from_rgb(0, 255, 255),   // Cyan
+    egui::Color32::from_rgb(255, 128, 255), // Light magenta
+    egui::Color32::from_rgb(0, 255, 0),     // Lime
+    egui::Color32::from_rgb(255, 165, 0),   // Orange
+    egui::Color32::WHITE,
+    egui::Color32::from_rgb(135, 206, 250), // LightSkyBlue
+    egui::Color32::from_rgb(255, 160, 160), // Light red
+];
+
+/// The built-in accessibility theme for low-vision users: white text and
+/// bright curves on pure black. The app also thickens lines and enlarges text
+/// while it is in use.
+pub fn high_contrast() -> Theme {
+    let mut visuals = egui::Visuals::dark();
+    visuals.override_text_color = Some(egui::Color32::WHITE);
+    visuals.panel_fill = egui::Color32::BLACK;
+    visuals.window_fill = egui::Color32::BLACK;
+    visuals.extreme_bg_color = egui::Color32::BLACK;
+    visuals.faint_bg_color = egui::Color32::from_gray(24);
+    visuals.window_stroke = egui::Stroke::new(2.0, egui::Color32::WHITE);
+    for widget in [
+        &mut visuals.widgets.noninteractive,
+        &mut visuals.widgets.inactive,
+        &mut visuals.widgets.hovered,
+        &mut visuals.widgets.active,
+        &mut visuals.widgets.open,
+    ] {
+        widget.bg_fill = egui::Color32::BLACK;
+        widget.weak_bg_fill = egui::Color32::BLACK;
+        widget.bg_stroke = egui::Stroke::new(1.5, egui::Color32::WHITE);
+        widget.fg_stroke = egui::Stroke::new(1.5, egui::Color32::WHITE);
+    }
+    visuals.widgets.hovered.bg_stroke = egui::Stroke::new(2.5, egui::Color32::YELLOW);
+    visuals.widgets.active.bg_stroke = egui::Stroke::new(2.5, egui::Color32::YELLOW);
+    visuals.selection.bg_fill = egui::Color32::from_rgb(0, 0, 160);
+    visuals.selection.stroke = egui::Stroke::new(2.0, egui::Color32::YELLOW);
+    visuals.hyperlink_color = egui::Color32::from_rgb(0, 255, 255);
+    visuals.warn_fg_color = egui::Color32::YELLOW;
+    visuals.error_fg_color = egui::Color32::from_rgb(255, 110, 110);
+    Theme {
+        name: HIGH_CONTRAST.to_owned(),
+        visuals,
+        grid: Some(egui::Color32::from_gray(140)),
+        palette: HIGH_CONTRAST_PALETTE.to_vec(),
+    }
+}
+
+/// Reads a theme file, see `parse_theme`.
+pub fn load_theme(path: &Path) -> Result<Theme, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    parse_theme(&text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn themes_are_validated() {
+        let theme = parse_theme(
+            r##"{"name": "Brand", "dark": true, "plot_background": "#102030",
+                 "palette": ["#ff0000", "#00ff0080"]}"##,
+        )
+        .unwrap();
+        assert_eq!(theme.name, "Brand");
+        assert!(theme.visuals.dark_mode);
+        assert_eq!(
+            theme.visuals.extreme_bg_color,
+            egui::Color32::from_rgb(0x10, 0x20, 0x30)
+        );
+        assert_eq!(theme.palette.len(), 2);
+        assert_eq!(theme.palette[0], egui::Color32::RED);
+
+        assert_eq!(
+            parse_theme(r#"{"palette": ["red"]}"#).err().as_deref(),
+            Some("palette[0]: invalid color 'red', expected #rrggbb")
+        );
+        assert!(parse_theme(r##"{"backgrund": "#000000"}"##).is_err());
+        assert!(parse_color("#+f+f+f").is_err());
+    }
+}
@@ -0,0 +1,70 @@
+//! Watching an expressions file so that the plot follows edits made in another editor.
+
+use notify::{RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+
+/// Seconds the file must stay unchanged after a change before it is read;
+/// editors often save in several steps (truncate, write, rename).
+pub const DEBOUNCE: f64 = 0.3;
+
+/// A file being watched for changes. Dropping it stops the watch.
+pub struct FileWatch {
+    pub path: PathBuf,
+    _watcher: notify::RecommendedWatcher,
+    changes: mpsc::Receiver<()>,
+    /// When the latest change not yet reported was noticed.
+    pending: Option<f64>,
+}
+
+impl FileWatch {
+    pub fn new(path: &Path) -> Result<Self, String> {
+        let name = path
+            .file_name()
+            .ok_or_else(|| format!("{} is not a file", path.display()))?
+            .to_owned();
+        // Saving by writing a new file and renaming it over the old one replaces
+        // what a watch on the file itself would follow, so the directory is
+        // watched and its events filtered by name.
+        let directory = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let (sender, changes) = mpsc::channel();
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                let Ok(event) = event else { return };
+                if !event.kind.is_access()
+                    && event.paths.iter().any(|p| p.file_name() == Some(&name))
+                {
+                    // Nothing is listening any more once the watch is dropped.
+                    let _ = sender.send(());
+                }
+            })
+            .map_err(|e| format!("Cannot watch {}: {}", path.display(), e))?;
+        watcher
+            .watch(&directory, RecursiveMode::NonRecursive)
+            .map_err(|e| format!("Cannot watch {}: {}", path.display(), e))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            _watcher: watcher,
+            changes,
+            pending: None,
+        })
+    }
+
+    /// Whether the file changed and has then been left alone for `DEBOUNCE`
+    /// seconds as of `now`; reported once per burst of changes.
+    pub fn changed(&mut self, now: f64) -> bool {
+        while self.changes.try_recv().is_ok() {
+            self.pending = Some(now);
+        }
+        match self.pending {
+            Some(since) if now - since >= DEBOUNCE => {
+                self.pending = None;
+                true
+            }
+            _ => false,
+        }
+    }
+}